@@ -12,14 +12,15 @@ import {
   Descriptions,
   Tag,
   InputNumber,
+  Modal,
   message
 } from 'antd';
 import type { ColumnsType } from 'antd/es/table';
 import { ReloadOutlined } from '@ant-design/icons';
 import { Prism as SyntaxHighlighter } from 'react-syntax-highlighter';
 import { oneDark } from 'react-syntax-highlighter/dist/esm/styles/prism';
-import { apiGet } from './api';
-import type { ChangeRecord, FileResponse, StatusResponse } from './types';
+import { apiGet, apiUrl } from './api';
+import type { ChangeRecord, FileAtResponse, FileResponse, StatusResponse } from './types';
 
 const { Header, Content } = Layout;
 const { Title, Text } = Typography;
@@ -57,10 +58,51 @@ function guessLanguage(path: string) {
   return map[ext] || 'text';
 }
 
+function formatPolicy(status: StatusResponse | null) {
+  const policy = status?.policy;
+  if (!policy) return '-';
+  const parts = [
+    policy.deny.length ? `deny: ${policy.deny.join(', ')}` : '',
+    policy.readonly.length ? `readonly: ${policy.readonly.join(', ')}` : '',
+    policy.writable.length ? `writable: ${policy.writable.join(', ')}` : ''
+  ].filter(Boolean);
+  return parts.length ? parts.join(' | ') : 'None';
+}
+
+function formatBytes(value: number) {
+  if (value < 1024) return `${value} B`;
+  if (value < 1024 * 1024) return `${(value / 1024).toFixed(1)} KB`;
+  return `${(value / 1024 / 1024).toFixed(1)} MB`;
+}
+
+function formatStats(status: StatusResponse | null) {
+  const stats = status?.change_log;
+  if (!stats) return '-';
+  return `${stats.changes} changes, ${stats.blobs} blobs (${formatBytes(stats.blob_bytes)}), ${stats.packed_diffs} compressed diffs | file ${formatBytes(stats.db_bytes)}, ${formatBytes(stats.free_bytes)} free`;
+}
+
+function formatSearchIndex(status: StatusResponse | null) {
+  const index = status?.search_index;
+  if (!index) return 'Disabled';
+  return `${index.files} files (${index.text_files} text, ${formatBytes(index.bytes)}) | last indexed ${formatDate(index.indexed_at || undefined) || '-'}`;
+}
+
+function renderPath(path: string, record: ChangeRecord) {
+  return record.source_path ? `${record.source_path} → ${path}` : path;
+}
+
 function actionColor(action: string) {
   if (action === 'delete') return 'red';
   if (action === 'append') return 'blue';
   if (action === 'write') return 'green';
+  if (action === 'edit') return 'cyan';
+  if (action === 'revert') return 'orange';
+  if (action === 'restore') return 'gold';
+  if (action === 'format') return 'magenta';
+  if (action === 'external') return 'volcano';
+  if (action === 'move') return 'purple';
+  if (action === 'copy') return 'geekblue';
+  if (action === 'mkdir') return 'lime';
   return 'default';
 }
 
@@ -81,6 +123,7 @@ export default function App() {
   const [fileDetail, setFileDetail] = useState<FileResponse | null>(null);
   const [fileChanges, setFileChanges] = useState<ChangeRecord[]>([]);
   const [fileLoading, setFileLoading] = useState(false);
+  const [fileAt, setFileAt] = useState<FileAtResponse | null>(null);
 
   const refreshStatus = async () => {
     setStatusLoading(true);
@@ -94,6 +137,13 @@ export default function App() {
     }
   };
 
+  const exportUrl = (format: 'patch' | 'jsonl') => {
+    const params = new URLSearchParams({ format });
+    if (sessionFilter) params.set('session_id', sessionFilter);
+    if (runFilter) params.set('run_id', runFilter);
+    return apiUrl(`/api/export?${params.toString()}`);
+  };
+
   const refreshChanges = async () => {
     setChangesLoading(true);
     try {
@@ -132,6 +182,16 @@ export default function App() {
     }
   };
 
+  const openFileAt = async (record: ChangeRecord) => {
+    try {
+      const params = new URLSearchParams({ path: selectedPath || record.path, change_id: record.id });
+      const data = await apiGet<FileAtResponse>(`/api/file_at?${params.toString()}`);
+      setFileAt(data);
+    } catch (err) {
+      message.error(String(err));
+    }
+  };
+
   useEffect(() => {
     refreshStatus();
     refreshChanges();
@@ -139,7 +199,7 @@ export default function App() {
 
   const changeColumns: ColumnsType<ChangeRecord> = [
     { title: 'Time', dataIndex: 'created_at', key: 'created_at', render: (value) => formatDate(value) },
-    { title: 'Path', dataIndex: 'path', key: 'path' },
+    { title: 'Path', dataIndex: 'path', key: 'path', render: (value, record) => renderPath(value, record) },
     {
       title: 'Action',
       dataIndex: 'action',
@@ -158,13 +218,23 @@ export default function App() {
       title: 'Action',
       dataIndex: 'action',
       key: 'action',
-      render: (value) => <Tag color={actionColor(value)}>{value}</Tag>
+      render: (value, record) => (
+        <Space>
+          <Tag color={actionColor(value)}>{value}</Tag>
+          {record.source_path ? <Text type="secondary">{renderPath(record.path, record)}</Text> : null}
+        </Space>
+      )
     },
     { title: 'Bytes', dataIndex: 'bytes', key: 'bytes' },
     { title: 'Session', dataIndex: 'session_id', key: 'session_id' },
     { title: 'Run', dataIndex: 'run_id', key: 'run_id' },
     { title: 'SHA256', dataIndex: 'sha256', key: 'sha256' },
-    { title: 'Diff', dataIndex: 'diff', key: 'diff', render: (value) => value ? <Tag color="geekblue">diff</Tag> : <Text type="secondary">-</Text> }
+    { title: 'Diff', dataIndex: 'diff', key: 'diff', render: (value) => value ? <Tag color="geekblue">diff</Tag> : <Text type="secondary">-</Text> },
+    {
+      title: 'Content',
+      key: 'content',
+      render: (_, record) => <Button size="small" onClick={() => openFileAt(record)}>View</Button>
+    }
   ];
 
   const language = useMemo(() => (fileDetail ? guessLanguage(fileDetail.path) : 'text'), [fileDetail]);
@@ -190,7 +260,10 @@ export default function App() {
                 { key: 'db', label: 'DB', children: status?.db_path || '-' },
                 { key: 'writes', label: 'Writes', children: status?.allow_writes ? 'Enabled' : 'Disabled' },
                 { key: 'session', label: 'Session', children: status?.session_id || '-' },
-                { key: 'run', label: 'Run', children: status?.run_id || '-' }
+                { key: 'run', label: 'Run', children: status?.run_id || '-' },
+                { key: 'policy', label: 'Path Policy', span: 2, children: formatPolicy(status) },
+                { key: 'change_log', label: 'Change Log DB', span: 2, children: formatStats(status) },
+                { key: 'search_index', label: 'Search Index', span: 2, children: formatSearchIndex(status) }
               ]}
             />
           </Card>
@@ -223,8 +296,16 @@ export default function App() {
                   options={[
                     { value: '', label: 'All Actions' },
                     { value: 'write', label: 'write' },
+                    { value: 'edit', label: 'edit' },
                     { value: 'append', label: 'append' },
-                    { value: 'delete', label: 'delete' }
+                    { value: 'delete', label: 'delete' },
+                    { value: 'move', label: 'move' },
+                    { value: 'copy', label: 'copy' },
+                    { value: 'mkdir', label: 'mkdir' },
+                    { value: 'revert', label: 'revert' },
+                    { value: 'restore', label: 'restore' },
+                    { value: 'format', label: 'format' },
+                    { value: 'external', label: 'external' }
                   ]}
                 />
                 <Space>
@@ -232,6 +313,8 @@ export default function App() {
                   <InputNumber min={1} max={1000} value={limit} onChange={(value) => setLimit(value || 200)} />
                 </Space>
                 <Button onClick={refreshChanges} loading={changesLoading}>Refresh</Button>
+                <Button href={exportUrl('patch')}>Export .patch</Button>
+                <Button href={exportUrl('jsonl')}>Export JSONL</Button>
               </Space>
               <Table
                 rowKey="id"
@@ -314,6 +397,28 @@ export default function App() {
           </Space>
         ) : null}
       </Drawer>
+
+      <Modal
+        title={fileAt ? `${fileAt.path} @ ${formatDate(fileAt.as_of)}` : ''}
+        width={900}
+        open={!!fileAt}
+        footer={null}
+        onCancel={() => setFileAt(null)}
+      >
+        {fileAt?.content !== undefined ? (
+          <SyntaxHighlighter language={guessLanguage(fileAt.path)} style={oneDark} showLineNumbers wrapLongLines>
+            {fileAt.content}
+          </SyntaxHighlighter>
+        ) : (
+          <Text type="secondary">
+            {fileAt?.exists === false
+              ? 'File did not exist at this point.'
+              : fileAt?.content_base64
+                ? `Binary file (${fileAt.size_bytes} bytes).`
+                : fileAt?.reason || 'No content recorded.'}
+          </Text>
+        )}
+      </Modal>
     </Layout>
   );
 }
@@ -15,6 +15,10 @@ export async function apiGet<T>(path: string): Promise<T> {
   return data as T;
 }
 
+export function apiUrl(path: string): string {
+  return `${API_BASE}${path}`;
+}
+
 async function safeJson(res: Response): Promise<any> {
   const text = await res.text();
   if (!text) return {};
@@ -8,6 +8,9 @@ export interface ChangeRecord {
   session_id: string;
   run_id: string;
   created_at: string;
+  before_sha256?: string | null;
+  after_sha256?: string | null;
+  source_path?: string | null;
 }
 
 export interface StatusResponse {
@@ -19,10 +22,36 @@ export interface StatusResponse {
   max_file_bytes: number;
   max_write_bytes: number;
   search_limit: number;
+  policy?: PathPolicy;
+  change_log?: ChangeLogStats;
+  search_index?: SearchIndexStats | null;
   session_id: string;
   run_id: string;
 }
 
+export interface ChangeLogStats {
+  changes: number;
+  blobs: number;
+  blob_bytes: number;
+  packed_diffs: number;
+  plain_diffs: number;
+  db_bytes: number;
+  free_bytes: number;
+}
+
+export interface SearchIndexStats {
+  files: number;
+  text_files: number;
+  bytes: number;
+  indexed_at?: string | null;
+}
+
+export interface PathPolicy {
+  deny: string[];
+  readonly: string[];
+  writable: string[];
+}
+
 export interface FileResponse {
   ok: boolean;
   path: string;
@@ -30,3 +59,15 @@ export interface FileResponse {
   sha256: string;
   content: string;
 }
+
+export interface FileAtResponse {
+  ok: boolean;
+  path: string;
+  as_of: string;
+  exists: boolean | null;
+  reason?: string;
+  size_bytes?: number;
+  sha256?: string;
+  content?: string;
+  content_base64?: string;
+}
@@ -1,4 +1,8 @@
 use crate::fs_ops::FsOps;
+use crate::export::{export_changes, ExportFormat};
+use crate::history::{diff_between, file_at, parse_as_of};
+use crate::policy::PathPolicy;
+use crate::search_index::SearchIndex;
 use crate::storage::{ChangeLogStore, ChangeQuery};
 use serde_json::{json, Value};
 use std::collections::HashMap;
@@ -6,6 +10,7 @@ use std::fs;
 use std::io::{BufRead, BufReader, Read, Write};
 use std::net::{TcpListener, TcpStream};
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 #[derive(Clone)]
 pub struct AdminServerOptions {
@@ -16,6 +21,9 @@ pub struct AdminServerOptions {
     pub max_file_bytes: i64,
     pub max_write_bytes: i64,
     pub search_limit: usize,
+    pub policy: Arc<PathPolicy>,
+    pub allowed_external: Arc<Vec<PathBuf>>,
+    pub search_index: bool,
     pub session_id: String,
     pub run_id: String,
     pub host: String,
@@ -113,6 +121,12 @@ fn handle_api(
     options: &AdminServerOptions,
 ) -> Result<(), String> {
     if method == "GET" && path == "/api/status" {
+        let change_log = ChangeLogStore::new(&options.db_path)?.stats()?;
+        let search_index = if options.search_index {
+            Some(SearchIndex::open(&options.db_path, &options.root)?.stats()?)
+        } else {
+            None
+        };
         return send_json(
             stream,
             200,
@@ -125,6 +139,9 @@ fn handle_api(
                 "max_file_bytes": options.max_file_bytes,
                 "max_write_bytes": options.max_write_bytes,
                 "search_limit": options.search_limit,
+                "policy": options.policy.rules(),
+                "change_log": change_log,
+                "search_index": search_index,
                 "session_id": options.session_id,
                 "run_id": options.run_id
             }),
@@ -174,7 +191,9 @@ fn handle_api(
             options.max_file_bytes,
             options.max_write_bytes,
             options.search_limit,
-        );
+        )
+        .with_policy(options.policy.clone())
+        .with_allowed_external(options.allowed_external.clone());
         let (path, size, sha256, content) = fs_ops.read_file_raw(&rel_path)?;
         return send_json(
             stream,
@@ -189,6 +208,65 @@ fn handle_api(
         );
     }
 
+    if method == "GET" && path == "/api/file_at" {
+        let rel_path = query
+            .get("path")
+            .ok_or("path is required".to_string())?;
+        let fs_ops = FsOps::new(
+            options.root.clone(),
+            false,
+            options.max_file_bytes,
+            options.max_write_bytes,
+            options.search_limit,
+        )
+        .with_policy(options.policy.clone())
+        .with_allowed_external(options.allowed_external.clone());
+        let target = fs_ops.resolve_path(rel_path)?;
+        let at = parse_as_of(
+            query.get("change_id").map(String::as_str),
+            query.get("timestamp").map(String::as_str),
+        )?;
+        let store = ChangeLogStore::new(&options.db_path)?;
+        let mut result = file_at(&store, &fs_ops.relative(&target), &at)?;
+        result["ok"] = json!(true);
+        return send_json(stream, 200, result);
+    }
+
+    if method == "GET" && path == "/api/diff_between" {
+        let change_a = query
+            .get("change_a")
+            .ok_or("change_a is required".to_string())?;
+        let change_b = query
+            .get("change_b")
+            .ok_or("change_b is required".to_string())?;
+        let store = ChangeLogStore::new(&options.db_path)?;
+        let mut result = diff_between(&store, change_a, change_b)?;
+        result["ok"] = json!(true);
+        return send_json(stream, 200, result);
+    }
+
+    if method == "GET" && path == "/api/export" {
+        let format = ExportFormat::parse(query.get("format").map(String::as_str))?;
+        let session_id = query.get("session_id").map(String::as_str).filter(|v| !v.is_empty());
+        let run_id = query.get("run_id").map(String::as_str).filter(|v| !v.is_empty());
+        let store = ChangeLogStore::new(&options.db_path)?;
+        let export = export_changes(&store, session_id, run_id, format)?;
+        let name = session_id.or(run_id).unwrap_or("changes");
+        let safe_name: String = name
+            .chars()
+            .map(|ch| if ch.is_ascii_alphanumeric() || ch == '-' || ch == '_' { ch } else { '_' })
+            .collect();
+        let disposition = format!("attachment; filename=\"{safe_name}.{}\"", format.extension());
+        return send_response(
+            stream,
+            200,
+            format.content_type(),
+            &[("Content-Disposition", &disposition)],
+            export.content.as_bytes(),
+            false,
+        );
+    }
+
     let _payload = if !body.is_empty() {
         serde_json::from_slice::<Value>(&body).unwrap_or_else(|_| json!({}))
     } else {
@@ -290,15 +368,15 @@ fn content_type_for(path: &Path) -> String {
 
 fn send_json(stream: &mut TcpStream, code: u16, value: Value) -> Result<(), String> {
     let body = serde_json::to_string(&value).map_err(|err| err.to_string())?;
-    send_response(stream, code, "application/json; charset=utf-8", body.as_bytes(), false)
+    send_response(stream, code, "application/json; charset=utf-8", &[], body.as_bytes(), false)
 }
 
 fn send_text(stream: &mut TcpStream, code: u16, text: &str) -> Result<(), String> {
-    send_response(stream, code, "text/plain; charset=utf-8", text.as_bytes(), false)
+    send_response(stream, code, "text/plain; charset=utf-8", &[], text.as_bytes(), false)
 }
 
 fn send_empty(stream: &mut TcpStream, code: u16) -> Result<(), String> {
-    send_response(stream, code, "text/plain; charset=utf-8", &[], true)
+    send_response(stream, code, "text/plain; charset=utf-8", &[], &[], true)
 }
 
 fn send_bytes(
@@ -308,13 +386,14 @@ fn send_bytes(
     body: &[u8],
     head_only: bool,
 ) -> Result<(), String> {
-    send_response(stream, code, content_type, body, head_only)
+    send_response(stream, code, content_type, &[], body, head_only)
 }
 
 fn send_response(
     stream: &mut TcpStream,
     code: u16,
     content_type: &str,
+    extra_headers: &[(&str, &str)],
     body: &[u8],
     head_only: bool,
 ) -> Result<(), String> {
@@ -329,13 +408,17 @@ fn send_response(
         500 => "Internal Server Error",
         _ => "OK",
     };
-    let header = format!(
-        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nAccess-Control-Allow-Origin: *\r\nAccess-Control-Allow-Methods: GET,POST,OPTIONS\r\nAccess-Control-Allow-Headers: Content-Type\r\n\r\n",
+    let mut header = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nAccess-Control-Allow-Origin: *\r\nAccess-Control-Allow-Methods: GET,POST,OPTIONS\r\nAccess-Control-Allow-Headers: Content-Type\r\n",
         code,
         status_text,
         content_type,
         body.len()
     );
+    for (name, value) in extra_headers {
+        header.push_str(&format!("{name}: {value}\r\n"));
+    }
+    header.push_str("\r\n");
     stream.write_all(header.as_bytes()).map_err(|err| err.to_string())?;
     if !head_only {
         stream.write_all(body).map_err(|err| err.to_string())?;
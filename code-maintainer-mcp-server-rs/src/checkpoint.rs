@@ -0,0 +1,307 @@
+use crate::diff::snapshot_diff;
+use crate::fs_ops::FsOps;
+use crate::patch::{write_files, FileWrite};
+use crate::storage::{ChangeLogStore, ChangeRecord, CheckpointRecord, Snapshot};
+use crate::utils::{is_binary_buffer, sha256_bytes};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+
+#[derive(Debug, serde::Serialize)]
+pub struct CheckpointFileDiff {
+    pub path: String,
+    /// "added" (new since the checkpoint), "modified" or "deleted".
+    pub status: String,
+    /// False when the current file is binary, larger than the write limit or
+    /// unreadable: it is known to exist, not how it differs.
+    pub comparable: bool,
+    pub diff: Option<String>,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct RestoredFile {
+    pub path: String,
+    pub status: String,
+    pub change: Option<ChangeRecord>,
+}
+
+pub struct Checkpoints<'a> {
+    pub fs_ops: &'a FsOps,
+    pub change_log: &'a ChangeLogStore,
+    pub session_id: &'a str,
+    pub run_id: &'a str,
+}
+
+/// The workspace as a checkpoint sees it.
+struct Scan {
+    /// Tracked text files and their content hashes.
+    files: BTreeMap<String, String>,
+    /// Tracked files left out because they are binary, larger than the write
+    /// limit or unreadable.
+    skipped: BTreeSet<String>,
+    total_bytes: i64,
+}
+
+impl Checkpoints<'_> {
+    /// Snapshots every tracked text file under the root (ignore files and the
+    /// path policy apply; binary and oversized files are skipped).
+    pub fn create(&self, name: &str) -> Result<(CheckpointRecord, usize), String> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err("name is required".to_string());
+        }
+        if self.change_log.get_checkpoint(name)?.is_some() {
+            return Err(format!("Checkpoint already exists: {name}"));
+        }
+        let scan = self.scan(true)?;
+        let files: Vec<(String, String)> = scan.files.into_iter().collect();
+        let record = self.change_log.create_checkpoint(
+            name,
+            self.session_id,
+            self.run_id,
+            &files,
+            scan.total_bytes,
+        )?;
+        Ok((record, scan.skipped.len()))
+    }
+
+    /// Differences from the checkpoint to the current workspace.
+    pub fn diff(&self, name: &str, include_diff: bool) -> Result<Vec<CheckpointFileDiff>, String> {
+        let (_, saved) = self.load(name)?;
+        let current = self.scan(false)?;
+        let mut diffs = Vec::new();
+        for delta in compare(&saved, &current) {
+            let diff = if include_diff {
+                let before = self.change_log.load_snapshot(Some(&delta.saved))?;
+                let after = self.fs_ops.snapshot(&self.fs_ops.resolve_path(&delta.path)?);
+                snapshot_diff(&before, &after)
+            } else {
+                None
+            };
+            diffs.push(CheckpointFileDiff {
+                path: delta.path,
+                status: delta.status.to_string(),
+                comparable: delta.comparable,
+                diff,
+            });
+        }
+        Ok(diffs)
+    }
+
+    /// Writes every checkpointed file back and, unless `keep_new_files` is set,
+    /// deletes tracked files created since. Each touched file is logged as a
+    /// `restore` change. All paths are checked before anything is written, and
+    /// the files are written as one unit: a failed write rolls back the rest.
+    pub fn restore(&self, name: &str, keep_new_files: bool, dry_run: bool) -> Result<Vec<RestoredFile>, String> {
+        let (_, saved) = self.load(name)?;
+        let current = self.scan(false)?;
+        let plan: Vec<_> = compare(&saved, &current)
+            .into_iter()
+            .filter(|delta| !(keep_new_files && delta.status == "added"))
+            .collect();
+        let mut writes = Vec::new();
+        let mut befores = Vec::new();
+        for delta in &plan {
+            let saved = self.change_log.load_snapshot(Some(&delta.saved))?;
+            let target = match &saved {
+                Snapshot::Content(content) => self.fs_ops.check_write(&delta.path, content.len())?,
+                Snapshot::Missing => self.fs_ops.check_delete(&delta.path)?,
+                Snapshot::Unknown => {
+                    return Err(format!("Checkpoint content for {} is missing from the store.", delta.path));
+                }
+            };
+            if dry_run {
+                continue;
+            }
+            befores.push(self.fs_ops.snapshot(&target));
+            writes.push(FileWrite {
+                path: delta.path.clone(),
+                target,
+                content: match saved {
+                    Snapshot::Content(content) => Some(content),
+                    _ => None,
+                },
+            });
+        }
+        if dry_run {
+            return Ok(plan
+                .into_iter()
+                .map(|delta| RestoredFile {
+                    path: delta.path,
+                    status: delta.status.to_string(),
+                    change: None,
+                })
+                .collect());
+        }
+        write_files(&writes)?;
+
+        let mut restored = Vec::new();
+        for ((delta, write), before) in plan.into_iter().zip(writes).zip(befores) {
+            let after = match write.content {
+                Some(content) => Snapshot::Content(content),
+                None => Snapshot::Missing,
+            };
+            let (bytes, hash) = match &after {
+                Snapshot::Content(content) => (content.len() as i64, sha256_bytes(content)),
+                _ => (0, String::new()),
+            };
+            let record = self.change_log.log_change(
+                &delta.path,
+                "restore",
+                bytes,
+                &hash,
+                self.session_id,
+                self.run_id,
+                snapshot_diff(&before, &after),
+                &before,
+                &after,
+            )?;
+            restored.push(RestoredFile {
+                path: delta.path,
+                status: delta.status.to_string(),
+                change: Some(record),
+            });
+        }
+        Ok(restored)
+    }
+
+    fn load(&self, name: &str) -> Result<(CheckpointRecord, BTreeMap<String, String>), String> {
+        let record = self
+            .change_log
+            .get_checkpoint(name)?
+            .ok_or_else(|| format!("Checkpoint not found: {name}"))?;
+        let files = self.change_log.checkpoint_files(&record.id)?.into_iter().collect();
+        Ok((record, files))
+    }
+
+    /// Hashes the tracked text files under the root one at a time, storing
+    /// each in the blob store when `store` is set.
+    fn scan(&self, store: bool) -> Result<Scan, String> {
+        let root = self.fs_ops.resolve_path(".")?;
+        let max_bytes = self.fs_ops.max_write_bytes();
+        let mut scan = Scan {
+            files: BTreeMap::new(),
+            skipped: BTreeSet::new(),
+            total_bytes: 0,
+        };
+        for entry in self.fs_ops.walk(&root, &[], &[], true)?.filter_map(|e| e.ok()) {
+            if !entry.file_type().map(|t| t.is_file()).unwrap_or(false) {
+                continue;
+            }
+            let path = self.fs_ops.relative(entry.path());
+            let size = entry.metadata().map(|m| m.len()).unwrap_or(0);
+            if size as i64 > max_bytes {
+                scan.skipped.insert(path);
+                continue;
+            }
+            let content = match fs::read(entry.path()) {
+                Ok(content) if !is_binary_buffer(&content) => content,
+                _ => {
+                    scan.skipped.insert(path);
+                    continue;
+                }
+            };
+            let hash = if store {
+                self.change_log.put_blob(&content)?
+            } else {
+                sha256_bytes(&content)
+            };
+            scan.total_bytes += content.len() as i64;
+            scan.files.insert(path, hash);
+        }
+        Ok(scan)
+    }
+}
+
+/// A path that differs between a checkpoint and the workspace.
+struct Delta {
+    path: String,
+    status: &'static str,
+    comparable: bool,
+    /// Checkpoint blob hash, or "" if the path was not in the checkpoint.
+    saved: String,
+}
+
+/// A checkpointed file the scan skipped still exists, so it is reported as
+/// modified (not comparable) rather than deleted.
+fn compare(saved: &BTreeMap<String, String>, current: &Scan) -> Vec<Delta> {
+    let mut paths: Vec<&String> = saved.keys().chain(current.files.keys()).collect();
+    paths.sort();
+    paths.dedup();
+    let mut changed = Vec::new();
+    for path in paths {
+        let (status, comparable) = match (saved.get(path), current.files.get(path)) {
+            (Some(hash), Some(current)) if hash == current => continue,
+            (Some(_), Some(_)) => ("modified", true),
+            (Some(_), None) if current.skipped.contains(path) => ("modified", false),
+            (Some(_), None) => ("deleted", true),
+            (None, _) => ("added", true),
+        };
+        changed.push(Delta {
+            path: path.clone(),
+            status,
+            comparable,
+            saved: saved.get(path).cloned().unwrap_or_default(),
+        });
+    }
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::Scratch;
+
+    fn checkpoints<'a>(fs_ops: &'a FsOps, store: &'a ChangeLogStore) -> Checkpoints<'a> {
+        Checkpoints {
+            fs_ops,
+            change_log: store,
+            session_id: "s1",
+            run_id: "",
+        }
+    }
+
+    #[test]
+    fn files_that_became_binary_are_modified_not_deleted() {
+        let scratch = Scratch::new();
+        let fs_ops = scratch.fs_ops();
+        let store = ChangeLogStore::new(":memory:").unwrap();
+        let checkpoints = checkpoints(&fs_ops, &store);
+        fs::write(scratch.0.join("a.txt"), "text\n").unwrap();
+        fs::write(scratch.0.join("b.txt"), "gone\n").unwrap();
+        checkpoints.create("base").unwrap();
+
+        fs::write(scratch.0.join("a.txt"), b"\0binary").unwrap();
+        fs::remove_file(scratch.0.join("b.txt")).unwrap();
+        let diffs = checkpoints.diff("base", false).unwrap();
+        let statuses: Vec<_> = diffs.iter().map(|d| (d.path.as_str(), d.status.as_str(), d.comparable)).collect();
+        assert_eq!(statuses, vec![("a.txt", "modified", false), ("b.txt", "deleted", true)]);
+
+        let restored = checkpoints.restore("base", false, false).unwrap();
+        assert_eq!(restored[0].status, "modified");
+        assert_eq!(fs::read_to_string(scratch.0.join("a.txt")).unwrap(), "text\n");
+        assert_eq!(fs::read_to_string(scratch.0.join("b.txt")).unwrap(), "gone\n");
+        assert!(checkpoints.diff("base", false).unwrap().is_empty());
+    }
+
+    #[test]
+    fn failed_restore_rolls_back_and_logs_nothing() {
+        let scratch = Scratch::new();
+        let fs_ops = scratch.fs_ops();
+        let store = ChangeLogStore::new(":memory:").unwrap();
+        let checkpoints = checkpoints(&fs_ops, &store);
+        fs::write(scratch.0.join("0.txt"), "saved\n").unwrap();
+        fs::create_dir(scratch.0.join("a")).unwrap();
+        fs::write(scratch.0.join("a/b.txt"), "nested\n").unwrap();
+        checkpoints.create("base").unwrap();
+
+        fs::write(scratch.0.join("0.txt"), "edited\n").unwrap();
+        fs::remove_dir_all(scratch.0.join("a")).unwrap();
+        fs::write(scratch.0.join("a"), "now a file\n").unwrap();
+
+        let err = checkpoints.restore("base", true, false).unwrap_err();
+        assert!(err.starts_with("Failed to write a/b.txt"), "{err}");
+        assert!(err.ends_with("All changes were rolled back."), "{err}");
+        assert_eq!(fs::read_to_string(scratch.0.join("0.txt")).unwrap(), "edited\n");
+        assert!(store.session_changes("s1").unwrap().is_empty());
+    }
+}
@@ -0,0 +1,139 @@
+use crate::diff::{build_diff, DiffInput};
+use crate::fs_ops::FsOps;
+use crate::storage::{ChangeLogStore, Snapshot};
+use crate::utils::sha256_bytes;
+use serde_json::json;
+use std::fs;
+
+/// Fails with a JSON conflict error when `path` no longer hashes to
+/// `expected_sha256`, i.e. the file changed since the caller read it.
+///
+/// The error carries the current hash and a diff: from the expected version
+/// to the current file when that version is in the blob store, otherwise from
+/// the current file to the proposed content, the state the write would have
+/// left behind.
+pub fn check_expected_sha256(
+    fs_ops: &FsOps,
+    change_log: &ChangeLogStore,
+    path: &str,
+    expected_sha256: &str,
+    proposed: Option<&Snapshot>,
+) -> Result<(), String> {
+    let expected_sha256 = expected_sha256.trim().to_lowercase();
+    let target = fs_ops.resolve_path(path)?;
+    let current = if target.is_file() {
+        Snapshot::Content(fs::read(&target).map_err(|err| err.to_string())?)
+    } else if target.exists() {
+        Snapshot::Unknown
+    } else {
+        Snapshot::Missing
+    };
+    let current_sha256 = match &current {
+        Snapshot::Content(bytes) => Some(sha256_bytes(bytes)),
+        _ => None,
+    };
+    if current_sha256.as_deref() == Some(expected_sha256.as_str()) {
+        return Ok(());
+    }
+
+    let known = change_log.get_blob(&expected_sha256)?;
+    let (diff_base, diff) = match (known, proposed) {
+        (Some(bytes), _) => (
+            "expected",
+            build_diff(
+                DiffInput::from_snapshot(&Snapshot::Content(bytes)),
+                DiffInput::from_snapshot(&current),
+            ),
+        ),
+        (None, Some(proposed)) => (
+            "current",
+            build_diff(
+                DiffInput::from_snapshot(&current),
+                DiffInput::from_snapshot(proposed),
+            ),
+        ),
+        (None, None) => ("none", None),
+    };
+    let error = json!({
+        "error": "conflict",
+        "message": format!("{path} changed since it was read (expected sha256 {expected_sha256})."),
+        "path": path,
+        "expected_sha256": expected_sha256,
+        "current_sha256": current_sha256,
+        "exists": current != Snapshot::Missing,
+        "diff_base": diff_base,
+        "diff": diff
+    });
+    Err(serde_json::to_string_pretty(&error).unwrap_or_else(|_| error.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::Scratch;
+    use serde_json::Value;
+
+    fn conflict(result: Result<(), String>) -> Value {
+        serde_json::from_str(&result.unwrap_err()).unwrap()
+    }
+
+    #[test]
+    fn matching_hash_passes() {
+        let scratch = Scratch::new();
+        let fs_ops = scratch.fs_ops();
+        let store = ChangeLogStore::new(":memory:").unwrap();
+        fs::write(scratch.0.join("a.txt"), "one\n").unwrap();
+        let expected = format!(" {} ", sha256_bytes(b"one\n").to_uppercase());
+        assert!(check_expected_sha256(&fs_ops, &store, "a.txt", &expected, None).is_ok());
+    }
+
+    #[test]
+    fn conflict_diffs_from_the_expected_version_when_it_is_stored() {
+        let scratch = Scratch::new();
+        let fs_ops = scratch.fs_ops();
+        let store = ChangeLogStore::new(":memory:").unwrap();
+        let expected = store.put_blob(b"one\n").unwrap();
+        fs::write(scratch.0.join("a.txt"), "two\n").unwrap();
+        let proposed = Snapshot::Content(b"three\n".to_vec());
+
+        let error = conflict(check_expected_sha256(&fs_ops, &store, "a.txt", &expected, Some(&proposed)));
+        assert_eq!(error["error"], "conflict");
+        assert_eq!(error["expected_sha256"], expected);
+        assert_eq!(error["current_sha256"], sha256_bytes(b"two\n"));
+        assert_eq!(error["exists"], true);
+        assert_eq!(error["diff_base"], "expected");
+        let diff = error["diff"].as_str().unwrap();
+        assert!(diff.contains("-one\n+two\n"), "{diff}");
+    }
+
+    #[test]
+    fn conflict_falls_back_to_the_proposed_content() {
+        let scratch = Scratch::new();
+        let fs_ops = scratch.fs_ops();
+        let store = ChangeLogStore::new(":memory:").unwrap();
+        fs::write(scratch.0.join("a.txt"), "two\n").unwrap();
+        let proposed = Snapshot::Content(b"three\n".to_vec());
+
+        let error = conflict(check_expected_sha256(&fs_ops, &store, "a.txt", "abc", Some(&proposed)));
+        assert_eq!(error["diff_base"], "current");
+        assert!(error["diff"].as_str().unwrap().contains("-two\n+three\n"));
+
+        let error = conflict(check_expected_sha256(&fs_ops, &store, "a.txt", "abc", None));
+        assert_eq!(error["diff_base"], "none");
+        assert!(error["diff"].is_null());
+    }
+
+    #[test]
+    fn missing_file_conflicts_without_a_current_hash() {
+        let scratch = Scratch::new();
+        let fs_ops = scratch.fs_ops();
+        let store = ChangeLogStore::new(":memory:").unwrap();
+        let proposed = Snapshot::Content(b"new\n".to_vec());
+
+        let error = conflict(check_expected_sha256(&fs_ops, &store, "gone.txt", "abc", Some(&proposed)));
+        assert_eq!(error["exists"], false);
+        assert!(error["current_sha256"].is_null());
+        assert_eq!(error["path"], "gone.txt");
+        assert!(error["diff"].as_str().unwrap().contains("+new\n"));
+    }
+}
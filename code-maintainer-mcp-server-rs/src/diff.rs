@@ -1,9 +1,14 @@
-use crate::utils::is_binary_buffer;
+use crate::storage::Snapshot;
+use crate::utils::{is_binary_buffer, sha256_bytes};
 use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 const MAX_DIFF_CHARS: usize = 200_000;
+const MAX_EDIT_DISTANCE: isize = 2_000;
+
+static CONTEXT_LINES: AtomicUsize = AtomicUsize::new(3);
 
 #[derive(Debug, Clone)]
 pub struct DiffInput {
@@ -25,6 +30,15 @@ impl DiffInput {
             reason: Some(reason),
         }
     }
+
+    pub fn from_snapshot(snapshot: &Snapshot) -> Self {
+        match snapshot {
+            Snapshot::Unknown => Self::omitted("not captured".to_string()),
+            Snapshot::Missing => Self::text(String::new()),
+            Snapshot::Content(bytes) if is_binary_buffer(bytes) => Self::omitted("binary file".to_string()),
+            Snapshot::Content(bytes) => Self::text(String::from_utf8_lossy(bytes).to_string()),
+        }
+    }
 }
 
 pub fn read_text_for_diff(path: &Path, max_bytes: i64) -> Result<DiffInput, String> {
@@ -72,59 +86,250 @@ pub fn build_diff(before: DiffInput, after: DiffInput) -> Option<String> {
     Some(render_diff(&before_text, &after_text))
 }
 
+/// Diff between two captured states. Binary content gets a placeholder with
+/// both hashes instead of a line diff.
+pub fn snapshot_diff(before: &Snapshot, after: &Snapshot) -> Option<String> {
+    if !before.is_binary() && !after.is_binary() {
+        return build_diff(DiffInput::from_snapshot(before), DiffInput::from_snapshot(after));
+    }
+    let describe = |snapshot: &Snapshot| match snapshot {
+        Snapshot::Unknown => "unknown".to_string(),
+        Snapshot::Missing => "none".to_string(),
+        Snapshot::Content(bytes) => format!("sha256 {} ({} bytes)", sha256_bytes(bytes), bytes.len()),
+    };
+    Some(format!(
+        "binary file changed\nbefore: {}\nafter: {}",
+        describe(before),
+        describe(after)
+    ))
+}
+
+pub fn set_context_lines(lines: usize) {
+    CONTEXT_LINES.store(lines, Ordering::Relaxed);
+}
+
 pub fn render_diff(before: &str, after: &str) -> String {
+    render_diff_with_context(before, after, CONTEXT_LINES.load(Ordering::Relaxed))
+}
+
+/// Renders a unified diff with `context` unchanged lines around each hunk.
+pub fn render_diff_with_context(before: &str, after: &str, context: usize) -> String {
     if before == after {
         return "No changes.".to_string();
     }
-    let before_lines: Vec<&str> = before.split('\n').collect();
-    let after_lines: Vec<&str> = after.split('\n').collect();
+    let (mut out, complete) = render_hunks(before, after, context);
+    if !complete {
+        out.push_str("... (diff truncated)\n");
+    }
+    out
+}
+
+/// Like `render_diff`, but `None` where the diff would be truncated, for
+/// output that has to stay a valid patch.
+pub fn render_complete_diff(before: &str, after: &str) -> Option<String> {
+    let (out, complete) = render_hunks(before, after, CONTEXT_LINES.load(Ordering::Relaxed));
+    complete.then_some(out)
+}
+
+/// The hunks of a unified diff, and whether they all fit in `MAX_DIFF_CHARS`.
+fn render_hunks(before: &str, after: &str, context: usize) -> (String, bool) {
+    // Lines keep their terminator so a missing final newline shows up as a change.
+    let before_lines: Vec<&str> = before.split_inclusive('\n').collect();
+    let after_lines: Vec<&str> = after.split_inclusive('\n').collect();
+    let edits = diff_lines(&before_lines, &after_lines);
+
+    let mut out = String::new();
+    out.push_str("--- before\n+++ after\n");
+    let changes: Vec<usize> = edits
+        .iter()
+        .enumerate()
+        .filter(|(_, edit)| edit.tag != Tag::Equal)
+        .map(|(idx, _)| idx)
+        .collect();
+    let mut group_start = 0usize;
+    while group_start < changes.len() {
+        let mut group_end = group_start;
+        while group_end + 1 < changes.len() && changes[group_end + 1] - changes[group_end] <= 2 * context + 1 {
+            group_end += 1;
+        }
+        let from = changes[group_start].saturating_sub(context);
+        let to = (changes[group_end] + context + 1).min(edits.len());
+        if !append_hunk(&mut out, &edits[from..to], &before_lines, &after_lines) {
+            return (out, false);
+        }
+        group_start = group_end + 1;
+    }
+    (out, true)
+}
+
+/// Number of (added, removed) lines between two texts.
+pub fn line_stats(before: &str, after: &str) -> (usize, usize) {
+    let before_lines: Vec<&str> = before.split_inclusive('\n').collect();
+    let after_lines: Vec<&str> = after.split_inclusive('\n').collect();
+    let edits = diff_lines(&before_lines, &after_lines);
+    let added = edits.iter().filter(|edit| edit.tag == Tag::Insert).count();
+    let removed = edits.iter().filter(|edit| edit.tag == Tag::Delete).count();
+    (added, removed)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Tag {
+    Equal,
+    Delete,
+    Insert,
+}
+
+/// One line of the edit script; `old`/`new` are the line positions in each
+/// file at the point this edit applies.
+#[derive(Debug, Clone, Copy)]
+struct Edit {
+    tag: Tag,
+    old: usize,
+    new: usize,
+}
+
+fn append_hunk(out: &mut String, edits: &[Edit], before: &[&str], after: &[&str]) -> bool {
+    let old_count = edits.iter().filter(|edit| edit.tag != Tag::Insert).count();
+    let new_count = edits.iter().filter(|edit| edit.tag != Tag::Delete).count();
+    let old_start = edits.first().map(|edit| edit.old).unwrap_or(0);
+    let new_start = edits.first().map(|edit| edit.new).unwrap_or(0);
+    out.push_str(&format!(
+        "@@ -{},{} +{},{} @@\n",
+        if old_count == 0 { old_start } else { old_start + 1 },
+        old_count,
+        if new_count == 0 { new_start } else { new_start + 1 },
+        new_count
+    ));
+    for edit in edits {
+        let (prefix, line) = match edit.tag {
+            Tag::Equal => (' ', before[edit.old]),
+            Tag::Delete => ('-', before[edit.old]),
+            Tag::Insert => ('+', after[edit.new]),
+        };
+        let text = line.strip_suffix('\n').unwrap_or(line);
+        if !append_diff_line(out, prefix, text) {
+            return false;
+        }
+        if !line.ends_with('\n') && !append_diff_line(out, '\\', " No newline at end of file") {
+            return false;
+        }
+    }
+    true
+}
+
+fn diff_lines(before: &[&str], after: &[&str]) -> Vec<Edit> {
     let mut prefix = 0usize;
-    while prefix < before_lines.len()
-        && prefix < after_lines.len()
-        && before_lines[prefix] == after_lines[prefix]
-    {
+    while prefix < before.len() && prefix < after.len() && before[prefix] == after[prefix] {
         prefix += 1;
     }
     let mut suffix = 0usize;
-    while suffix < before_lines.len().saturating_sub(prefix)
-        && suffix < after_lines.len().saturating_sub(prefix)
-        && before_lines[before_lines.len() - 1 - suffix]
-            == after_lines[after_lines.len() - 1 - suffix]
+    while suffix < before.len() - prefix
+        && suffix < after.len() - prefix
+        && before[before.len() - 1 - suffix] == after[after.len() - 1 - suffix]
     {
         suffix += 1;
     }
-    let old_mid = &before_lines[prefix..before_lines.len().saturating_sub(suffix)];
-    let new_mid = &after_lines[prefix..after_lines.len().saturating_sub(suffix)];
+    let old_mid = &before[prefix..before.len() - suffix];
+    let new_mid = &after[prefix..after.len() - suffix];
 
-    let mut out = String::new();
-    out.push_str("--- before\n+++ after\n");
-    out.push_str(&format!(
-        "@@ -{},{} +{},{} @@\n",
-        if before_lines.is_empty() { 0 } else { prefix + 1 },
-        old_mid.len(),
-        if after_lines.is_empty() { 0 } else { prefix + 1 },
-        new_mid.len()
-    ));
+    let mut edits: Vec<Edit> = (0..prefix)
+        .map(|idx| Edit {
+            tag: Tag::Equal,
+            old: idx,
+            new: idx,
+        })
+        .collect();
+    let middle = myers(old_mid, new_mid).unwrap_or_else(|| {
+        // Too many differences to diff precisely; fall back to one replace block.
+        let deletes = (0..old_mid.len()).map(|idx| (Tag::Delete, idx, 0));
+        let inserts = (0..new_mid.len()).map(|idx| (Tag::Insert, old_mid.len(), idx));
+        deletes.chain(inserts).collect()
+    });
+    edits.extend(middle.into_iter().map(|(tag, old, new)| Edit {
+        tag,
+        old: prefix + old,
+        new: prefix + new,
+    }));
+    edits.extend((0..suffix).map(|idx| Edit {
+        tag: Tag::Equal,
+        old: before.len() - suffix + idx,
+        new: after.len() - suffix + idx,
+    }));
+    edits
+}
+
+/// Myers' O(ND) shortest edit script. Returns `None` when the edit distance
+/// exceeds `MAX_EDIT_DISTANCE`, which keeps the trace memory bounded.
+fn myers(a: &[&str], b: &[&str]) -> Option<Vec<(Tag, usize, usize)>> {
+    let n = a.len() as isize;
+    let m = b.len() as isize;
+    let max = n + m;
+    let offset = max + 1;
+    let mut v = vec![0isize; (2 * max + 3) as usize];
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+    let mut found = None;
 
-    let mut truncated = false;
-    for line in old_mid {
-        if !append_diff_line(&mut out, '-', line) {
-            truncated = true;
+    for d in 0..=max.min(MAX_EDIT_DISTANCE) {
+        trace.push(v[(offset - d) as usize..=(offset + d) as usize].to_vec());
+        let mut k = -d;
+        while k <= d {
+            let idx = (offset + k) as usize;
+            let mut x = if k == -d || (k != d && v[idx - 1] < v[idx + 1]) {
+                v[idx + 1]
+            } else {
+                v[idx - 1] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[idx] = x;
+            if x >= n && y >= m {
+                found = Some(d);
+                break;
+            }
+            k += 2;
+        }
+        if found.is_some() {
             break;
         }
     }
-    if !truncated {
-        for line in new_mid {
-            if !append_diff_line(&mut out, '+', line) {
-                truncated = true;
-                break;
-            }
+    let depth = found?;
+
+    let mut edits = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for d in (1..=depth).rev() {
+        let prev = &trace[d as usize];
+        let at = |k: isize| prev[(k + d) as usize];
+        let k = x - y;
+        let prev_k = if k == -d || (k != d && at(k - 1) < at(k + 1)) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let prev_x = at(prev_k);
+        let prev_y = prev_x - prev_k;
+        while x > prev_x && y > prev_y {
+            x -= 1;
+            y -= 1;
+            edits.push((Tag::Equal, x as usize, y as usize));
         }
+        if prev_k == k + 1 {
+            edits.push((Tag::Insert, prev_x as usize, prev_y as usize));
+        } else {
+            edits.push((Tag::Delete, prev_x as usize, prev_y as usize));
+        }
+        x = prev_x;
+        y = prev_y;
     }
-    if truncated {
-        out.push_str("... (diff truncated)\n");
+    while x > 0 && y > 0 {
+        x -= 1;
+        y -= 1;
+        edits.push((Tag::Equal, x as usize, y as usize));
     }
-    out
+    edits.reverse();
+    Some(edits)
 }
 
 fn append_diff_line(out: &mut String, prefix: char, line: &str) -> bool {
@@ -139,6 +344,9 @@ fn append_diff_line(out: &mut String, prefix: char, line: &str) -> bool {
 
 pub fn extract_patch_diffs(patch: &str) -> HashMap<String, String> {
     let text = patch.replace("\r\n", "\n");
+    if !text.starts_with("*** Begin Patch") && is_unified_diff(&text) {
+        return unified_diff_sections(&text);
+    }
     let lines: Vec<&str> = text.split('\n').collect();
     let mut map = HashMap::new();
     let mut i = 0usize;
@@ -174,6 +382,12 @@ fn collect_patch_section(lines: &[&str], start: usize, path: &str) -> (String, S
     let mut idx = start;
     while idx < lines.len() {
         let line = lines[idx];
+        if line.starts_with("*** Update File: ")
+            || line.starts_with("*** Add File: ")
+            || line.starts_with("*** Delete File: ")
+        {
+            break;
+        }
         section.push(line.to_string());
         if let Some(dest) = line.strip_prefix("*** Move to: ") {
             let dest = dest.trim();
@@ -189,3 +403,402 @@ fn collect_patch_section(lines: &[&str], start: usize, path: &str) -> (String, S
     let key = move_to.unwrap_or_else(|| path.to_string());
     (key, section.join("\n"), idx)
 }
+
+/// One file operation of a patch, in either the `*** Begin Patch` format or a
+/// unified diff.
+#[derive(Debug)]
+pub enum PatchOp {
+    Update {
+        path: String,
+        move_to: Option<String>,
+        hunks: Vec<String>,
+    },
+    Add {
+        path: String,
+        lines: Vec<String>,
+    },
+    Delete {
+        path: String,
+    },
+}
+
+/// True for `diff --git` output and plain `---`/`+++`/`@@` unified diffs.
+pub fn is_unified_diff(text: &str) -> bool {
+    let lines: Vec<&str> = text.lines().collect();
+    lines.iter().any(|line| line.starts_with("diff --git "))
+        || lines
+            .windows(2)
+            .any(|pair| pair[0].starts_with("--- ") && pair[1].starts_with("+++ "))
+}
+
+/// Maps each file's final path to its section of a unified diff, for the change log.
+pub fn unified_diff_sections(text: &str) -> HashMap<String, String> {
+    let text = text.replace("\r\n", "\n");
+    parse_unified_diff(&text)
+        .map(|files| {
+            files
+                .into_iter()
+                .map(|(op, section)| {
+                    let path = match op {
+                        PatchOp::Update {
+                            path, move_to, ..
+                        } => move_to.unwrap_or(path),
+                        PatchOp::Add { path, .. } | PatchOp::Delete { path } => path,
+                    };
+                    (path, section)
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[derive(Default)]
+struct UnifiedFile {
+    old_path: Option<String>,
+    new_path: Option<String>,
+    new_file: bool,
+    deleted: bool,
+    binary: bool,
+    hunks: Vec<String>,
+    section: Vec<String>,
+}
+
+impl UnifiedFile {
+    fn into_op(self) -> Result<(PatchOp, String), String> {
+        let section = self.section.join("\n");
+        let display = self
+            .new_path
+            .clone()
+            .or_else(|| self.old_path.clone())
+            .unwrap_or_default();
+        if display.is_empty() {
+            return Err("Unified diff file header is missing a path.".to_string());
+        }
+        if self.binary {
+            return Err(format!("Binary patches are not supported: {display}"));
+        }
+        if self.deleted || self.new_path.is_none() {
+            let path = self.old_path.unwrap_or(display);
+            return Ok((PatchOp::Delete { path }, section));
+        }
+        if self.new_file || self.old_path.is_none() {
+            let mut lines: Vec<String> = Vec::new();
+            let mut trailing_newline = true;
+            for line in &self.hunks {
+                if let Some(added) = line.strip_prefix('+') {
+                    lines.push(added.to_string());
+                    trailing_newline = true;
+                } else if line.starts_with('\\') {
+                    trailing_newline = false;
+                }
+            }
+            if trailing_newline && !lines.is_empty() {
+                lines.push(String::new());
+            }
+            return Ok((PatchOp::Add { path: display, lines }, section));
+        }
+        let path = self.old_path.unwrap_or_default();
+        let move_to = self.new_path.filter(|new_path| *new_path != path);
+        Ok((
+            PatchOp::Update {
+                path,
+                move_to,
+                hunks: self.hunks,
+            },
+            section,
+        ))
+    }
+}
+
+/// Splits a unified diff into per-file operations, each with its raw section.
+pub fn parse_unified_diff(text: &str) -> Result<Vec<(PatchOp, String)>, String> {
+    let lines: Vec<&str> = text.split('\n').collect();
+    let is_git = lines.iter().any(|line| line.starts_with("diff --git "));
+    let mut files: Vec<(PatchOp, String)> = Vec::new();
+    let mut current: Option<UnifiedFile> = None;
+    let mut i = 0usize;
+
+    while i < lines.len() {
+        let line = lines[i];
+        if let Some(rest) = line.strip_prefix("diff --git ") {
+            if let Some(file) = current.take() {
+                files.push(file.into_op()?);
+            }
+            let mut file = UnifiedFile::default();
+            if let Some((old, new)) = rest.split_once(" b/") {
+                file.old_path = Some(old.trim_start_matches("a/").to_string());
+                file.new_path = Some(new.to_string());
+            }
+            file.section.push(line.to_string());
+            current = Some(file);
+            i += 1;
+            continue;
+        }
+        if starts_unified_file(&lines, i) && !line.starts_with("diff --git ") {
+            let mut file = match current.take() {
+                Some(file) if is_git && file.hunks.is_empty() => file,
+                Some(file) => {
+                    files.push(file.into_op()?);
+                    UnifiedFile::default()
+                }
+                None => UnifiedFile::default(),
+            };
+            let old = &line[4..];
+            let new = &lines[i + 1][4..];
+            let strip = is_git || (old.starts_with("a/") && new.starts_with("b/"));
+            file.old_path = unified_path(old, if strip { "a/" } else { "" });
+            file.new_path = unified_path(new, if strip { "b/" } else { "" });
+            file.new_file |= file.old_path.is_none();
+            file.deleted |= file.new_path.is_none();
+            file.section.push(line.to_string());
+            file.section.push(lines[i + 1].to_string());
+            current = Some(file);
+            i += 2;
+            continue;
+        }
+        if line.starts_with("@@") {
+            let file = current
+                .as_mut()
+                .ok_or_else(|| format!("Hunk without a file header at line {}", i + 1))?;
+            let start = i;
+            i += 1;
+            if let Some((mut old_count, mut new_count)) = hunk_counts(line) {
+                while i < lines.len() && (old_count > 0 || new_count > 0) {
+                    let body = lines[i];
+                    let overrun = || format!("Hunk at line {} is longer than its header.", start + 1);
+                    if body.starts_with('+') {
+                        new_count = new_count.checked_sub(1).ok_or_else(overrun)?;
+                    } else if body.starts_with('-') {
+                        old_count = old_count.checked_sub(1).ok_or_else(overrun)?;
+                    } else if body.starts_with(' ') || body.is_empty() {
+                        old_count = old_count.saturating_sub(1);
+                        new_count = new_count.saturating_sub(1);
+                    } else if !body.starts_with('\\') {
+                        break;
+                    }
+                    i += 1;
+                }
+                while lines.get(i).is_some_and(|body| body.starts_with('\\')) {
+                    i += 1;
+                }
+            } else {
+                while i < lines.len() && !lines[i].starts_with("@@") && !starts_unified_file(&lines, i) {
+                    i += 1;
+                }
+            }
+            for body in &lines[start..i] {
+                let body = if body.is_empty() { " " } else { body };
+                file.hunks.push(body.to_string());
+                file.section.push(body.to_string());
+            }
+            continue;
+        }
+        if let Some(file) = current.as_mut() {
+            if line.starts_with("new file mode") {
+                file.new_file = true;
+            } else if line.starts_with("deleted file mode") {
+                file.deleted = true;
+            } else if let Some(from) = line.strip_prefix("rename from ") {
+                file.old_path = Some(from.trim().to_string());
+            } else if let Some(to) = line.strip_prefix("rename to ") {
+                file.new_path = Some(to.trim().to_string());
+            } else if line.starts_with("Binary files ") || line.starts_with("GIT binary patch") {
+                file.binary = true;
+            }
+            if !line.is_empty() {
+                file.section.push(line.to_string());
+            }
+        }
+        i += 1;
+    }
+    if let Some(file) = current.take() {
+        files.push(file.into_op()?);
+    }
+    if files.is_empty() {
+        return Err("No file changes found in unified diff.".to_string());
+    }
+    Ok(files)
+}
+
+fn starts_unified_file(lines: &[&str], index: usize) -> bool {
+    let line = lines[index];
+    line.starts_with("diff --git ")
+        || (line.starts_with("--- ") && lines.get(index + 1).is_some_and(|next| next.starts_with("+++ ")))
+}
+
+/// Parses a `---`/`+++` header path, dropping timestamps and the git `a/`/`b/` prefix.
+fn unified_path(raw: &str, prefix: &str) -> Option<String> {
+    let path = raw.split('\t').next().unwrap_or("").trim();
+    if path == "/dev/null" || path.is_empty() {
+        return None;
+    }
+    Some(path.strip_prefix(prefix).unwrap_or(path).to_string())
+}
+
+/// Old and new line counts of a `@@ -a,b +c,d @@` header.
+fn hunk_counts(header: &str) -> Option<(usize, usize)> {
+    let (_, old_count) = hunk_old_range(header)?;
+    let new_range = header
+        .strip_prefix("@@")?
+        .split_whitespace()
+        .find(|part| part.starts_with('+'))?;
+    let new_count = match new_range[1..].split_once(',') {
+        Some((_, count)) => count.parse().ok()?,
+        None => 1,
+    };
+    Some((old_count, new_count))
+}
+
+/// Parses the `-start,count` part of a unified `@@ -a,b +c,d @@` header.
+pub fn hunk_old_range(header: &str) -> Option<(usize, usize)> {
+    let rest = header.strip_prefix("@@")?.trim_start();
+    let range = rest.strip_prefix('-')?.split_whitespace().next()?;
+    let (start, count) = match range.split_once(',') {
+        Some((start, count)) => (start.parse().ok()?, count.parse().ok()?),
+        None => (range.parse().ok()?, 1),
+    };
+    Some((start, count))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Result<Vec<(PatchOp, String)>, String> {
+        parse_unified_diff(text)
+    }
+
+    #[test]
+    fn detects_unified_diffs() {
+        assert!(is_unified_diff("--- a/x\n+++ b/x\n@@ -1 +1 @@\n-a\n+b\n"));
+        assert!(is_unified_diff("diff --git a/x b/x\n"));
+        assert!(!is_unified_diff("*** Begin Patch\n*** Update File: x\n"));
+    }
+
+    #[test]
+    fn parses_git_update_rename_add_and_delete() {
+        let text = "diff --git a/src/a.rs b/src/b.rs\nsimilarity index 90%\nrename from src/a.rs\nrename to src/b.rs\n--- a/src/a.rs\n+++ b/src/b.rs\n@@ -1,2 +1,2 @@\n fn a() {}\n-old\n+new\ndiff --git a/new.txt b/new.txt\nnew file mode 100644\n--- /dev/null\n+++ b/new.txt\n@@ -0,0 +1,2 @@\n+one\n+two\ndiff --git a/gone.txt b/gone.txt\ndeleted file mode 100644\n--- a/gone.txt\n+++ /dev/null\n@@ -1 +0,0 @@\n-bye\n";
+        let files = parse(text).unwrap();
+        assert_eq!(files.len(), 3);
+        match &files[0].0 {
+            PatchOp::Update { path, move_to, hunks } => {
+                assert_eq!(path, "src/a.rs");
+                assert_eq!(move_to.as_deref(), Some("src/b.rs"));
+                assert_eq!(hunks, &["@@ -1,2 +1,2 @@", " fn a() {}", "-old", "+new"]);
+            }
+            _ => panic!("expected an update"),
+        }
+        match &files[1].0 {
+            PatchOp::Add { path, lines } => {
+                assert_eq!(path, "new.txt");
+                assert_eq!(lines, &["one", "two", ""]);
+            }
+            _ => panic!("expected an add"),
+        }
+        assert!(matches!(&files[2].0, PatchOp::Delete { path } if path == "gone.txt"));
+    }
+
+    #[test]
+    fn plain_unified_diff_strips_timestamps() {
+        let files = parse("--- x.txt\t2024-01-01\n+++ x.txt\t2024-01-02\n@@ -1 +1 @@\n-a\n+b\n").unwrap();
+        assert!(matches!(&files[0].0, PatchOp::Update { path, move_to: None, .. } if path == "x.txt"));
+    }
+
+    #[test]
+    fn hunk_counts_bound_the_hunk() {
+        let text = "--- a/x\n+++ b/x\n@@ -1 +1 @@\n-a\n+b\n--- a/y\n+++ b/y\n@@ -1 +1 @@\n-c\n+d\n";
+        let files = parse(text).unwrap();
+        assert_eq!(files.len(), 2);
+    }
+
+    #[test]
+    fn hunk_longer_than_header_is_an_error() {
+        let err = parse("--- a/x\n+++ b/x\n@@ -1,3 +1,1 @@\n-a\n-b\n+x\n+y\n").unwrap_err();
+        assert!(err.contains("longer than its header"), "{err}");
+        let err = parse("--- a/x\n+++ b/x\n@@ -1,1 +1,3 @@\n-a\n-b\n+x\n").unwrap_err();
+        assert!(err.contains("longer than its header"), "{err}");
+    }
+
+    #[test]
+    fn binary_patches_are_rejected() {
+        let err = parse("diff --git a/i.png b/i.png\nBinary files a/i.png and b/i.png differ\n").unwrap_err();
+        assert!(err.contains("Binary"), "{err}");
+    }
+
+    #[test]
+    fn reads_hunk_ranges() {
+        assert_eq!(hunk_old_range("@@ -10,4 +10,5 @@ fn x"), Some((10, 4)));
+        assert_eq!(hunk_old_range("@@ -3 +3 @@"), Some((3, 1)));
+        assert_eq!(hunk_counts("@@ -0,0 +1,2 @@"), Some((0, 2)));
+        assert_eq!(hunk_old_range("@@ fn main"), None);
+    }
+
+    /// Replays an edit script, checking it is consistent with both inputs.
+    fn replay(a: &[&str], b: &[&str], edits: &[Edit]) -> Vec<String> {
+        let mut out = Vec::new();
+        let (mut old, mut new) = (0, 0);
+        for edit in edits {
+            assert_eq!((edit.old, edit.new), (old, new), "{edits:?}");
+            match edit.tag {
+                Tag::Equal => {
+                    assert_eq!(a[old], b[new]);
+                    out.push(a[old].to_string());
+                    old += 1;
+                    new += 1;
+                }
+                Tag::Delete => old += 1,
+                Tag::Insert => {
+                    out.push(b[new].to_string());
+                    new += 1;
+                }
+            }
+        }
+        assert_eq!((old, new), (a.len(), b.len()));
+        out
+    }
+
+    #[test]
+    fn myers_finds_a_shortest_edit_script() {
+        let a: Vec<&str> = "abcabba".split("").filter(|c| !c.is_empty()).collect();
+        let b: Vec<&str> = "cbabac".split("").filter(|c| !c.is_empty()).collect();
+        let edits = diff_lines(&a, &b);
+        assert_eq!(replay(&a, &b, &edits), b);
+        assert_eq!(edits.iter().filter(|edit| edit.tag != Tag::Equal).count(), 5);
+    }
+
+    #[test]
+    fn edit_scripts_reproduce_the_new_text() {
+        let mut seed = 7u32;
+        let mut next = || {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            (seed >> 16) % 4
+        };
+        let words = ["a", "b", "c", "d"];
+        for _ in 0..200 {
+            let a: Vec<&str> = (0..next() * 3).map(|_| words[next() as usize]).collect();
+            let b: Vec<&str> = (0..next() * 3).map(|_| words[next() as usize]).collect();
+            assert_eq!(replay(&a, &b, &diff_lines(&a, &b)), b, "{a:?} -> {b:?}");
+        }
+    }
+
+    #[test]
+    fn renders_separate_hunks_with_context() {
+        let before: String = (1..=20).map(|n| format!("line {n}\n")).collect();
+        let after = before.replace("line 2\n", "line two\n").replace("line 18\n", "");
+        let diff = render_diff_with_context(&before, &after, 1);
+        assert_eq!(
+            diff,
+            "--- before\n+++ after\n@@ -1,3 +1,3 @@\n line 1\n-line 2\n+line two\n line 3\n@@ -17,3 +17,2 @@\n line 17\n-line 18\n line 19\n"
+        );
+        assert_eq!(line_stats(&before, &after), (1, 2));
+        assert_eq!(render_diff_with_context(&before, &before, 3), "No changes.");
+    }
+
+    #[test]
+    fn marks_a_missing_final_newline() {
+        let diff = render_diff_with_context("a\nb\n", "a\nb", 3);
+        assert_eq!(diff, "--- before\n+++ after\n@@ -1,2 +1,2 @@\n a\n-b\n+b\n\\ No newline at end of file\n");
+        let added = render_diff_with_context("", "new\n", 3);
+        assert_eq!(added, "--- before\n+++ after\n@@ -0,0 +1,1 @@\n+new\n");
+    }
+}
@@ -0,0 +1,155 @@
+use serde_json::Value;
+
+#[derive(Debug, Clone)]
+pub struct Edit {
+    pub old_string: String,
+    pub new_string: String,
+    pub replace_all: bool,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct EditMatch {
+    pub edit: usize,
+    pub matches: usize,
+    pub replaced: usize,
+}
+
+/// Reads either a single `old_string`/`new_string` pair or an `edits` array
+/// from tool arguments.
+pub fn parse_edits(args: &Value) -> Result<Vec<Edit>, String> {
+    if let Some(items) = args.get("edits").and_then(|v| v.as_array()) {
+        if items.is_empty() {
+            return Err("edits must not be empty".to_string());
+        }
+        return items.iter().map(parse_edit).collect();
+    }
+    parse_edit(args).map(|edit| vec![edit])
+}
+
+fn parse_edit(value: &Value) -> Result<Edit, String> {
+    let old_string = value
+        .get("old_string")
+        .and_then(|v| v.as_str())
+        .ok_or("old_string is required".to_string())?;
+    let new_string = value
+        .get("new_string")
+        .and_then(|v| v.as_str())
+        .ok_or("new_string is required".to_string())?;
+    Ok(Edit {
+        old_string: old_string.to_string(),
+        new_string: new_string.to_string(),
+        replace_all: value
+            .get("replace_all")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false),
+    })
+}
+
+/// Applies edits in order to `content`. Each `old_string` must match exactly
+/// once unless `replace_all` is set; nothing is returned if any edit fails.
+pub fn apply_edits(path: &str, content: &str, edits: &[Edit]) -> Result<(String, Vec<EditMatch>), String> {
+    let mut next = content.to_string();
+    let mut report = Vec::new();
+    for (idx, edit) in edits.iter().enumerate() {
+        let label = if edits.len() > 1 {
+            format!(" (edit {})", idx + 1)
+        } else {
+            String::new()
+        };
+        if edit.old_string.is_empty() {
+            return Err(format!("old_string must not be empty{label}."));
+        }
+        if edit.old_string == edit.new_string {
+            return Err(format!("old_string and new_string are identical{label}."));
+        }
+        let matches = next.matches(edit.old_string.as_str()).count();
+        if matches == 0 {
+            return Err(format!("old_string not found in {path}{label}."));
+        }
+        if matches > 1 && !edit.replace_all {
+            return Err(format!(
+                "old_string matches {matches} times in {path}{label}; include more surrounding context or set replace_all."
+            ));
+        }
+        next = if edit.replace_all {
+            next.replace(&edit.old_string, &edit.new_string)
+        } else {
+            next.replacen(&edit.old_string, &edit.new_string, 1)
+        };
+        report.push(EditMatch {
+            edit: idx + 1,
+            matches,
+            replaced: if edit.replace_all { matches } else { 1 },
+        });
+    }
+    Ok((next, report))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn edit(old_string: &str, new_string: &str) -> Edit {
+        Edit {
+            old_string: old_string.to_string(),
+            new_string: new_string.to_string(),
+            replace_all: false,
+        }
+    }
+
+    #[test]
+    fn single_match_is_replaced() {
+        let (next, report) = apply_edits("a.rs", "let x = 1;\nlet y = 2;\n", &[edit("y = 2", "y = 3")]).unwrap();
+        assert_eq!(next, "let x = 1;\nlet y = 3;\n");
+        assert_eq!((report[0].edit, report[0].matches, report[0].replaced), (1, 1, 1));
+    }
+
+    #[test]
+    fn missing_and_ambiguous_strings_are_errors() {
+        let err = apply_edits("a.rs", "foo\n", &[edit("bar", "baz")]).unwrap_err();
+        assert_eq!(err, "old_string not found in a.rs.");
+
+        let err = apply_edits("a.rs", "foo foo\n", &[edit("foo", "bar")]).unwrap_err();
+        assert!(err.starts_with("old_string matches 2 times in a.rs;"), "{err}");
+
+        let all = Edit { replace_all: true, ..edit("foo", "bar") };
+        let (next, report) = apply_edits("a.rs", "foo foo\n", &[all]).unwrap();
+        assert_eq!(next, "bar bar\n");
+        assert_eq!((report[0].matches, report[0].replaced), (2, 2));
+    }
+
+    #[test]
+    fn identical_and_empty_strings_are_rejected() {
+        let err = apply_edits("a.rs", "foo\n", &[edit("foo", "foo")]).unwrap_err();
+        assert_eq!(err, "old_string and new_string are identical.");
+        let err = apply_edits("a.rs", "foo\n", &[edit("", "x")]).unwrap_err();
+        assert_eq!(err, "old_string must not be empty.");
+    }
+
+    #[test]
+    fn edits_apply_in_sequence_and_name_the_failing_edit() {
+        let edits = [edit("alpha", "beta"), edit("beta", "gamma")];
+        let (next, report) = apply_edits("a.rs", "alpha\n", &edits).unwrap();
+        assert_eq!(next, "gamma\n");
+        assert_eq!(report.len(), 2);
+
+        let edits = [edit("alpha", "beta"), edit("alpha", "delta")];
+        let err = apply_edits("a.rs", "alpha\n", &edits).unwrap_err();
+        assert_eq!(err, "old_string not found in a.rs (edit 2).");
+    }
+
+    #[test]
+    fn parse_edits_accepts_a_pair_or_an_array() {
+        let edits = parse_edits(&json!({ "old_string": "a", "new_string": "b", "replace_all": true })).unwrap();
+        assert_eq!(edits.len(), 1);
+        assert!(edits[0].replace_all);
+
+        let edits = parse_edits(&json!({ "edits": [{ "old_string": "a", "new_string": "b" }, { "old_string": "b", "new_string": "c" }] })).unwrap();
+        assert_eq!(edits.len(), 2);
+        assert!(!edits[1].replace_all);
+
+        assert!(parse_edits(&json!({ "edits": [] })).is_err());
+        assert_eq!(parse_edits(&json!({ "old_string": "a" })).unwrap_err(), "new_string is required");
+    }
+}
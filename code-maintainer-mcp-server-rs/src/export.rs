@@ -0,0 +1,80 @@
+use crate::storage::ChangeLogStore;
+use crate::summary::summarize_changes;
+use crate::utils::now_iso;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// Net diff of every touched file in `git diff` layout, applicable to the
+    /// tree as it was before the first exported change.
+    Patch,
+    /// One change record per line, oldest first, diffs included.
+    Jsonl,
+}
+
+impl ExportFormat {
+    pub fn parse(value: Option<&str>) -> Result<Self, String> {
+        match value.map(|v| v.trim().to_lowercase()).as_deref() {
+            None | Some("") | Some("patch") => Ok(Self::Patch),
+            Some("jsonl") => Ok(Self::Jsonl),
+            Some(other) => Err(format!("Unknown export format {other} (expected patch or jsonl).")),
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Patch => "patch",
+            Self::Jsonl => "jsonl",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::Patch => "text/x-diff; charset=utf-8",
+            Self::Jsonl => "application/x-ndjson; charset=utf-8",
+        }
+    }
+}
+
+pub struct Export {
+    pub content: String,
+    pub changes: usize,
+}
+
+/// Serializes the changes of a session and/or run as a single document.
+pub fn export_changes(
+    store: &ChangeLogStore,
+    session_id: Option<&str>,
+    run_id: Option<&str>,
+    format: ExportFormat,
+) -> Result<Export, String> {
+    match format {
+        ExportFormat::Patch => {
+            let summary = summarize_changes(store, session_id, run_id, true)?;
+            let mut content = format!(
+                "# session: {}\n# run: {}\n# changes: {}, files: {}\n# exported_at: {}\n",
+                session_id.unwrap_or("*"),
+                run_id.unwrap_or("*"),
+                summary.totals.changes,
+                summary.totals.files,
+                now_iso()
+            );
+            content.push_str(summary.diff.as_deref().unwrap_or(""));
+            Ok(Export {
+                content,
+                changes: summary.totals.changes,
+            })
+        }
+        ExportFormat::Jsonl => {
+            let records = store.ordered_changes(session_id, run_id, true)?;
+            let mut content = String::new();
+            for record in &records {
+                content.push_str(&serde_json::to_string(record).map_err(|err| err.to_string())?);
+                content.push('\n');
+            }
+            Ok(Export {
+                content,
+                changes: records.len(),
+            })
+        }
+    }
+}
@@ -0,0 +1,258 @@
+use crate::diff::snapshot_diff;
+use crate::fs_ops::FsOps;
+use crate::storage::{ChangeLogStore, ChangeQuery, ChangeRecord, Snapshot};
+use crate::utils::{now_iso, sha256_bytes};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
+use std::time::UNIX_EPOCH;
+
+/// What the session last saw of a file, through one of its reads or writes.
+struct Seen {
+    sha256: String,
+    mtime_ms: i64,
+    size: i64,
+    seen_at: String,
+}
+
+/// mtime/hash cache of the files this session has read or written, used to
+/// notice edits made outside the server (a shell, an editor, git checkout).
+pub struct ReadTracker {
+    seen: RefCell<HashMap<String, Seen>>,
+    /// Set when external changes are recorded (`--record-external`).
+    record: bool,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct ExternalChange {
+    pub path: String,
+    /// `modified` or `deleted`.
+    pub status: &'static str,
+    pub seen_sha256: String,
+    pub seen_at: String,
+    pub current_sha256: Option<String>,
+    pub warning: String,
+    /// The `external` row, when external changes are recorded.
+    pub change: Option<ChangeRecord>,
+    #[serde(skip)]
+    current: Snapshot,
+}
+
+impl ReadTracker {
+    pub fn new(record: bool) -> Self {
+        Self {
+            seen: RefCell::new(HashMap::new()),
+            record,
+        }
+    }
+
+    pub fn records(&self) -> bool {
+        self.record
+    }
+
+    pub fn tracked(&self) -> usize {
+        self.seen.borrow().len()
+    }
+
+    /// Records the file's current state as seen. A missing file is forgotten.
+    pub fn remember(&self, fs_ops: &FsOps, path: &str) {
+        let Ok(target) = fs_ops.resolve_path(path) else {
+            return;
+        };
+        let path = fs_ops.relative(&target);
+        let state = fs::metadata(&target)
+            .ok()
+            .filter(|metadata| metadata.is_file())
+            .and_then(|metadata| fs::read(&target).ok().map(|bytes| (metadata, bytes)));
+        let mut seen = self.seen.borrow_mut();
+        match state {
+            Some((metadata, bytes)) => {
+                let (mtime_ms, size) = stamp(&metadata);
+                seen.insert(
+                    path,
+                    Seen {
+                        sha256: sha256_bytes(&bytes),
+                        mtime_ms,
+                        size,
+                        seen_at: now_iso(),
+                    },
+                );
+            }
+            None => {
+                seen.remove(&path);
+            }
+        }
+    }
+
+    fn paths(&self) -> Vec<String> {
+        let mut paths: Vec<String> = self.seen.borrow().keys().cloned().collect();
+        paths.sort();
+        paths
+    }
+}
+
+/// Compares tracked files with the disk. A difference the change log accounts
+/// for (a revert, a checkpoint restore, a hook) is taken as seen, not external.
+pub struct ExternalCheck<'a> {
+    pub tracker: &'a ReadTracker,
+    pub fs_ops: &'a FsOps,
+    pub change_log: &'a ChangeLogStore,
+    pub session_id: &'a str,
+    pub run_id: &'a str,
+    /// Log external changes as `external` rows and take them as seen.
+    pub record: bool,
+}
+
+impl ExternalCheck<'_> {
+    /// Checks one path before a write. `None` if it was never seen or has not
+    /// changed outside the server.
+    pub fn check(&self, path: &str) -> Result<Option<ExternalChange>, String> {
+        let target = self.fs_ops.resolve_path(path)?;
+        let path = self.fs_ops.relative(&target);
+        let metadata = fs::metadata(&target).ok().filter(|metadata| metadata.is_file());
+        if let Some(metadata) = &metadata {
+            let unchanged = self
+                .tracker
+                .seen
+                .borrow()
+                .get(&path)
+                .map(|seen| (seen.mtime_ms, seen.size) == stamp(metadata));
+            if unchanged != Some(false) {
+                return Ok(None);
+            }
+        }
+        let current = match metadata.and_then(|_| fs::read(&target).ok()) {
+            Some(bytes) => Snapshot::Content(bytes),
+            None => Snapshot::Missing,
+        };
+        self.compare(&path, current)
+    }
+
+    /// Checks `path` against content captured just before a successful write,
+    /// so a write that fails its own checks logs nothing.
+    pub fn compare(&self, path: &str, current: Snapshot) -> Result<Option<ExternalChange>, String> {
+        if matches!(current, Snapshot::Unknown) {
+            return Ok(None);
+        }
+        let current_sha256 = match &current {
+            Snapshot::Content(bytes) => Some(sha256_bytes(bytes)),
+            _ => None,
+        };
+        let seen = self
+            .tracker
+            .seen
+            .borrow()
+            .get(path)
+            .map(|seen| (seen.sha256.clone(), seen.seen_at.clone()));
+        let Some((seen_sha256, seen_at)) = seen else {
+            return Ok(None);
+        };
+        if Some(&seen_sha256) == current_sha256.as_ref() {
+            self.refresh_stamp(path);
+            return Ok(None);
+        }
+        if self.last_logged_state(path)? == Some(current_sha256.clone().unwrap_or_default()) {
+            self.tracker.remember(self.fs_ops, path);
+            return Ok(None);
+        }
+        let status = if current_sha256.is_some() { "modified" } else { "deleted" };
+        let mut change = ExternalChange {
+            path: path.to_string(),
+            status,
+            warning: format!(
+                "{path} was {status} outside this server since it was last read or written at {seen_at}; re-read it before relying on earlier content."
+            ),
+            seen_sha256,
+            seen_at,
+            current_sha256,
+            change: None,
+            current,
+        };
+        if self.record {
+            self.record_change(&mut change)?;
+        }
+        Ok(Some(change))
+    }
+
+    /// Every tracked file that changed outside the server.
+    pub fn all(&self) -> Result<Vec<ExternalChange>, String> {
+        let mut changes = Vec::new();
+        for path in self.tracker.paths() {
+            if let Some(change) = self.check(&path)? {
+                changes.push(change);
+            }
+        }
+        Ok(changes)
+    }
+
+    /// Logs `change` as an `external` row. Only hashes of seen files are
+    /// kept, so the row has a before content and a diff only when the blob
+    /// store already holds the seen version, e.g. because a tool wrote it.
+    fn record_change(&self, change: &mut ExternalChange) -> Result<(), String> {
+        let before = self.change_log.load_snapshot(Some(&change.seen_sha256))?;
+        let bytes = match &change.current {
+            Snapshot::Content(content) => content.len() as i64,
+            _ => 0,
+        };
+        let record = self.change_log.log_change(
+            &change.path,
+            "external",
+            bytes,
+            change.current_sha256.as_deref().unwrap_or(""),
+            self.session_id,
+            self.run_id,
+            snapshot_diff(&before, &change.current),
+            &before,
+            &change.current,
+        )?;
+        change.change = Some(record);
+        self.tracker.remember(self.fs_ops, &change.path);
+        Ok(())
+    }
+
+    /// The state the most recent logged change left `path` in: a content
+    /// hash, or "" if it removed the file.
+    fn last_logged_state(&self, path: &str) -> Result<Option<String>, String> {
+        let latest = self.change_log.list_changes(
+            ChangeQuery {
+                path: Some(path.to_string()),
+                path_prefix: None,
+                action: None,
+                session_id: None,
+                run_id: None,
+                limit: 1,
+                offset: 0,
+            },
+            false,
+        )?;
+        Ok(latest.first().and_then(|record| {
+            record
+                .effects()
+                .into_iter()
+                .find(|effect| effect.path == path)
+                .and_then(|effect| effect.after.map(str::to_string))
+        }))
+    }
+
+    /// Same content with a new mtime (e.g. `touch`): keep the fast path fast.
+    fn refresh_stamp(&self, path: &str) -> Option<()> {
+        let target = self.fs_ops.resolve_path(path).ok()?;
+        let metadata = fs::metadata(target).ok()?;
+        let (mtime_ms, size) = stamp(&metadata);
+        let mut seen = self.tracker.seen.borrow_mut();
+        let entry = seen.get_mut(path)?;
+        entry.mtime_ms = mtime_ms;
+        entry.size = size;
+        Some(())
+    }
+}
+
+fn stamp(metadata: &fs::Metadata) -> (i64, i64) {
+    let mtime_ms = metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|duration| duration.as_millis() as i64)
+        .unwrap_or(0);
+    (mtime_ms, metadata.len() as i64)
+}
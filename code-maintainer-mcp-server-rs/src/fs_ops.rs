@@ -1,7 +1,17 @@
-use crate::utils::{ensure_path_inside_root, is_binary_buffer, sha256_bytes};
+use crate::policy::PathPolicy;
+use crate::storage::Snapshot;
+use crate::utils::{ensure_path_inside_root, is_binary_buffer, normalize_path, real_path, sha256_bytes};
+use std::collections::{BTreeMap, HashSet};
 use std::fs;
 use std::path::{Path, PathBuf};
-use walkdir::WalkDir;
+use std::sync::Arc;
+use ignore::overrides::OverrideBuilder;
+use ignore::WalkBuilder;
+use regex::RegexBuilder;
+
+/// Directories never searched, even without ignore files.
+const SKIPPED_DIRS: [&str; 3] = ["node_modules", ".git", "dist"];
+const MAX_SNIPPET_BYTES: usize = 400;
 
 #[derive(Clone, Debug)]
 pub struct FsOps {
@@ -10,8 +20,13 @@ pub struct FsOps {
     max_file_bytes: i64,
     max_write_bytes: i64,
     search_limit: usize,
+    policy: Arc<PathPolicy>,
+    allowed_external: Arc<Vec<PathBuf>>,
 }
 
+/// (path, size, sha256, start_line, end_line, total_lines, content)
+pub type FileRange = (String, u64, String, usize, usize, usize, String);
+
 #[derive(Debug, serde::Serialize)]
 pub struct FileEntry {
     pub name: String,
@@ -35,12 +50,74 @@ impl FsOps {
             max_file_bytes,
             max_write_bytes,
             search_limit,
+            policy: Arc::new(PathPolicy::default()),
+            allowed_external: Arc::new(Vec::new()),
         }
     }
 
+    pub fn with_policy(mut self, policy: Arc<PathPolicy>) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    pub fn max_file_bytes(&self) -> i64 {
+        self.max_file_bytes
+    }
+
+    pub fn max_write_bytes(&self) -> i64 {
+        self.max_write_bytes
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn writes_enabled(&self) -> bool {
+        self.allow_writes
+    }
+
+    /// Whether the policy hides `rel_path` (relative to the root, `/`-separated).
+    pub fn is_denied(&self, rel_path: &str) -> bool {
+        self.policy.is_denied(rel_path)
+    }
+
+    /// Canonical directories outside the root that symlinks may point into.
+    pub fn with_allowed_external(mut self, allowed: Arc<Vec<PathBuf>>) -> Self {
+        self.allowed_external = allowed;
+        self
+    }
+
     pub fn resolve_path(&self, rel_path: &str) -> Result<PathBuf, String> {
         let target = Path::new(rel_path);
-        ensure_path_inside_root(&self.root, target)
+        let resolved = ensure_path_inside_root(&self.root, target, &self.allowed_external)?;
+        for rel in self.policy_paths(&resolved)? {
+            self.policy.check_read(&rel)?;
+        }
+        Ok(resolved)
+    }
+
+    /// The paths the policy is checked against: `resolved` itself and, when a
+    /// symlink leads elsewhere inside the root, the path it resolves to. This
+    /// keeps `src/link -> ../.env` as denied as `.env`.
+    fn policy_paths(&self, resolved: &Path) -> Result<Vec<String>, String> {
+        let mut paths = vec![policy_path(&self.root, resolved)];
+        let real_root = real_path(&self.root)?;
+        let real = real_path(resolved)?;
+        if real.starts_with(&real_root) {
+            let rel = policy_path(&real_root, &real);
+            if !paths.contains(&rel) {
+                paths.push(rel);
+            }
+        }
+        Ok(paths)
+    }
+
+    /// Path relative to the workspace root, as reported to clients.
+    pub fn relative(&self, path: &Path) -> String {
+        pathdiff::diff_paths(path, &self.root)
+            .unwrap_or_else(|| path.to_path_buf())
+            .to_string_lossy()
+            .to_string()
     }
 
     pub fn read_file_raw(&self, rel_path: &str) -> Result<(String, u64, String, String), String> {
@@ -54,20 +131,34 @@ impl FsOps {
         }
         let buffer = fs::read(&target).map_err(|err| err.to_string())?;
         if is_binary_buffer(&buffer) {
-            return Err("Binary file not supported.".to_string());
+            return Err("Binary file not supported; use read_file_base64.".to_string());
         }
         let content = String::from_utf8_lossy(&buffer).to_string();
         let hash = sha256_bytes(&buffer);
         Ok((rel_path.to_string(), metadata.len(), hash, content))
     }
 
+    /// Reads any file, text or binary, within the read size limit.
+    pub fn read_file_bytes(&self, rel_path: &str) -> Result<(PathBuf, Vec<u8>), String> {
+        let target = self.resolve_path(rel_path)?;
+        let metadata = fs::metadata(&target).map_err(|err| err.to_string())?;
+        if !metadata.is_file() {
+            return Err("Target is not a file.".to_string());
+        }
+        if metadata.len() as i64 > self.max_file_bytes {
+            return Err(format!("File too large ({} bytes).", metadata.len()));
+        }
+        let buffer = fs::read(&target).map_err(|err| err.to_string())?;
+        Ok((target, buffer))
+    }
+
     pub fn read_file_range(
         &self,
         rel_path: &str,
         start_line: usize,
         end_line: usize,
         with_numbers: bool,
-    ) -> Result<(String, u64, String, usize, usize, usize, String), String> {
+    ) -> Result<FileRange, String> {
         let (path, size, hash, content) = self.read_file_raw(rel_path)?;
         let lines: Vec<String> = content
             .split('\n')
@@ -109,6 +200,9 @@ impl FsOps {
         for entry in read_dir.take(max_entries) {
             let entry = entry.map_err(|err| err.to_string())?;
             let path = entry.path();
+            if self.policy.is_denied(&policy_path(&self.root, &path)) {
+                continue;
+            }
             let metadata = fs::symlink_metadata(&path).map_err(|err| err.to_string())?;
             let file_type = metadata.file_type();
             let kind = if file_type.is_dir() {
@@ -118,47 +212,186 @@ impl FsOps {
             } else {
                 "file"
             };
-            let mtime_ms = metadata
-                .modified()
-                .ok()
-                .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
-                .map(|d| d.as_millis())
-                .unwrap_or(0);
             entries.push(FileEntry {
                 name: entry.file_name().to_string_lossy().to_string(),
-                path: pathdiff::diff_paths(&path, &self.root)
-                    .unwrap_or_else(|| path.to_path_buf())
-                    .to_string_lossy()
-                    .to_string(),
+                path: self.relative(&path),
                 r#type: kind.to_string(),
                 size: metadata.len(),
-                mtime_ms,
+                mtime_ms: mtime_ms(&metadata),
             });
         }
         Ok(entries)
     }
 
-    pub fn search_text(
+    /// Walks files under `start`, honouring `.gitignore`/`.ignore` files unless
+    /// `respect_ignore` is false, always skipping `SKIPPED_DIRS`, and filtering
+    /// by include/exclude globs relative to the workspace root.
+    pub fn walk(
+        &self,
+        start: &Path,
+        include: &[String],
+        exclude: &[String],
+        respect_ignore: bool,
+    ) -> Result<ignore::Walk, String> {
+        let mut overrides = OverrideBuilder::new(&self.root);
+        for glob in include {
+            overrides.add(glob).map_err(|err| err.to_string())?;
+        }
+        for glob in exclude {
+            overrides
+                .add(&format!("!{glob}"))
+                .map_err(|err| err.to_string())?;
+        }
+        let overrides = overrides.build().map_err(|err| err.to_string())?;
+        let mut builder = WalkBuilder::new(start);
+        builder
+            .hidden(false)
+            .parents(respect_ignore)
+            .ignore(respect_ignore)
+            .git_ignore(respect_ignore)
+            .git_exclude(respect_ignore)
+            .git_global(false)
+            .require_git(false)
+            .overrides(overrides)
+            .sort_by_file_name(|a, b| a.cmp(b));
+        let root = self.root.clone();
+        let policy = self.policy.clone();
+        builder.filter_entry(move |entry| {
+            let is_dir = entry.file_type().map(|t| t.is_dir()).unwrap_or(false);
+            if is_dir && SKIPPED_DIRS.contains(&entry.file_name().to_string_lossy().as_ref()) {
+                return false;
+            }
+            !policy.is_denied(&policy_path(&root, entry.path()))
+        });
+        Ok(builder.build())
+    }
+
+    /// Files under `rel_path` matching a glob (relative to the workspace root),
+    /// most recently modified first.
+    pub fn find_files(
         &self,
         pattern: &str,
         rel_path: &str,
-        max_results: Option<usize>,
-    ) -> Result<Vec<SearchResult>, String> {
-        let root = self.resolve_path(rel_path)?;
-        let limit = max_results.unwrap_or(self.search_limit);
-        let mut results: Vec<SearchResult> = Vec::new();
-        let walker = WalkDir::new(root).into_iter().filter_entry(|entry| {
-            if entry.file_type().is_dir() {
-                let name = entry.file_name().to_string_lossy();
-                return name != "node_modules" && name != ".git" && name != "dist";
+        max_results: usize,
+        respect_ignore: bool,
+    ) -> Result<(Vec<FileEntry>, usize), String> {
+        let start = self.resolve_path(rel_path)?;
+        let mut entries = Vec::new();
+        for entry in self
+            .walk(&start, &[pattern.to_string()], &[], respect_ignore)?
+            .filter_map(|e| e.ok())
+        {
+            if !entry.file_type().map(|t| t.is_file()).unwrap_or(false) {
+                continue;
             }
-            true
-        });
+            let metadata = entry.metadata().map_err(|err| err.to_string())?;
+            entries.push(FileEntry {
+                name: entry.file_name().to_string_lossy().to_string(),
+                path: self.relative(entry.path()),
+                r#type: "file".to_string(),
+                size: metadata.len(),
+                mtime_ms: mtime_ms(&metadata),
+            });
+        }
+        let total = entries.len();
+        entries.sort_by(|a, b| b.mtime_ms.cmp(&a.mtime_ms).then_with(|| a.path.cmp(&b.path)));
+        entries.truncate(max_results);
+        Ok((entries, total))
+    }
+
+    /// Directory tree under `rel_path` down to `depth` levels. Every directory
+    /// reports the recursive file count and size of everything below it, even
+    /// when its children are cut off by `depth` or `max_entries`.
+    pub fn tree(
+        &self,
+        rel_path: &str,
+        depth: usize,
+        max_entries: usize,
+        respect_ignore: bool,
+    ) -> Result<TreeNode, String> {
+        let start = self.resolve_path(rel_path)?;
+        if !start.is_dir() {
+            return Err("Target is not a directory.".to_string());
+        }
+        let mut root = DirSummary::default();
+        for entry in self.walk(&start, &[], &[], respect_ignore)?.filter_map(|e| e.ok()) {
+            let Ok(rel) = entry.path().strip_prefix(&start) else {
+                continue;
+            };
+            let parts: Vec<String> = rel
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy().to_string())
+                .collect();
+            let Some((name, parents)) = parts.split_last() else {
+                continue;
+            };
+            let is_dir = entry.file_type().map(|t| t.is_dir()).unwrap_or(false);
+            let size = if is_dir {
+                0
+            } else {
+                entry.metadata().map(|m| m.len()).unwrap_or(0)
+            };
+            let mut node = &mut root;
+            for part in parents {
+                if !is_dir {
+                    node.files += 1;
+                    node.bytes += size;
+                }
+                node = node.dirs.entry(part.clone()).or_default();
+            }
+            if is_dir {
+                node.dirs.entry(name.clone()).or_default();
+            } else {
+                node.files += 1;
+                node.bytes += size;
+                node.entries.insert(name.clone(), size);
+            }
+        }
+        let mut budget = max_entries;
+        let name = start
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| ".".to_string());
+        let path = match self.relative(&start) {
+            rel if rel.is_empty() => ".".to_string(),
+            rel => rel,
+        };
+        Ok(root.into_node(name, path, depth, &mut budget))
+    }
+
+    pub fn search_text(&self, query: &SearchQuery) -> Result<SearchOutput, String> {
+        self.search_text_in(query, None)
+    }
+
+    /// `search_text` limited to `candidates` (relative paths) when given; the
+    /// walk order and filters are unchanged, other files are just not read.
+    pub fn search_text_in(
+        &self,
+        query: &SearchQuery,
+        candidates: Option<&HashSet<String>>,
+    ) -> Result<SearchOutput, String> {
+        let root = self.resolve_path(&query.path)?;
+        let limit = query.max_results.unwrap_or(self.search_limit);
+        let source = if query.regex {
+            query.pattern.clone()
+        } else {
+            regex::escape(&query.pattern)
+        };
+        let matcher = RegexBuilder::new(&source)
+            .case_insensitive(query.case_insensitive)
+            .build()
+            .map_err(|err| format!("Invalid regex: {err}"))?;
+        let mut output = SearchOutput::default();
+        let walker = self.walk(&root, &query.include, &query.exclude, query.respect_ignore)?;
         for entry in walker.filter_map(|e| e.ok()) {
-            if results.len() >= limit {
+            if output.results.len() >= limit || output.files.len() >= limit {
                 break;
             }
-            if !entry.file_type().is_file() {
+            if !entry.file_type().map(|t| t.is_file()).unwrap_or(false) {
+                continue;
+            }
+            let rel = self.relative(entry.path());
+            if candidates.is_some_and(|candidates| !candidates.contains(&rel)) {
                 continue;
             }
             let metadata = entry.metadata().map_err(|err| err.to_string())?;
@@ -170,39 +403,98 @@ impl FsOps {
                 continue;
             }
             let content = String::from_utf8_lossy(&buffer);
-            for (idx, line) in content.split('\n').enumerate() {
-                if results.len() >= limit {
-                    break;
+            let lines: Vec<&str> = content.split('\n').map(|line| line.trim_end_matches('\r')).collect();
+            let mut file_matches = 0usize;
+            for (idx, line) in lines.iter().enumerate() {
+                if !matcher.is_match(line) {
+                    continue;
                 }
-                if line.contains(pattern) {
-                    let trimmed = line.trim();
-                    let snippet = if trimmed.len() > 400 {
-                        trimmed[..400].to_string()
-                    } else {
-                        trimmed.to_string()
-                    };
-                    let rel = pathdiff::diff_paths(entry.path(), &self.root)
-                        .unwrap_or_else(|| entry.path().to_path_buf());
-                    results.push(SearchResult {
-                        path: rel.to_string_lossy().to_string(),
-                        line: idx + 1,
-                        text: snippet,
-                    });
+                file_matches += 1;
+                if query.files_only {
+                    continue;
                 }
+                if output.results.len() >= limit {
+                    break;
+                }
+                let before_start = idx.saturating_sub(query.before);
+                let after_end = (idx + 1 + query.after).min(lines.len());
+                output.results.push(SearchResult {
+                    path: rel.clone(),
+                    line: idx + 1,
+                    text: truncate_line(line.trim()),
+                    before: lines[before_start..idx].iter().map(|l| truncate_line(l)).collect(),
+                    after: lines[idx + 1..after_end].iter().map(|l| truncate_line(l)).collect(),
+                });
+            }
+            if query.files_only && file_matches > 0 {
+                output.files.push(FileMatch {
+                    path: rel,
+                    matches: file_matches,
+                });
             }
         }
-        Ok(results)
+        Ok(output)
     }
 
-    pub fn write_file(&self, rel_path: &str, content: &str) -> Result<WriteResult, String> {
+    /// Runs the same permission, path and size checks as a real write and
+    /// returns the resolved target.
+    pub fn check_write(&self, rel_path: &str, bytes: usize) -> Result<PathBuf, String> {
         if !self.allow_writes {
             return Err("Writes are disabled.".to_string());
         }
         let target = self.resolve_path(rel_path)?;
-        let buffer = content.as_bytes();
-        if buffer.len() as i64 > self.max_write_bytes {
+        for rel in self.policy_paths(&target)? {
+            self.policy.check_write(&rel)?;
+        }
+        if bytes as i64 > self.max_write_bytes {
             return Err("Write exceeds max-write-bytes limit.".to_string());
         }
+        Ok(target)
+    }
+
+    pub fn check_delete(&self, rel_path: &str) -> Result<PathBuf, String> {
+        if !self.allow_writes {
+            return Err("Writes are disabled.".to_string());
+        }
+        let target = self.resolve_path(rel_path)?;
+        for rel in self.policy_paths(&target)? {
+            self.policy.check_write(&rel)?;
+        }
+        Ok(target)
+    }
+
+    /// `check_delete` for `rel_path` and, when it is a directory, for
+    /// everything below it. Symlinks are checked but not followed.
+    pub fn check_delete_tree(&self, rel_path: &str) -> Result<PathBuf, String> {
+        let target = self.check_delete(rel_path)?;
+        let metadata = fs::symlink_metadata(&target);
+        if !metadata.map(|meta| meta.is_dir()).unwrap_or(false) {
+            return Ok(target);
+        }
+        let mut pending = vec![target.clone()];
+        while let Some(dir) = pending.pop() {
+            for entry in fs::read_dir(&dir).map_err(|err| err.to_string())? {
+                let entry = entry.map_err(|err| err.to_string())?;
+                let path = entry.path();
+                let rel = policy_path(&self.root, &path);
+                if self.policy.is_denied(&rel) {
+                    return Err(format!("{rel_path} contains paths the policy denies."));
+                }
+                self.policy.check_write(&rel)?;
+                if entry.file_type().map_err(|err| err.to_string())?.is_dir() {
+                    pending.push(path);
+                }
+            }
+        }
+        Ok(target)
+    }
+
+    pub fn write_file(&self, rel_path: &str, content: &str) -> Result<WriteResult, String> {
+        self.write_bytes(rel_path, content.as_bytes())
+    }
+
+    pub fn write_bytes(&self, rel_path: &str, buffer: &[u8]) -> Result<WriteResult, String> {
+        let target = self.check_write(rel_path, buffer.len())?;
         if let Some(parent) = target.parent() {
             fs::create_dir_all(parent).map_err(|err| err.to_string())?;
         }
@@ -215,14 +507,8 @@ impl FsOps {
     }
 
     pub fn append_file(&self, rel_path: &str, content: &str) -> Result<WriteResult, String> {
-        if !self.allow_writes {
-            return Err("Writes are disabled.".to_string());
-        }
-        let target = self.resolve_path(rel_path)?;
         let buffer = content.as_bytes();
-        if buffer.len() as i64 > self.max_write_bytes {
-            return Err("Write exceeds max-write-bytes limit.".to_string());
-        }
+        let target = self.check_write(rel_path, buffer.len())?;
         if let Some(parent) = target.parent() {
             fs::create_dir_all(parent).map_err(|err| err.to_string())?;
         }
@@ -240,11 +526,22 @@ impl FsOps {
         })
     }
 
-    pub fn delete_path(&self, rel_path: &str) -> Result<String, String> {
-        if !self.allow_writes {
-            return Err("Writes are disabled.".to_string());
+    /// Captures a file's current content for the change log, or `Unknown` for
+    /// directories and files over the write limit.
+    pub fn snapshot(&self, target: &Path) -> Snapshot {
+        match fs::metadata(target) {
+            Err(_) => Snapshot::Missing,
+            Ok(metadata) if !metadata.is_file() || metadata.len() as i64 > self.max_write_bytes => {
+                Snapshot::Unknown
+            }
+            Ok(_) => fs::read(target)
+                .map(Snapshot::Content)
+                .unwrap_or(Snapshot::Unknown),
         }
-        let target = self.resolve_path(rel_path)?;
+    }
+
+    pub fn delete_path(&self, rel_path: &str) -> Result<String, String> {
+        let target = self.check_delete_tree(rel_path)?;
         if target.is_dir() {
             fs::remove_dir_all(&target).map_err(|err| err.to_string())?;
         } else if target.exists() {
@@ -252,6 +549,153 @@ impl FsOps {
         }
         Ok(rel_path.to_string())
     }
+
+    /// Validates a move or copy and returns the resolved (source, destination).
+    /// The source only has to be readable; `check_move` adds the delete checks.
+    pub fn check_transfer(
+        &self,
+        from: &str,
+        to: &str,
+        overwrite: bool,
+    ) -> Result<(PathBuf, PathBuf), String> {
+        let source = self.resolve_path(from)?;
+        let dest = self.check_write(to, 0)?;
+        if !source.exists() {
+            return Err(format!("Source does not exist: {from}"));
+        }
+        if source == self.root {
+            return Err("Cannot move or copy the workspace root.".to_string());
+        }
+        if source == dest {
+            return Err("Source and destination are the same path.".to_string());
+        }
+        if source.is_dir() && dest.starts_with(&source) {
+            return Err("Destination is inside the source directory.".to_string());
+        }
+        if dest.exists() {
+            if !overwrite {
+                return Err(format!("Destination already exists: {to}; pass overwrite to replace it."));
+            }
+            if dest.is_dir() || source.is_dir() {
+                return Err("overwrite only replaces files, not directories.".to_string());
+            }
+        }
+        Ok((source, dest))
+    }
+
+    /// `check_transfer` plus the delete checks on the source and, through
+    /// `check_delete_tree`, everything a moved directory contains.
+    pub fn check_move(&self, from: &str, to: &str, overwrite: bool) -> Result<(PathBuf, PathBuf), String> {
+        let paths = self.check_transfer(from, to, overwrite)?;
+        self.check_delete_tree(from)?;
+        Ok(paths)
+    }
+
+    pub fn move_path(&self, from: &str, to: &str, overwrite: bool) -> Result<(), String> {
+        let (source, dest) = self.check_move(from, to, overwrite)?;
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent).map_err(|err| err.to_string())?;
+        }
+        fs::rename(&source, &dest).map_err(|err| err.to_string())
+    }
+
+    /// Copies a regular file or directory tree. Symlinks, special files and
+    /// denied paths inside a copied directory are skipped and reported, so a
+    /// copy never pulls in content from outside the workspace. A copy that
+    /// fails partway is removed again.
+    pub fn copy_path(&self, from: &str, to: &str, overwrite: bool) -> Result<CopyResult, String> {
+        let (source, dest) = self.check_transfer(from, to, overwrite)?;
+        let metadata = fs::symlink_metadata(&source).map_err(|err| err.to_string())?;
+        if metadata.is_file() {
+            self.check_write(to, metadata.len() as usize)?;
+        } else if !metadata.is_dir() {
+            return Err(format!("Cannot copy {from}: only regular files and directories can be copied."));
+        }
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent).map_err(|err| err.to_string())?;
+        }
+        let existed = dest.exists();
+        let mut result = CopyResult::default();
+        if let Err(err) = self.copy_recursive(&source, &dest, &mut result) {
+            if !existed {
+                let _ = if dest.is_dir() {
+                    fs::remove_dir_all(&dest)
+                } else {
+                    fs::remove_file(&dest)
+                };
+            }
+            return Err(err);
+        }
+        Ok(result)
+    }
+
+    fn copy_recursive(&self, source: &Path, dest: &Path, result: &mut CopyResult) -> Result<(), String> {
+        if self.policy.is_denied(&policy_path(&self.root, source)) {
+            result.skipped.push(self.relative(source));
+            return Ok(());
+        }
+        self.policy.check_write(&policy_path(&self.root, dest))?;
+        let metadata = fs::symlink_metadata(source).map_err(|err| err.to_string())?;
+        if metadata.is_file() {
+            if metadata.len() as i64 > self.max_write_bytes {
+                return Err(format!(
+                    "{} exceeds max-write-bytes limit.",
+                    self.relative(source)
+                ));
+            }
+            result.bytes += fs::copy(source, dest).map_err(|err| err.to_string())?;
+            return Ok(());
+        }
+        if !metadata.is_dir() {
+            result.skipped.push(self.relative(source));
+            return Ok(());
+        }
+        fs::create_dir_all(dest).map_err(|err| err.to_string())?;
+        let mut entries: Vec<_> = fs::read_dir(source)
+            .map_err(|err| err.to_string())?
+            .filter_map(|entry| entry.ok())
+            .collect();
+        entries.sort_by_key(|entry| entry.file_name());
+        for entry in entries {
+            self.copy_recursive(&entry.path(), &dest.join(entry.file_name()), result)?;
+        }
+        Ok(())
+    }
+
+    /// Creates a directory and any missing parents. Returns false if it
+    /// already existed.
+    pub fn make_dir(&self, rel_path: &str) -> Result<bool, String> {
+        let target = self.check_write(rel_path, 0)?;
+        if target.is_dir() {
+            return Ok(false);
+        }
+        if target.exists() {
+            return Err(format!("A file already exists at {rel_path}."));
+        }
+        fs::create_dir_all(&target).map_err(|err| err.to_string())?;
+        Ok(true)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SearchQuery {
+    pub pattern: String,
+    pub path: String,
+    pub max_results: Option<usize>,
+    pub regex: bool,
+    pub case_insensitive: bool,
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+    pub before: usize,
+    pub after: usize,
+    pub files_only: bool,
+    pub respect_ignore: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct SearchOutput {
+    pub results: Vec<SearchResult>,
+    pub files: Vec<FileMatch>,
 }
 
 #[derive(Debug, serde::Serialize)]
@@ -259,6 +703,113 @@ pub struct SearchResult {
     pub path: String,
     pub line: usize,
     pub text: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub before: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub after: Vec<String>,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct FileMatch {
+    pub path: String,
+    pub matches: usize,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct TreeNode {
+    pub name: String,
+    pub path: String,
+    pub r#type: String,
+    pub size: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub files: Option<usize>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<TreeNode>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub truncated: bool,
+}
+
+#[derive(Default)]
+struct DirSummary {
+    files: usize,
+    bytes: u64,
+    dirs: BTreeMap<String, DirSummary>,
+    entries: BTreeMap<String, u64>,
+}
+
+impl DirSummary {
+    fn into_node(self, name: String, path: String, depth: usize, budget: &mut usize) -> TreeNode {
+        let mut node = TreeNode {
+            name,
+            path: path.clone(),
+            r#type: "dir".to_string(),
+            size: self.bytes,
+            files: Some(self.files),
+            children: Vec::new(),
+            truncated: false,
+        };
+        if self.dirs.is_empty() && self.entries.is_empty() {
+            return node;
+        }
+        if depth == 0 {
+            node.truncated = true;
+            return node;
+        }
+        let child_path = |child: &str| {
+            if path.is_empty() || path == "." {
+                child.to_string()
+            } else {
+                format!("{path}/{child}")
+            }
+        };
+        for (dir_name, summary) in self.dirs {
+            if *budget == 0 {
+                node.truncated = true;
+                return node;
+            }
+            *budget -= 1;
+            let dir_path = child_path(&dir_name);
+            node.children
+                .push(summary.into_node(dir_name, dir_path, depth - 1, budget));
+        }
+        for (file_name, size) in self.entries {
+            if *budget == 0 {
+                node.truncated = true;
+                return node;
+            }
+            *budget -= 1;
+            node.children.push(TreeNode {
+                path: child_path(&file_name),
+                name: file_name,
+                r#type: "file".to_string(),
+                size,
+                files: None,
+                children: Vec::new(),
+                truncated: false,
+            });
+        }
+        node
+    }
+}
+
+fn mtime_ms(metadata: &fs::Metadata) -> u128 {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|d| d.as_millis())
+        .unwrap_or(0)
+}
+
+fn truncate_line(line: &str) -> String {
+    if line.len() <= MAX_SNIPPET_BYTES {
+        return line.to_string();
+    }
+    let mut end = MAX_SNIPPET_BYTES;
+    while !line.is_char_boundary(end) {
+        end -= 1;
+    }
+    line[..end].to_string()
 }
 
 #[derive(Debug, serde::Serialize)]
@@ -267,3 +818,96 @@ pub struct WriteResult {
     pub sha256: String,
     pub path: String,
 }
+
+#[derive(Debug, Default, serde::Serialize)]
+pub struct CopyResult {
+    pub bytes: u64,
+    /// Entries of a copied directory that were left out: symlinks, special
+    /// files and paths the policy denies.
+    pub skipped: Vec<String>,
+}
+
+/// `path` relative to `root` with `/` separators, as policy globs expect.
+fn policy_path(root: &Path, path: &Path) -> String {
+    let root = normalize_path(root);
+    path.strip_prefix(&root)
+        .unwrap_or(path)
+        .components()
+        .map(|part| part.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::policy::PolicyRules;
+    use crate::utils::Scratch;
+    use std::os::unix::fs::symlink;
+
+    #[test]
+    fn copy_reports_skipped_entries() {
+        let scratch = Scratch::new();
+        let policy = PathPolicy::new(PolicyRules {
+            deny: vec!["**/.env".to_string()],
+            ..PolicyRules::default()
+        })
+        .unwrap();
+        let fs_ops = scratch.fs_ops().with_policy(Arc::new(policy));
+        fs::create_dir(scratch.0.join("app")).unwrap();
+        fs::write(scratch.0.join("app/main.rs"), "fn main() {}\n").unwrap();
+        fs::write(scratch.0.join("app/.env"), "KEY=1\n").unwrap();
+        symlink("main.rs", scratch.0.join("app/link.rs")).unwrap();
+
+        let result = fs_ops.copy_path("app", "copy", false).unwrap();
+        assert_eq!(result.bytes, 13);
+        assert_eq!(result.skipped, vec!["app/.env", "app/link.rs"]);
+        assert!(scratch.0.join("copy/main.rs").is_file());
+        assert!(!scratch.0.join("copy/.env").exists());
+    }
+
+    #[test]
+    fn read_only_sources_can_be_copied_but_not_moved() {
+        let scratch = Scratch::new();
+        let policy = PathPolicy::new(PolicyRules {
+            readonly: vec!["vendor".to_string()],
+            ..PolicyRules::default()
+        })
+        .unwrap();
+        let fs_ops = scratch.fs_ops().with_policy(Arc::new(policy));
+        fs::create_dir_all(scratch.0.join("vendor/lib")).unwrap();
+        fs::write(scratch.0.join("vendor/lib/a.rs"), "a").unwrap();
+        fs::create_dir(scratch.0.join(".git")).unwrap();
+        fs::write(scratch.0.join(".git/HEAD"), "ref: refs/heads/main\n").unwrap();
+
+        fs_ops.copy_path("vendor/lib", "lib", false).unwrap();
+        fs_ops.copy_path(".git/HEAD", "HEAD.txt", false).unwrap();
+        assert!(scratch.0.join("lib/a.rs").is_file());
+        assert!(fs_ops.move_path("vendor/lib", "moved", false).is_err());
+        assert!(fs_ops.copy_path("lib", "vendor/copy", false).is_err());
+    }
+
+    #[test]
+    fn copy_rejects_symlink_sources() {
+        let scratch = Scratch::new();
+        let fs_ops = scratch.fs_ops();
+        fs::write(scratch.0.join("a.txt"), "a").unwrap();
+        symlink("a.txt", scratch.0.join("link.txt")).unwrap();
+        let err = fs_ops.copy_path("link.txt", "b.txt", false).unwrap_err();
+        assert!(err.contains("only regular files and directories"), "{err}");
+        assert!(!scratch.0.join("b.txt").exists());
+    }
+
+    #[test]
+    fn failed_copy_leaves_no_partial_tree() {
+        let scratch = Scratch::new();
+        let fs_ops = FsOps::new(scratch.0.clone(), true, 1024, 1024, 100);
+        fs::create_dir(scratch.0.join("src")).unwrap();
+        fs::write(scratch.0.join("src/a.txt"), "small").unwrap();
+        fs::write(scratch.0.join("src/b.txt"), vec![b'x'; 2048]).unwrap();
+
+        let err = fs_ops.copy_path("src", "dest", false).unwrap_err();
+        assert!(err.contains("src/b.txt exceeds max-write-bytes"), "{err}");
+        assert!(!scratch.0.join("dest").exists());
+    }
+}
@@ -0,0 +1,631 @@
+use crate::fs_ops::FsOps;
+use crate::utils::is_binary_buffer;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chrono::DateTime;
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::process::Command;
+
+/// Field and record separators for `--format` output.
+const FIELD_SEP: char = '\x1f';
+const RECORD_SEP: char = '\x1e';
+
+/// Runs the local `git` binary in the workspace root. Paths in and out are
+/// relative to the workspace root and pass through the same path policy as
+/// the file tools: denied paths are rejected as arguments and left out of
+/// every listing and diff.
+pub struct Git<'a> {
+    fs_ops: &'a FsOps,
+    max_output_bytes: usize,
+}
+
+impl<'a> Git<'a> {
+    pub fn new(fs_ops: &'a FsOps, max_output_bytes: usize) -> Self {
+        Self {
+            fs_ops,
+            max_output_bytes,
+        }
+    }
+
+    pub fn status(&self) -> Result<Value, String> {
+        let prefix = self.prefix()?;
+        let raw = self.run_text(&["status", "--porcelain=v2", "-z", "--branch", "--untracked-files=all", "--", "."])?;
+        Ok(parse_status(&raw, |path| self.workspace_path(&prefix, path)))
+    }
+
+    /// Working tree (or, with `staged`, index) changes with per-file line counts.
+    pub fn diff(&self, path: Option<&str>, staged: bool) -> Result<Value, String> {
+        let pathspec = self.pathspec(path)?;
+        let mut args = vec!["diff", "--relative", "--no-color", "--no-ext-diff", "--no-textconv", "--find-renames"];
+        if staged {
+            args.push("--cached");
+        }
+        let mut numstat_args = args.clone();
+        numstat_args.extend(["--numstat", "-z", "--", &pathspec]);
+        let files = self.parse_numstat(&self.run_text(&numstat_args)?);
+        args.extend(["--", &pathspec]);
+        let diff = self.filter_diff(&self.run_text(&args)?);
+        let (diff, truncated) = self.truncate(diff);
+        Ok(json!({
+            "staged": staged,
+            "files": files,
+            "diff": diff,
+            "truncated": truncated
+        }))
+    }
+
+    pub fn log(&self, path: Option<&str>, limit: usize) -> Result<Value, String> {
+        let pathspec = self.pathspec(path)?;
+        let limit = format!("-n{}", limit.max(1));
+        let format = format!("--format={RECORD_SEP}%H{FIELD_SEP}%h{FIELD_SEP}%an{FIELD_SEP}%ae{FIELD_SEP}%aI{FIELD_SEP}%s");
+        let mut args = vec!["log", &limit, &format, "--name-status", "--relative", "--find-renames"];
+        let follow = path.is_some() && self.fs_ops.resolve_path(&pathspec).map(|p| p.is_file()).unwrap_or(false);
+        if follow {
+            args.push("--follow");
+        }
+        args.extend(["--", &pathspec]);
+        let raw = self.run_text(&args)?;
+        let mut commits = Vec::new();
+        for record in raw.split(RECORD_SEP).filter(|r| !r.trim().is_empty()) {
+            let mut lines = record.lines();
+            let header: Vec<&str> = lines.next().unwrap_or("").split(FIELD_SEP).collect();
+            if header.len() < 6 {
+                continue;
+            }
+            let files: Vec<Value> = lines
+                .filter_map(|line| {
+                    let mut parts = line.split('\t');
+                    let status = parts.next().filter(|s| !s.is_empty())?;
+                    let paths: Vec<&str> = parts.collect();
+                    if paths.is_empty() || paths.iter().any(|p| self.fs_ops.is_denied(p)) {
+                        return None;
+                    }
+                    let path = paths[paths.len() - 1];
+                    let orig_path = (paths.len() > 1).then(|| paths[0]);
+                    Some(json!({ "status": status, "path": path, "orig_path": orig_path }))
+                })
+                .collect();
+            commits.push(json!({
+                "commit": header[0],
+                "short": header[1],
+                "author": header[2],
+                "email": header[3],
+                "date": header[4],
+                "subject": header[5],
+                "files": files
+            }));
+        }
+        Ok(json!({ "commits": commits }))
+    }
+
+    /// Line-by-line authorship of `path`, optionally limited to a 1-based
+    /// inclusive line range.
+    pub fn blame(&self, path: &str, start_line: Option<usize>, end_line: Option<usize>) -> Result<Value, String> {
+        let pathspec = self.pathspec(Some(path))?;
+        let mut args = vec!["blame".to_string(), "--porcelain".to_string(), "--no-textconv".to_string()];
+        if start_line.is_some() || end_line.is_some() {
+            let start = start_line.unwrap_or(1).max(1);
+            let end = end_line.map(|end| end.to_string()).unwrap_or_default();
+            args.push(format!("-L{start},{end}"));
+        }
+        args.extend(["--".to_string(), pathspec.clone()]);
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        let raw = self.run_text(&args)?;
+
+        let mut commits: HashMap<String, Map<String, Value>> = HashMap::new();
+        let mut lines = Vec::new();
+        let mut current: Option<(String, usize)> = None;
+        for line in raw.lines() {
+            if let Some(content) = line.strip_prefix('\t') {
+                if let Some((commit, number)) = current.take() {
+                    lines.push(json!({ "line": number, "commit": commit, "content": content }));
+                }
+                continue;
+            }
+            match &current {
+                None => {
+                    let mut parts = line.split(' ');
+                    let commit = parts.next().unwrap_or("").to_string();
+                    let number = parts.nth(1).and_then(|v| v.parse().ok()).unwrap_or(0);
+                    commits.entry(commit.clone()).or_default();
+                    current = Some((commit, number));
+                }
+                Some((commit, _)) => {
+                    let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+                    let info = commits.entry(commit.clone()).or_default();
+                    match key {
+                        "author" => {
+                            info.insert("author".into(), json!(value));
+                        }
+                        "author-mail" => {
+                            info.insert("email".into(), json!(value.trim_matches(['<', '>'])));
+                        }
+                        "author-time" => {
+                            let date = value
+                                .parse::<i64>()
+                                .ok()
+                                .and_then(|secs| DateTime::from_timestamp(secs, 0))
+                                .map(|date| date.to_rfc3339());
+                            info.insert("date".into(), json!(date));
+                        }
+                        "summary" => {
+                            info.insert("summary".into(), json!(value));
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+        Ok(json!({
+            "path": pathspec,
+            "commits": commits,
+            "lines": lines
+        }))
+    }
+
+    /// `rev:path` shows a file as of a revision; a bare `rev` shows the commit
+    /// with its changed files and patch.
+    pub fn show(&self, spec: &str) -> Result<Value, String> {
+        let spec = spec.trim();
+        if spec.is_empty() || spec.starts_with('-') {
+            return Err("rev is required".to_string());
+        }
+        if let Some((rev, path)) = spec.split_once(':') {
+            let pathspec = self.pathspec(Some(path))?;
+            let rev = if rev.is_empty() { "HEAD" } else { rev };
+            let object = format!("{rev}:./{pathspec}");
+            if self.run_text(&["cat-file", "-t", &object])?.trim() == "tree" {
+                return self.show_tree(rev, &pathspec, &object);
+            }
+            let bytes = self.run(&["show", "--no-textconv", &object])?;
+            let size = bytes.len();
+            let mut result = json!({ "rev": rev, "path": pathspec, "size_bytes": size });
+            if is_binary_buffer(&bytes) {
+                if size > self.max_output_bytes {
+                    return Err(format!("File too large ({size} bytes)."));
+                }
+                result["content_base64"] = json!(BASE64.encode(&bytes));
+            } else {
+                let (content, truncated) = self.truncate(String::from_utf8_lossy(&bytes).to_string());
+                result["content"] = json!(content);
+                result["truncated"] = json!(truncated);
+            }
+            return Ok(result);
+        }
+        let format = format!("--format=%H{FIELD_SEP}%an{FIELD_SEP}%ae{FIELD_SEP}%aI{FIELD_SEP}%B");
+        let header = self.run_text(&["show", "-s", &format, spec, "--"])?;
+        let fields: Vec<&str> = header.splitn(5, FIELD_SEP).collect();
+        if fields.len() < 5 {
+            return Err(format!("Unexpected git show output for {spec}."));
+        }
+        let numstat = self.run_text(&["show", "--format=", "--relative", "--numstat", "-z", "--no-textconv", "--find-renames", spec, "--"])?;
+        let patch = self.run_text(&[
+            "show",
+            "--format=",
+            "--relative",
+            "--no-color",
+            "--no-ext-diff",
+            "--no-textconv",
+            "--find-renames",
+            spec,
+            "--",
+        ])?;
+        let (diff, truncated) = self.truncate(self.filter_diff(&patch));
+        Ok(json!({
+            "commit": fields[0],
+            "author": fields[1],
+            "email": fields[2],
+            "date": fields[3],
+            "message": fields[4].trim_end(),
+            "files": self.parse_numstat(&numstat),
+            "diff": diff,
+            "truncated": truncated
+        }))
+    }
+
+    /// Lists a directory as of a revision, without the entries the policy denies.
+    fn show_tree(&self, rev: &str, pathspec: &str, object: &str) -> Result<Value, String> {
+        let raw = self.run_text(&["ls-tree", "-z", object])?;
+        let mut entries = Vec::new();
+        for entry in raw.split('\0').filter(|e| !e.is_empty()) {
+            let Some((meta, name)) = entry.split_once('\t') else {
+                continue;
+            };
+            let path = if pathspec == "." { name.to_string() } else { format!("{pathspec}/{name}") };
+            if self.fs_ops.is_denied(&path) {
+                continue;
+            }
+            let kind = meta.split(' ').nth(1).unwrap_or("");
+            entries.push(json!({ "name": name, "path": path, "type": kind }));
+        }
+        Ok(json!({ "rev": rev, "path": pathspec, "entries": entries }))
+    }
+
+    /// Adds (or with `unstage`, removes) paths to the index. Requires writes
+    /// and write access to every path under the policy.
+    pub fn stage(&self, paths: &[String], unstage: bool) -> Result<Value, String> {
+        if paths.is_empty() {
+            return Err("paths is required".to_string());
+        }
+        let mut pathspecs = Vec::new();
+        for path in paths {
+            let target = self.fs_ops.check_write(path, 0)?;
+            pathspecs.push(self.spec_for(&self.fs_ops.relative(&target)));
+        }
+        // A directory stages everything below it, so check each file git
+        // would touch, not just the path given.
+        let mut list_args = if unstage {
+            vec!["diff", "--cached", "--relative", "--name-only", "-z", "--"]
+        } else {
+            vec!["ls-files", "-z", "--modified", "--deleted", "--others", "--exclude-standard", "--"]
+        };
+        list_args.extend(pathspecs.iter().map(String::as_str));
+        for path in self.run_text(&list_args)?.split('\0').filter(|p| !p.is_empty()) {
+            if self.fs_ops.is_denied(path) {
+                return Err("The paths to stage include files the policy denies.".to_string());
+            }
+            self.fs_ops.check_write(path, 0)?;
+        }
+        let mut args = if unstage {
+            vec!["restore", "--staged", "--"]
+        } else {
+            vec!["add", "-A", "--"]
+        };
+        args.extend(pathspecs.iter().map(String::as_str));
+        self.run(&args)?;
+        Ok(json!({
+            "unstaged": unstage,
+            "paths": pathspecs,
+            "staged": self.staged_files()?
+        }))
+    }
+
+    /// Commits the index. Everything staged must lie inside the workspace
+    /// root and be writable under the policy.
+    pub fn commit(&self, message: &str) -> Result<Value, String> {
+        if !self.fs_ops.writes_enabled() {
+            return Err("Writes are disabled.".to_string());
+        }
+        if message.trim().is_empty() {
+            return Err("message is required".to_string());
+        }
+        let prefix = self.prefix()?;
+        let staged = self.run_text(&["diff", "--cached", "--name-only", "-z"])?;
+        let mut count = 0;
+        for repo_path in staged.split('\0').filter(|p| !p.is_empty()) {
+            let Some(path) = repo_path.strip_prefix(&prefix) else {
+                return Err(format!("Staged path {repo_path} is outside the workspace root."));
+            };
+            self.fs_ops.check_write(path, 0)?;
+            count += 1;
+        }
+        if count == 0 {
+            return Err("Nothing is staged.".to_string());
+        }
+        self.run(&["commit", "-q", "-m", message])?;
+        let head = self.run_text(&["rev-parse", "HEAD"])?;
+        let mut result = self.show(head.trim())?;
+        if let Some(object) = result.as_object_mut() {
+            object.remove("diff");
+            object.remove("truncated");
+        }
+        Ok(result)
+    }
+
+    fn staged_files(&self) -> Result<Vec<Value>, String> {
+        let raw = self.run_text(&["diff", "--cached", "--relative", "--name-status", "-z"])?;
+        let mut files = Vec::new();
+        let mut fields = raw.split('\0').filter(|f| !f.is_empty());
+        while let Some(status) = fields.next() {
+            let mut path = fields.next().unwrap_or("");
+            let mut orig_path = None;
+            if status.starts_with('R') || status.starts_with('C') {
+                orig_path = Some(path);
+                path = fields.next().unwrap_or("");
+            }
+            if self.fs_ops.is_denied(path) {
+                continue;
+            }
+            files.push(json!({ "status": status, "path": path, "orig_path": orig_path }));
+        }
+        Ok(files)
+    }
+
+    /// Parses `--numstat -z` output; binary files report null counts.
+    fn parse_numstat(&self, raw: &str) -> Vec<Value> {
+        let mut files = Vec::new();
+        let mut fields = raw.split('\0');
+        while let Some(field) = fields.next() {
+            let field = field.trim_start_matches('\n');
+            if field.is_empty() {
+                continue;
+            }
+            let mut parts = field.splitn(3, '\t');
+            let added = parts.next().and_then(|v| v.parse::<i64>().ok());
+            let removed = parts.next().and_then(|v| v.parse::<i64>().ok());
+            let mut path = parts.next().unwrap_or("").to_string();
+            let mut orig_path = None;
+            if path.is_empty() {
+                orig_path = fields.next().map(str::to_string);
+                path = fields.next().unwrap_or("").to_string();
+            }
+            if self.fs_ops.is_denied(&path) || orig_path.as_deref().map(|p| self.fs_ops.is_denied(p)).unwrap_or(false) {
+                continue;
+            }
+            files.push(json!({
+                "path": path,
+                "orig_path": orig_path,
+                "added": added,
+                "removed": removed,
+                "binary": added.is_none()
+            }));
+        }
+        files
+    }
+
+    /// Drops the sections of a unified diff that touch denied paths.
+    fn filter_diff(&self, diff: &str) -> String {
+        let mut out = String::new();
+        let mut section = String::new();
+        let flush = |section: &mut String, out: &mut String| {
+            if !section.is_empty() && !section_paths(section).iter().any(|p| self.fs_ops.is_denied(p)) {
+                out.push_str(section);
+            }
+            section.clear();
+        };
+        for line in diff.split_inclusive('\n') {
+            if line.starts_with("diff --git ") {
+                flush(&mut section, &mut out);
+            }
+            section.push_str(line);
+        }
+        flush(&mut section, &mut out);
+        out
+    }
+
+    fn truncate(&self, mut text: String) -> (String, bool) {
+        if text.len() <= self.max_output_bytes {
+            return (text, false);
+        }
+        let mut end = self.max_output_bytes;
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        text.truncate(end);
+        (text, true)
+    }
+
+    /// Where the workspace root sits inside the repository, e.g. `app/` or "".
+    fn prefix(&self) -> Result<String, String> {
+        Ok(self.run_text(&["rev-parse", "--show-prefix"])?.trim_end_matches('\n').to_string())
+    }
+
+    /// Maps a repository-relative path to the workspace, or `None` when it is
+    /// outside the root or denied.
+    fn workspace_path(&self, prefix: &str, repo_path: &str) -> Option<String> {
+        let path = repo_path.strip_prefix(prefix)?;
+        if path.is_empty() || self.fs_ops.is_denied(path.trim_end_matches('/')) {
+            return None;
+        }
+        Some(path.to_string())
+    }
+
+    /// A policy-checked pathspec relative to the root; the whole root if none.
+    fn pathspec(&self, path: Option<&str>) -> Result<String, String> {
+        match path.map(str::trim).filter(|p| !p.is_empty()) {
+            Some(path) => {
+                let target = self.fs_ops.resolve_path(path)?;
+                Ok(self.spec_for(&self.fs_ops.relative(&target)))
+            }
+            None => Ok(".".to_string()),
+        }
+    }
+
+    fn spec_for(&self, rel_path: &str) -> String {
+        if rel_path.is_empty() {
+            ".".to_string()
+        } else {
+            rel_path.replace('\\', "/")
+        }
+    }
+
+    fn run_text(&self, args: &[&str]) -> Result<String, String> {
+        self.run(args).map(|bytes| String::from_utf8_lossy(&bytes).to_string())
+    }
+
+    fn run(&self, args: &[&str]) -> Result<Vec<u8>, String> {
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(self.fs_ops.root())
+            .args(["--literal-pathspecs", "-c", "core.quotepath=off", "-c", "color.ui=false"])
+            // Repository config must not make a read run programs.
+            .args(["-c", "core.fsmonitor=", "-c", "core.hooksPath=/dev/null"])
+            .args(args)
+            .env("GIT_TERMINAL_PROMPT", "0")
+            .env("GIT_OPTIONAL_LOCKS", "0")
+            .output()
+            .map_err(|err| format!("Failed to run git: {err}"))?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let message = stderr.trim();
+            if message.contains("not a git repository") {
+                return Err("Workspace root is not inside a git repository.".to_string());
+            }
+            return Err(format!("git {} failed: {message}", args.first().unwrap_or(&"")));
+        }
+        Ok(output.stdout)
+    }
+}
+
+/// Parses `git status --porcelain=v2 -z --branch` output. `workspace_path`
+/// maps repository paths into the workspace and drops the ones outside the
+/// root or denied.
+fn parse_status(raw: &str, workspace_path: impl Fn(&str) -> Option<String>) -> Value {
+    let mut branch = Map::new();
+    let mut entries = Vec::new();
+    let mut fields = raw.split('\0');
+    while let Some(field) = fields.next() {
+        if let Some(header) = field.strip_prefix("# ") {
+            let (key, value) = header.split_once(' ').unwrap_or((header, ""));
+            match key {
+                "branch.oid" => branch.insert("oid".into(), json!(value)),
+                "branch.head" => branch.insert("head".into(), json!(value)),
+                "branch.upstream" => branch.insert("upstream".into(), json!(value)),
+                "branch.ab" => {
+                    let mut counts = value.split(' ').map(|v| v.trim_start_matches(['+', '-']).parse::<i64>().unwrap_or(0));
+                    branch.insert("ahead".into(), json!(counts.next().unwrap_or(0)));
+                    branch.insert("behind".into(), json!(counts.next().unwrap_or(0)))
+                }
+                _ => None,
+            };
+            continue;
+        }
+        let (kind, rest) = field.split_once(' ').unwrap_or((field, ""));
+        let (index, worktree, path, orig_path) = match kind {
+            "1" => {
+                let parts: Vec<&str> = rest.splitn(8, ' ').collect();
+                (xy(parts[0], 0), xy(parts[0], 1), parts.get(7).copied(), None)
+            }
+            "2" => {
+                let parts: Vec<&str> = rest.splitn(9, ' ').collect();
+                (xy(parts[0], 0), xy(parts[0], 1), parts.get(8).copied(), fields.next())
+            }
+            "u" => {
+                let parts: Vec<&str> = rest.splitn(10, ' ').collect();
+                (xy(parts[0], 0), xy(parts[0], 1), parts.get(9).copied(), None)
+            }
+            "?" => ("?", "?", Some(rest), None),
+            _ => continue,
+        };
+        let Some(path) = path.and_then(&workspace_path) else {
+            continue;
+        };
+        let orig_path = orig_path.and_then(&workspace_path);
+        let status = match kind {
+            "2" => "renamed",
+            "u" => "unmerged",
+            "?" => "untracked",
+            _ => "changed",
+        };
+        entries.push(json!({
+            "path": path,
+            "orig_path": orig_path,
+            "status": status,
+            "index": index,
+            "worktree": worktree,
+            "staged": kind != "?" && index != ".",
+            "unstaged": kind == "?" || worktree != "."
+        }));
+    }
+    json!({
+        "branch": branch,
+        "clean": entries.is_empty(),
+        "entries": entries
+    })
+}
+
+fn xy(code: &str, index: usize) -> &str {
+    code.get(index..index + 1).unwrap_or(".")
+}
+
+/// Paths named by one `diff --git` section.
+fn section_paths(section: &str) -> Vec<&str> {
+    let mut paths = Vec::new();
+    for line in section.lines() {
+        if let Some(rest) = line.strip_prefix("diff --git ") {
+            // "a/<p> b/<p>" splits evenly when the paths match.
+            let half = rest.len().saturating_sub(1) / 2;
+            if rest.len() > 5 && rest.is_char_boundary(half) && rest.is_char_boundary(half + 1) {
+                let (a, b) = (&rest[..half], &rest[half + 1..]);
+                if let (Some(a), Some(b)) = (a.strip_prefix("a/"), b.strip_prefix("b/")) {
+                    if a == b {
+                        paths.push(a);
+                    }
+                }
+            }
+            continue;
+        }
+        let named = line
+            .strip_prefix("--- a/")
+            .or_else(|| line.strip_prefix("+++ b/"))
+            .or_else(|| line.strip_prefix("rename from "))
+            .or_else(|| line.strip_prefix("rename to "))
+            .or_else(|| line.strip_prefix("copy from "))
+            .or_else(|| line.strip_prefix("copy to "));
+        if let Some(path) = named {
+            paths.push(path);
+        }
+        if line.starts_with("@@") {
+            break;
+        }
+    }
+    paths
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn workspace(path: &str) -> Option<String> {
+        let path = path.strip_prefix("app/")?;
+        (!path.starts_with("secret")).then(|| path.to_string())
+    }
+
+    #[test]
+    fn parses_branch_headers() {
+        let raw = "# branch.oid 1234abcd\0# branch.head main\0# branch.upstream origin/main\0# branch.ab +2 -1\0";
+        let status = parse_status(raw, workspace);
+        assert_eq!(status["branch"]["head"], "main");
+        assert_eq!(status["branch"]["upstream"], "origin/main");
+        assert_eq!(status["branch"]["ahead"], 2);
+        assert_eq!(status["branch"]["behind"], 1);
+        assert_eq!(status["clean"], true);
+    }
+
+    #[test]
+    fn parses_changed_renamed_unmerged_and_untracked_entries() {
+        let raw = [
+            "1 M. N... 100644 100644 100644 aaa bbb app/src/lib.rs",
+            "2 R. N... 100644 100644 100644 aaa bbb R100 app/src/new name.rs",
+            "app/src/old.rs",
+            "u UU N... 100644 100644 100644 100644 aaa bbb ccc app/conflict.rs",
+            "? app/notes.txt",
+            "",
+        ]
+        .join("\0");
+        let status = parse_status(&raw, workspace);
+        let entries = status["entries"].as_array().unwrap();
+        assert_eq!(entries.len(), 4);
+        assert_eq!(entries[0]["path"], "src/lib.rs");
+        assert_eq!(entries[0]["status"], "changed");
+        assert_eq!(entries[0]["staged"], true);
+        assert_eq!(entries[0]["unstaged"], false);
+        assert_eq!(entries[1]["path"], "src/new name.rs");
+        assert_eq!(entries[1]["orig_path"], "src/old.rs");
+        assert_eq!(entries[1]["status"], "renamed");
+        assert_eq!(entries[2]["status"], "unmerged");
+        assert_eq!(entries[3]["path"], "notes.txt");
+        assert_eq!(entries[3]["status"], "untracked");
+        assert_eq!(entries[3]["staged"], false);
+        assert_eq!(status["clean"], false);
+    }
+
+    #[test]
+    fn skips_paths_outside_the_workspace_or_denied() {
+        let raw = "1 .M N... 100644 100644 100644 aaa bbb other/lib.rs\0? app/secret.env\0? app/ok.rs\0";
+        let status = parse_status(raw, workspace);
+        let entries = status["entries"].as_array().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0]["path"], "ok.rs");
+        assert_eq!(entries[0]["unstaged"], true);
+    }
+
+    #[test]
+    fn section_paths_reads_headers_and_renames() {
+        let section = "diff --git a/src/a.rs b/src/a.rs\n--- a/src/a.rs\n+++ b/src/a.rs\n@@ -1 +1 @@\n--- a/not/a/header\n";
+        assert_eq!(section_paths(section), ["src/a.rs", "src/a.rs", "src/a.rs"]);
+        let rename = "diff --git a/old.rs b/new.rs\nsimilarity index 100%\nrename from old.rs\nrename to new.rs\n";
+        assert_eq!(section_paths(rename), ["old.rs", "new.rs"]);
+    }
+}
@@ -0,0 +1,201 @@
+use crate::diff::snapshot_diff;
+use crate::storage::{normalize_log_path, ChangeLogStore, ChangeRecord, Snapshot};
+use crate::utils::{is_binary_buffer, sha256_bytes};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use serde_json::{json, Value};
+
+/// A point in the change log: right after a change, or a wall-clock time.
+pub enum AsOf<'a> {
+    Change(&'a str),
+    Timestamp(&'a str),
+}
+
+/// Reads `change_id` or `timestamp` (RFC 3339) from tool or query arguments.
+pub fn parse_as_of<'a>(change_id: Option<&'a str>, timestamp: Option<&'a str>) -> Result<AsOf<'a>, String> {
+    match (change_id, timestamp) {
+        (Some(id), None) => Ok(AsOf::Change(id)),
+        (None, Some(ts)) => Ok(AsOf::Timestamp(ts)),
+        _ => Err("Pass exactly one of change_id or timestamp.".to_string()),
+    }
+}
+
+/// Content of `path` as of `at`, replayed from the before/after snapshots of
+/// the nearest logged change. Before a path's first change its before-content
+/// is used; after its last, its after-content.
+pub fn file_at(store: &ChangeLogStore, path: &str, at: &AsOf) -> Result<Value, String> {
+    let path = normalize_log_path(path);
+    let path = path.as_str();
+    let (created_at, rowid) = match at {
+        AsOf::Change(id) => store
+            .change_order(id)?
+            .ok_or_else(|| format!("Change not found: {id}"))?,
+        AsOf::Timestamp(ts) => {
+            let parsed = DateTime::parse_from_rfc3339(ts)
+                .map_err(|err| format!("Invalid timestamp {ts}: {err}"))?;
+            (parsed.with_timezone(&Utc).to_rfc3339(), i64::MAX)
+        }
+    };
+    let (record, snapshot) = match store.change_near(path, &created_at, rowid, false)? {
+        Some(record) => {
+            let hash = state_after(&record, path);
+            let snapshot = store.load_snapshot(hash)?;
+            (record, snapshot)
+        }
+        None => {
+            let record = store
+                .change_near(path, &created_at, rowid, true)?
+                .ok_or_else(|| format!("No logged changes for {path}."))?;
+            let hash = state_before(&record, path);
+            let snapshot = store.load_snapshot(hash)?;
+            (record, snapshot)
+        }
+    };
+    let mut result = json!({
+        "path": path,
+        "as_of": created_at,
+        "change": summarize(&record),
+    });
+    match snapshot {
+        Snapshot::Unknown => {
+            result["exists"] = Value::Null;
+            result["reason"] = json!("content was not recorded for this change");
+        }
+        Snapshot::Missing => {
+            result["exists"] = json!(false);
+        }
+        Snapshot::Content(bytes) => {
+            result["exists"] = json!(true);
+            result["size_bytes"] = json!(bytes.len());
+            result["sha256"] = json!(sha256_bytes(&bytes));
+            if is_binary_buffer(&bytes) {
+                result["content_base64"] = json!(BASE64.encode(&bytes));
+            } else {
+                result["content"] = json!(String::from_utf8_lossy(&bytes));
+            }
+        }
+    }
+    Ok(result)
+}
+
+/// Unified diff between the content right after `change_a` and right after
+/// `change_b`. Each side is the file that change touched.
+pub fn diff_between(store: &ChangeLogStore, change_a: &str, change_b: &str) -> Result<Value, String> {
+    let load = |id: &str| -> Result<(ChangeRecord, Snapshot), String> {
+        let record = store
+            .get_change(id)?
+            .ok_or_else(|| format!("Change not found: {id}"))?;
+        let snapshot = store.load_snapshot(record.after_sha256.as_deref())?;
+        Ok((record, snapshot))
+    };
+    let (a, before) = load(change_a)?;
+    let (b, after) = load(change_b)?;
+    if before == Snapshot::Unknown || after == Snapshot::Unknown {
+        return Err("Content was not recorded for one of the changes.".to_string());
+    }
+    Ok(json!({
+        "a": summarize(&a),
+        "b": summarize(&b),
+        "diff": snapshot_diff(&before, &after)
+    }))
+}
+
+fn state_after<'r>(record: &'r ChangeRecord, path: &str) -> Option<&'r str> {
+    if record.path == path {
+        record.after_sha256.as_deref()
+    } else {
+        // `path` is the source of a move, which leaves it missing.
+        Some("")
+    }
+}
+
+fn state_before<'r>(record: &'r ChangeRecord, path: &str) -> Option<&'r str> {
+    if record.path == path {
+        record.before_sha256.as_deref()
+    } else {
+        record.after_sha256.as_deref()
+    }
+}
+
+fn summarize(record: &ChangeRecord) -> Value {
+    json!({
+        "id": record.id,
+        "path": record.path,
+        "action": record.action,
+        "source_path": record.source_path,
+        "session_id": record.session_id,
+        "run_id": record.run_id,
+        "created_at": record.created_at
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(content: &str) -> Snapshot {
+        Snapshot::Content(content.as_bytes().to_vec())
+    }
+
+    fn content_at(store: &ChangeLogStore, path: &str, at: AsOf) -> Value {
+        let result = file_at(store, path, &at).unwrap();
+        match result["exists"].as_bool() {
+            Some(true) => result["content"].clone(),
+            Some(false) => json!("<missing>"),
+            None => Value::Null,
+        }
+    }
+
+    #[test]
+    fn parse_as_of_takes_exactly_one_reference() {
+        assert!(matches!(parse_as_of(Some("change_1"), None), Ok(AsOf::Change("change_1"))));
+        assert!(matches!(
+            parse_as_of(None, Some("2026-01-01T00:00:00Z")),
+            Ok(AsOf::Timestamp("2026-01-01T00:00:00Z"))
+        ));
+        assert!(parse_as_of(None, None).is_err());
+        assert!(parse_as_of(Some("change_1"), Some("2026-01-01T00:00:00Z")).is_err());
+    }
+
+    #[test]
+    fn file_at_follows_a_file_across_a_move_and_a_delete() {
+        let store = ChangeLogStore::new(":memory:").unwrap();
+        let write = store
+            .log_change("a.txt", "write", 4, "", "s1", "", None, &Snapshot::Missing, &text("one\n"))
+            .unwrap();
+        let moved = store
+            .log_transfer("b.txt", "a.txt", "move", 4, "", "s1", "", None, &Snapshot::Missing, &text("one\n"))
+            .unwrap();
+        let deleted = store
+            .log_change("./b.txt", "delete", 0, "", "s1", "", None, &text("one\n"), &Snapshot::Missing)
+            .unwrap();
+
+        assert_eq!(content_at(&store, "a.txt", AsOf::Change(&write.id)), "one\n");
+        assert_eq!(content_at(&store, "a.txt", AsOf::Change(&moved.id)), "<missing>");
+        assert_eq!(content_at(&store, "b.txt", AsOf::Change(&write.id)), "<missing>");
+        assert_eq!(content_at(&store, "./b.txt", AsOf::Change(&moved.id)), "one\n");
+        assert_eq!(content_at(&store, "b.txt", AsOf::Change(&deleted.id)), "<missing>");
+
+        let result = file_at(&store, "b.txt", &AsOf::Change(&moved.id)).unwrap();
+        assert_eq!(result["change"]["source_path"], "a.txt");
+        assert!(file_at(&store, "c.txt", &AsOf::Change(&moved.id)).unwrap_err().contains("No logged changes"));
+        assert!(file_at(&store, "a.txt", &AsOf::Change("change_missing")).unwrap_err().contains("Change not found"));
+    }
+
+    #[test]
+    fn file_at_accepts_timestamps_in_any_offset() {
+        let store = ChangeLogStore::new(":memory:").unwrap();
+        store
+            .log_change("a.txt", "write", 4, "", "s1", "", None, &text("one\n"), &text("two\n"))
+            .unwrap();
+        store
+            .log_change("a.txt", "write", 6, "", "s1", "", None, &text("two\n"), &Snapshot::Unknown)
+            .unwrap();
+
+        assert_eq!(content_at(&store, "a.txt", AsOf::Timestamp("2000-01-01T02:00:00+02:00")), "one\n");
+        assert_eq!(content_at(&store, "a.txt", AsOf::Timestamp("9999-01-01T00:00:00Z")), Value::Null);
+        let err = file_at(&store, "a.txt", &AsOf::Timestamp("yesterday")).unwrap_err();
+        assert!(err.starts_with("Invalid timestamp yesterday"), "{err}");
+    }
+}
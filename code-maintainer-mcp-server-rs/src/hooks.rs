@@ -0,0 +1,296 @@
+use crate::diff::snapshot_diff;
+use crate::fs_ops::FsOps;
+use crate::storage::{ChangeLogStore, ChangeRecord, Snapshot};
+use crate::utils::sha256_bytes;
+use globset::{GlobBuilder, GlobMatcher};
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+
+/// Bytes of hook stdout/stderr kept in tool responses.
+const MAX_HOOK_OUTPUT: usize = 4000;
+
+/// A command run after a write touches a file matching `glob`. `{path}` in
+/// the command is replaced with the file's path relative to the workspace
+/// root, which is also the working directory.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct HookRule {
+    pub glob: String,
+    pub command: String,
+}
+
+impl HookRule {
+    /// Parses the `--hook` form `<glob>=<command>`.
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let (glob, command) = value
+            .split_once('=')
+            .ok_or_else(|| format!("Invalid hook {value} (expected <glob>=<command>)."))?;
+        Ok(Self {
+            glob: glob.trim().to_string(),
+            command: command.trim().to_string(),
+        })
+    }
+
+    /// Reads a JSON hook file: a list of `{"glob", "command"}` objects, or an
+    /// object mapping globs to commands.
+    pub fn from_file(path: &Path) -> Result<Vec<Self>, String> {
+        let raw = fs::read_to_string(path)
+            .map_err(|err| format!("Failed to read hooks file {}: {err}", path.display()))?;
+        let value: serde_json::Value = serde_json::from_str(&raw)
+            .map_err(|err| format!("Invalid hooks file {}: {err}", path.display()))?;
+        match value {
+            serde_json::Value::Object(map) => map
+                .into_iter()
+                .map(|(glob, command)| match command {
+                    serde_json::Value::String(command) => Ok(Self { glob, command }),
+                    _ => Err(format!("Hook command for {glob} must be a string.")),
+                })
+                .collect(),
+            other => serde_json::from_value(other)
+                .map_err(|err| format!("Invalid hooks file {}: {err}", path.display())),
+        }
+    }
+}
+
+/// Post-write hooks in configuration order. A glob without `/` matches the
+/// file name anywhere in the tree; one with `/` matches the relative path.
+#[derive(Debug, Default)]
+pub struct PostWriteHooks {
+    rules: Vec<(HookRule, GlobMatcher)>,
+    timeout: Duration,
+}
+
+impl PostWriteHooks {
+    pub fn new(rules: Vec<HookRule>, timeout: Duration) -> Result<Self, String> {
+        let rules = rules
+            .into_iter()
+            .map(|rule| {
+                let glob = GlobBuilder::new(&rule.glob)
+                    .literal_separator(true)
+                    .build()
+                    .map_err(|err| format!("Invalid hook glob {}: {err}", rule.glob))?;
+                Ok((rule, glob.compile_matcher()))
+            })
+            .collect::<Result<_, String>>()?;
+        Ok(Self { rules, timeout })
+    }
+
+    pub fn rules(&self) -> impl Iterator<Item = &HookRule> {
+        self.rules.iter().map(|(rule, _)| rule)
+    }
+
+    fn matching<'h>(&'h self, rel_path: &'h str) -> impl Iterator<Item = &'h HookRule> {
+        let name = Path::new(rel_path)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        self.rules.iter().filter_map(move |(rule, matcher)| {
+            let candidate = if rule.glob.contains('/') { rel_path } else { name.as_str() };
+            matcher.is_match(candidate).then_some(rule)
+        })
+    }
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct HookRun {
+    pub glob: String,
+    pub command: String,
+    /// `None` if the hook could not be started, was killed or timed out.
+    pub exit_code: Option<i32>,
+    pub timed_out: bool,
+    pub duration_ms: u128,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct HookOutcome {
+    pub path: String,
+    pub runs: Vec<HookRun>,
+    /// Diff of what the hooks changed, logged as a `format` change.
+    pub diff: Option<String>,
+    pub change: Option<ChangeRecord>,
+}
+
+pub struct HookRunner<'a> {
+    pub hooks: &'a PostWriteHooks,
+    pub fs_ops: &'a FsOps,
+    pub change_log: &'a ChangeLogStore,
+    pub session_id: &'a str,
+    pub run_id: &'a str,
+}
+
+impl HookRunner<'_> {
+    /// Runs every hook matching `path` after a write. Returns `None` when no
+    /// hook matches. A failing hook is reported, not treated as an error.
+    pub fn after_write(&self, path: &str) -> Result<Option<HookOutcome>, String> {
+        let target = self.fs_ops.resolve_path(path)?;
+        let rel_path = self.fs_ops.relative(&target);
+        let rules: Vec<&HookRule> = self.hooks.matching(&rel_path).collect();
+        if rules.is_empty() {
+            return Ok(None);
+        }
+        let before = self.fs_ops.snapshot(&target);
+        let runs = rules
+            .into_iter()
+            .map(|rule| self.run(rule, &rel_path))
+            .collect();
+        let after = self.fs_ops.snapshot(&target);
+        if before == after {
+            return Ok(Some(HookOutcome {
+                path: rel_path,
+                runs,
+                diff: None,
+                change: None,
+            }));
+        }
+        let (bytes, hash) = match &after {
+            Snapshot::Content(content) => (content.len() as i64, sha256_bytes(content)),
+            _ => (0, String::new()),
+        };
+        let diff = snapshot_diff(&before, &after);
+        let record = self.change_log.log_change(
+            &rel_path,
+            "format",
+            bytes,
+            &hash,
+            self.session_id,
+            self.run_id,
+            diff.clone(),
+            &before,
+            &after,
+        )?;
+        Ok(Some(HookOutcome {
+            path: rel_path,
+            runs,
+            diff,
+            change: Some(record),
+        }))
+    }
+
+    fn run(&self, rule: &HookRule, rel_path: &str) -> HookRun {
+        let command = rule.command.replace("{path}", &shell_quote(rel_path));
+        let started = Instant::now();
+        let mut run = HookRun {
+            glob: rule.glob.clone(),
+            command: command.clone(),
+            exit_code: None,
+            timed_out: false,
+            duration_ms: 0,
+            stdout: String::new(),
+            stderr: String::new(),
+        };
+        let (shell, flag) = if cfg!(windows) { ("cmd", "/C") } else { ("sh", "-c") };
+        let mut process = Command::new(shell);
+        process
+            .arg(flag)
+            .arg(&command)
+            .current_dir(self.fs_ops.root())
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        #[cfg(unix)]
+        {
+            // Lead a new process group, so a timeout also kills what the hook started.
+            use std::os::unix::process::CommandExt;
+            process.process_group(0);
+        }
+        let child = process.spawn();
+        let mut child = match child {
+            Ok(child) => child,
+            Err(err) => {
+                run.stderr = format!("Failed to start hook: {err}");
+                return run;
+            }
+        };
+        let stdout = child.stdout.take().map(read_in_background);
+        let stderr = child.stderr.take().map(read_in_background);
+        let mut exited = false;
+        loop {
+            if !exited {
+                match child.try_wait() {
+                    Ok(Some(status)) => {
+                        run.exit_code = status.code();
+                        exited = true;
+                    }
+                    Ok(None) => {}
+                    Err(err) => {
+                        run.stderr = err.to_string();
+                        exited = true;
+                    }
+                }
+            }
+            // Background jobs left by the hook can keep its output open.
+            let drained = [&stdout, &stderr]
+                .into_iter()
+                .all(|reader| reader.as_ref().map(|handle| handle.is_finished()).unwrap_or(true));
+            if exited && drained {
+                break;
+            }
+            if started.elapsed() >= self.hooks.timeout {
+                kill_group(&mut child);
+                run.timed_out = true;
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        run.duration_ms = started.elapsed().as_millis();
+        run.stdout = collect_output(stdout);
+        run.stderr.push_str(&collect_output(stderr));
+        run
+    }
+}
+
+/// Kills the hook and, on Unix, every process left in its group.
+fn kill_group(child: &mut Child) {
+    #[cfg(unix)]
+    // SAFETY: kill(2) with a negative pid signals the group the hook leads.
+    unsafe {
+        libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL);
+    }
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+/// The reader's output, or nothing if a process that escaped the hook's group
+/// still holds the pipe shortly after the kill; that reader is left behind.
+fn collect_output(reader: Option<std::thread::JoinHandle<String>>) -> String {
+    let Some(handle) = reader else {
+        return String::new();
+    };
+    let deadline = Instant::now() + Duration::from_millis(200);
+    while !handle.is_finished() && Instant::now() < deadline {
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    if handle.is_finished() {
+        handle.join().unwrap_or_default()
+    } else {
+        String::new()
+    }
+}
+
+fn read_in_background<R: Read + Send + 'static>(mut reader: R) -> std::thread::JoinHandle<String> {
+    std::thread::spawn(move || {
+        let mut buffer = Vec::new();
+        let _ = reader.read_to_end(&mut buffer);
+        let mut text = String::from_utf8_lossy(&buffer).to_string();
+        if text.len() > MAX_HOOK_OUTPUT {
+            let mut end = MAX_HOOK_OUTPUT;
+            while !text.is_char_boundary(end) {
+                end -= 1;
+            }
+            text.truncate(end);
+        }
+        text
+    })
+}
+
+fn shell_quote(value: &str) -> String {
+    if cfg!(windows) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        format!("'{}'", value.replace('\'', "'\\''"))
+    }
+}
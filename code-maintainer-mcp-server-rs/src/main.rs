@@ -1,24 +1,60 @@
 mod admin_server;
+mod checkpoint;
+mod conflict;
 mod diff;
+mod edit;
+mod export;
+mod external;
 mod fs_ops;
+mod git;
+mod history;
+mod hooks;
 mod mcp;
+mod media;
+mod outline;
 mod patch;
+mod policy;
+mod revert;
+mod search_index;
 mod storage;
+mod summary;
+mod symbols;
+mod text_format;
 mod utils;
 
 use crate::admin_server::{run_admin_server, AdminServerOptions};
-use crate::diff::{build_diff, extract_patch_diffs, read_text_for_diff, DiffInput};
-use crate::fs_ops::FsOps;
+use crate::checkpoint::Checkpoints;
+use crate::conflict::check_expected_sha256;
+use crate::diff::{build_diff, extract_patch_diffs, read_text_for_diff, set_context_lines, snapshot_diff, DiffInput};
+use crate::edit::{apply_edits, parse_edits, Edit};
+use crate::external::{ExternalChange, ExternalCheck, ReadTracker};
+use crate::export::{export_changes, ExportFormat};
+use crate::fs_ops::{FsOps, SearchQuery};
+use crate::git::Git;
+use crate::history::{file_at, parse_as_of};
+use crate::hooks::{HookOutcome, HookRule, HookRunner, PostWriteHooks};
 use crate::mcp::McpServer;
-use crate::patch::apply_patch;
-use crate::storage::ChangeLogStore;
-use crate::utils::{clamp_number, ensure_dir, format_bytes, generate_id, normalize_id, normalize_name, parse_args, resolve_state_dir, sha256_bytes};
-use serde_json::json;
+use crate::media::{image_size, sniff_mime};
+use crate::outline::{outline, Language, SUPPORTED_EXTENSIONS};
+use crate::patch::{apply_patch, preview_patch};
+use crate::policy::{PathPolicy, PolicyRules};
+use crate::revert::Reverter;
+use crate::storage::{ChangeLogStore, ChangeRecord, DiffCodec, RetentionPolicy, Snapshot};
+use crate::summary::summarize_changes;
+use crate::search_index::SearchIndex;
+use crate::symbols::SymbolIndex;
+use crate::text_format::{parse_overrides, LineEnding};
+use crate::utils::{clamp_number, is_binary_buffer, ensure_dir, format_bytes, generate_id, normalize_id, normalize_name, parse_args, real_path, resolve_state_dir, sha256_bytes};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde_json::{json, Value};
 use std::cell::RefCell;
 use std::collections::HashMap;
 use std::env;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
+use std::sync::Arc;
+use std::time::Duration;
 
 fn main() {
     let argv: Vec<String> = env::args().skip(1).collect();
@@ -49,6 +85,36 @@ fn main() {
     let max_write_bytes =
         clamp_number(args.values.get("max-write-bytes"), 1024, 100 * 1024 * 1024, 5 * 1024 * 1024);
     let search_limit = clamp_number(args.values.get("max-search-results"), 1, 500, 40) as usize;
+    set_context_lines(clamp_number(args.values.get("diff-context"), 0, 100, 3) as usize);
+
+    let mut policy_rules = args
+        .values
+        .get("policy")
+        .map(|path| PolicyRules::from_file(Path::new(path)).expect("failed to load policy file"))
+        .unwrap_or_default();
+    policy_rules.extend(PolicyRules {
+        deny: comma_list(args.values.get("deny")),
+        readonly: comma_list(args.values.get("readonly")),
+        writable: comma_list(args.values.get("writable")),
+    });
+    let policy = Arc::new(PathPolicy::new(policy_rules).expect("invalid path policy"));
+    let allowed_external: Arc<Vec<PathBuf>> = Arc::new(
+        comma_list(args.values.get("allow-external"))
+            .iter()
+            .map(|path| real_path(Path::new(path)).expect("invalid --allow-external path"))
+            .collect(),
+    );
+
+    let mut hook_rules = args
+        .values
+        .get("hooks")
+        .map(|path| HookRule::from_file(Path::new(path)).expect("failed to load hooks file"))
+        .unwrap_or_default();
+    for rule in args.all_values.get("hook").into_iter().flatten() {
+        hook_rules.push(HookRule::parse(rule).expect("invalid --hook"));
+    }
+    let hook_timeout = Duration::from_secs(clamp_number(args.values.get("hook-timeout"), 1, 600, 30) as u64);
+    let hooks = Arc::new(PostWriteHooks::new(hook_rules, hook_timeout).expect("invalid post-write hooks"));
 
     let session_id_arg = normalize_id(args.values.get("session-id").or_else(|| args.values.get("session")));
     let run_id_arg = normalize_id(args.values.get("run-id").or_else(|| args.values.get("run")));
@@ -81,7 +147,36 @@ fn main() {
         .or_else(|| env::var("MODEL_CLI_FILE_CHANGES_DB").ok())
         .unwrap_or_else(|| state_dir.join(format!("{server_name}.db.sqlite")).to_string_lossy().to_string());
 
-    let change_log = ChangeLogStore::new(&db_path).expect("failed to open change log db");
+    let diff_codec = DiffCodec::parse(args.values.get("diff-codec").map(String::as_str).unwrap_or("none"))
+        .expect("invalid --diff-codec");
+    let retention = RetentionPolicy {
+        max_age_days: args.values.get("retain-days").and_then(|v| v.parse().ok()),
+        max_changes: args.values.get("retain-changes").and_then(|v| v.parse().ok()),
+        max_db_bytes: args
+            .values
+            .get("retain-mb")
+            .and_then(|v| v.parse::<i64>().ok())
+            .map(|mb| mb * 1024 * 1024),
+    };
+    for (flag, limit) in [("retain-days", retention.max_age_days), ("retain-changes", retention.max_changes)] {
+        if limit == Some(0) && !args.flags.contains("force-retention") {
+            eprintln!("[{server_name}] --{flag} 0 deletes every logged change; pass --force-retention to confirm.");
+            std::process::exit(2);
+        }
+    }
+    let change_log = ChangeLogStore::new(&db_path)
+        .expect("failed to open change log db")
+        .with_diff_codec(diff_codec);
+    if args.flags.contains("compact") {
+        let report = compact_store(&change_log, &retention, true, true).expect("failed to compact change log");
+        println!("{}", serde_json::to_string_pretty(&report).unwrap_or_default());
+        return;
+    }
+    if !retention.is_empty() {
+        if let Err(err) = change_log.prune(&retention) {
+            eprintln!("[{server_name}] change log retention failed: {err}");
+        }
+    }
     let change_log = Rc::new(RefCell::new(change_log));
     let fs_ops = FsOps::new(
         root.clone(),
@@ -89,7 +184,14 @@ fn main() {
         max_file_bytes,
         max_write_bytes,
         search_limit,
-    );
+    )
+    .with_policy(policy.clone())
+    .with_allowed_external(allowed_external.clone());
+    let record_external = args.flags.contains("record-external");
+    let tracker = Rc::new(ReadTracker::new(record_external));
+    let search_index = args.flags.contains("search-index").then(|| {
+        Rc::new(SearchIndex::open(&db_path, fs_ops.root()).expect("failed to open search index"))
+    });
 
     let admin_port = args
         .values
@@ -110,6 +212,9 @@ fn main() {
             max_file_bytes,
             max_write_bytes,
             search_limit,
+            policy: policy.clone(),
+            allowed_external: allowed_external.clone(),
+            search_index: search_index.is_some(),
             session_id: session_id.clone(),
             run_id: run_id.clone(),
             host: admin_host,
@@ -124,13 +229,27 @@ fn main() {
     }
 
     let mut server = McpServer::new(server_name.clone(), "0.1.0");
-    let workspace_note = format!(
+    let mut workspace_note = format!(
         "Workspace root: {}. Paths must stay inside this directory.",
         root.display()
     );
+    let rules = policy.rules();
+    for (kind, globs) in [("Denied", &rules.deny), ("Read-only", &rules.readonly), ("Writable only", &rules.writable)] {
+        if !globs.is_empty() {
+            workspace_note.push_str(&format!("\n{kind} paths: {}.", globs.join(", ")));
+        }
+    }
+    let hook_list: Vec<String> = hooks.rules().map(|rule| format!("{} -> {}", rule.glob, rule.command)).collect();
+    if !hook_list.is_empty() {
+        workspace_note.push_str(&format!(
+            "\nPost-write hooks (run after write_file, edit_file and apply_patch; changes are logged as format): {}.",
+            hook_list.join("; ")
+        ));
+    }
 
     {
         let fs_ops = fs_ops.clone();
+        let tracker = tracker.clone();
         server.register_tool(
             "read_file_raw",
             &format!("Return UTF-8 file content without line numbers.\n{workspace_note}"),
@@ -147,6 +266,7 @@ fn main() {
                     .and_then(|v| v.as_str())
                     .ok_or("path is required".to_string())?;
                 let (path, size, sha256, content) = fs_ops.read_file_raw(path)?;
+                tracker.remember(&fs_ops, &path);
                 Ok(text_result(json!({
                     "path": path,
                     "size_bytes": size,
@@ -159,6 +279,7 @@ fn main() {
 
     {
         let fs_ops = fs_ops.clone();
+        let tracker = tracker.clone();
         server.register_tool(
             "read_file_range",
             &format!(
@@ -194,6 +315,7 @@ fn main() {
                     .unwrap_or(false);
                 let (path, size, sha256, start, end, total, content) =
                     fs_ops.read_file_range(path, start_line, end_line, with_numbers)?;
+                tracker.remember(&fs_ops, &path);
                 Ok(text_result(json!({
                     "path": path,
                     "size_bytes": size,
@@ -207,6 +329,142 @@ fn main() {
         );
     }
 
+    {
+        let fs_ops = fs_ops.clone();
+        let tracker = tracker.clone();
+        let budget = max_file_bytes.saturating_mul(2) as usize;
+        server.register_tool(
+            "read_files",
+            &format!(
+                "Read several files or line ranges in one call. Each item takes path and optional start_line/end_line (1-based, inclusive); omit both for the whole file.\nErrors are reported per item. Combined content budget: {}.\n{workspace_note}",
+                format_bytes(budget as i64)
+            ),
+            json!({
+                "type": "object",
+                "properties": {
+                    "files": {
+                        "type": "array",
+                        "minItems": 1,
+                        "maxItems": 50,
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "path": { "type": "string" },
+                                "start_line": { "type": "integer", "minimum": 1 },
+                                "end_line": { "type": "integer", "minimum": 1 }
+                            },
+                            "required": ["path"]
+                        }
+                    },
+                    "with_line_numbers": { "type": "boolean" }
+                },
+                "required": ["files"]
+            }),
+            Box::new(move |args| {
+                let specs = args
+                    .get("files")
+                    .and_then(|v| v.as_array())
+                    .filter(|items| !items.is_empty())
+                    .ok_or("files is required".to_string())?;
+                if specs.len() > 50 {
+                    return Err("At most 50 files per call.".to_string());
+                }
+                let with_numbers = args
+                    .get("with_line_numbers")
+                    .and_then(|v| v.as_bool())
+                    .unwrap_or(false);
+                let mut remaining = budget;
+                let mut items = Vec::new();
+                for spec in specs {
+                    let Some(path) = spec.get("path").and_then(|v| v.as_str()) else {
+                        items.push(json!({ "error": "path is required" }));
+                        continue;
+                    };
+                    let start_line = spec.get("start_line").and_then(|v| v.as_u64()).unwrap_or(1) as usize;
+                    let end_line = spec
+                        .get("end_line")
+                        .and_then(|v| v.as_u64())
+                        .map(|v| v as usize)
+                        .unwrap_or(usize::MAX);
+                    match fs_ops.read_file_range(path, start_line, end_line, with_numbers) {
+                        Ok((_, _, _, _, _, _, content)) if content.len() > remaining => {
+                            items.push(json!({
+                                "path": path,
+                                "error": format!(
+                                    "Combined byte budget exhausted ({} needed, {} left); read this file in a separate call.",
+                                    content.len(),
+                                    remaining
+                                )
+                            }));
+                        }
+                        Ok((path, size, sha256, start, end, total, content)) => {
+                            remaining -= content.len();
+                            tracker.remember(&fs_ops, &path);
+                            items.push(json!({
+                                "path": path,
+                                "size_bytes": size,
+                                "sha256": sha256,
+                                "start_line": start,
+                                "end_line": end,
+                                "total_lines": total,
+                                "content": content
+                            }));
+                        }
+                        Err(err) => items.push(json!({ "path": path, "error": err })),
+                    }
+                }
+                Ok(text_result(json!({
+                    "files": items,
+                    "budget_remaining": remaining
+                })))
+            }),
+        );
+    }
+
+    {
+        let fs_ops = fs_ops.clone();
+        let tracker = tracker.clone();
+        server.register_tool(
+            "read_file_base64",
+            &format!(
+                "Return any file (including binary) as base64 with sha256, sniffed MIME type and image dimensions when available. Set metadata_only to skip the content.\nFile size limit: {}.\n{workspace_note}",
+                format_bytes(max_file_bytes)
+            ),
+            json!({
+                "type": "object",
+                "properties": {
+                    "path": { "type": "string" },
+                    "metadata_only": { "type": "boolean" }
+                },
+                "required": ["path"]
+            }),
+            Box::new(move |args| {
+                let path = args
+                    .get("path")
+                    .and_then(|v| v.as_str())
+                    .ok_or("path is required".to_string())?;
+                let metadata_only = args
+                    .get("metadata_only")
+                    .and_then(|v| v.as_bool())
+                    .unwrap_or(false);
+                let (target, bytes) = fs_ops.read_file_bytes(path)?;
+                tracker.remember(&fs_ops, path);
+                let mut result = json!({
+                    "path": path,
+                    "size_bytes": bytes.len(),
+                    "sha256": sha256_bytes(&bytes),
+                    "mime": sniff_mime(&target, &bytes),
+                    "binary": is_binary_buffer(&bytes),
+                    "image": image_size(&bytes)
+                });
+                if !metadata_only {
+                    result["content_base64"] = json!(BASE64.encode(&bytes));
+                }
+                Ok(text_result(result))
+            }),
+        );
+    }
+
     {
         let fs_ops = fs_ops.clone();
         server.register_tool(
@@ -235,14 +493,17 @@ fn main() {
     {
         let fs_ops = fs_ops.clone();
         server.register_tool(
-            "search_text",
-            &format!("Search text recursively under a directory.\n{workspace_note}"),
+            "find_files",
+            &format!(
+                "Find files by glob (relative to the workspace root; ** matches any depth, e.g. \"**/*.rs\", \"src/**/mod.rs\"), most recently modified first.\n.gitignore and .ignore files are respected unless no_ignore is set.\n{workspace_note}"
+            ),
             json!({
                 "type": "object",
                 "properties": {
                     "pattern": { "type": "string", "minLength": 1 },
                     "path": { "type": "string" },
-                    "max_results": { "type": "integer", "minimum": 1, "maximum": 500 }
+                    "max_results": { "type": "integer", "minimum": 1, "maximum": 1000 },
+                    "no_ignore": { "type": "boolean" }
                 },
                 "required": ["pattern"]
             }),
@@ -255,23 +516,162 @@ fn main() {
                 let max_results = args
                     .get("max_results")
                     .and_then(|v| v.as_u64())
-                    .map(|v| v as usize);
-                let results = fs_ops.search_text(pattern, path, max_results)?;
-                Ok(text_result(json!({ "count": results.len(), "results": results })))
+                    .map(|v| v as usize)
+                    .unwrap_or(200);
+                let no_ignore = args.get("no_ignore").and_then(|v| v.as_bool()).unwrap_or(false);
+                let (files, total) = fs_ops.find_files(pattern, path, max_results, !no_ignore)?;
+                Ok(text_result(json!({ "count": files.len(), "total": total, "files": files })))
+            }),
+        );
+    }
+
+    {
+        let fs_ops = fs_ops.clone();
+        server.register_tool(
+            "tree",
+            &format!(
+                "Show the directory tree down to depth levels (default 2). Directories report the recursive file count and total size below them.\n.gitignore and .ignore files are respected unless no_ignore is set.\n{workspace_note}"
+            ),
+            json!({
+                "type": "object",
+                "properties": {
+                    "path": { "type": "string" },
+                    "depth": { "type": "integer", "minimum": 0, "maximum": 10 },
+                    "max_entries": { "type": "integer", "minimum": 1, "maximum": 5000 },
+                    "no_ignore": { "type": "boolean" }
+                }
+            }),
+            Box::new(move |args| {
+                let path = args.get("path").and_then(|v| v.as_str()).unwrap_or(".");
+                let depth = args
+                    .get("depth")
+                    .and_then(|v| v.as_u64())
+                    .map(|v| v.min(10) as usize)
+                    .unwrap_or(2);
+                let max_entries = args
+                    .get("max_entries")
+                    .and_then(|v| v.as_u64())
+                    .map(|v| v as usize)
+                    .unwrap_or(500);
+                let no_ignore = args.get("no_ignore").and_then(|v| v.as_bool()).unwrap_or(false);
+                let tree = fs_ops.tree(path, depth, max_entries, !no_ignore)?;
+                Ok(text_result(json!({ "tree": tree })))
+            }),
+        );
+    }
+
+    {
+        let fs_ops = fs_ops.clone();
+        let search_index = search_index.clone();
+        server.register_tool(
+            "search_text",
+            &format!(
+                "Search text recursively under a directory.\nSet regex for regular expressions and case_insensitive to ignore case. include/exclude take globs relative to the workspace root (e.g. \"*.rs\", \"src/**\").\nbefore/after add context lines; files_only lists matching files with match counts.\n.gitignore and .ignore files are respected unless no_ignore is set.{}\n{workspace_note}",
+                if search_index.is_some() {
+                    "\nLiteral patterns of 3+ characters only read files the full-text index reports as candidates (indexed: true in the response)."
+                } else {
+                    ""
+                }
+            ),
+            json!({
+                "type": "object",
+                "properties": {
+                    "pattern": { "type": "string", "minLength": 1 },
+                    "path": { "type": "string" },
+                    "max_results": { "type": "integer", "minimum": 1, "maximum": 500 },
+                    "regex": { "type": "boolean" },
+                    "case_insensitive": { "type": "boolean" },
+                    "include": { "type": "array", "items": { "type": "string" } },
+                    "exclude": { "type": "array", "items": { "type": "string" } },
+                    "before": { "type": "integer", "minimum": 0, "maximum": 20 },
+                    "after": { "type": "integer", "minimum": 0, "maximum": 20 },
+                    "files_only": { "type": "boolean" },
+                    "no_ignore": { "type": "boolean" }
+                },
+                "required": ["pattern"]
+            }),
+            Box::new(move |args| {
+                let pattern = args
+                    .get("pattern")
+                    .and_then(|v| v.as_str())
+                    .ok_or("pattern is required".to_string())?;
+                let flag = |key: &str| args.get(key).and_then(|v| v.as_bool()).unwrap_or(false);
+                let context = |key: &str| {
+                    args.get(key)
+                        .and_then(|v| v.as_u64())
+                        .map(|v| v.min(20) as usize)
+                        .unwrap_or(0)
+                };
+                let query = SearchQuery {
+                    pattern: pattern.to_string(),
+                    path: args
+                        .get("path")
+                        .and_then(|v| v.as_str())
+                        .unwrap_or(".")
+                        .to_string(),
+                    max_results: args
+                        .get("max_results")
+                        .and_then(|v| v.as_u64())
+                        .map(|v| v as usize),
+                    regex: flag("regex"),
+                    case_insensitive: flag("case_insensitive"),
+                    include: string_list(args.get("include")),
+                    exclude: string_list(args.get("exclude")),
+                    before: context("before"),
+                    after: context("after"),
+                    files_only: flag("files_only"),
+                    respect_ignore: !flag("no_ignore"),
+                };
+                let indexed = match &search_index {
+                    Some(index) => index.search(&fs_ops, &query)?,
+                    None => None,
+                };
+                let is_indexed = indexed.is_some();
+                let output = match indexed {
+                    Some(output) => output,
+                    None => fs_ops.search_text(&query)?,
+                };
+                let mut response = if query.files_only {
+                    json!({ "count": output.files.len(), "files": output.files })
+                } else {
+                    json!({ "count": output.results.len(), "results": output.results })
+                };
+                if search_index.is_some() {
+                    response["indexed"] = json!(is_indexed);
+                }
+                Ok(text_result(response))
+            }),
+        );
+    }
+
+    if let Some(search_index) = search_index.clone() {
+        let fs_ops = fs_ops.clone();
+        server.register_tool(
+            "rebuild_index",
+            &format!(
+                "Rebuild the full-text search index from scratch. search_text refreshes it from file mtimes on every call, so this is only needed if the index looks wrong or the database was copied from another machine.\n{workspace_note}"
+            ),
+            json!({ "type": "object", "properties": {} }),
+            Box::new(move |_args| {
+                let rebuilt = search_index.rebuild(&fs_ops)?;
+                Ok(text_result(json!({ "rebuilt": rebuilt, "stats": search_index.stats()? })))
             }),
         );
     }
 
     {
         let fs_ops = fs_ops.clone();
+        let tracker = tracker.clone();
         let change_log = change_log.clone();
         let session_id = session_id.clone();
         let run_id = run_id.clone();
-        let max_file_bytes = max_file_bytes;
+        let hooks = hooks.clone();
+        let search_index = search_index.clone();
+        let server_name = server_name.clone();
         server.register_tool(
             "write_file",
             &format!(
-                "Write file content (overwrite).\nMax write bytes: {}.\n{}.\n{workspace_note}",
+                "Write file content (overwrite).\nMax write bytes: {}.\nLine endings, BOM and trailing newline follow the existing file unless overridden.\nSet dry_run to preview the diff without writing or logging.\n{}.\n{workspace_note}",
                 format_bytes(max_write_bytes),
                 if allow_writes { "Writes enabled" } else { "Writes disabled" }
             ),
@@ -279,7 +679,19 @@ fn main() {
                 "type": "object",
                 "properties": {
                     "path": { "type": "string" },
-                    "content": { "type": "string" }
+                    "content": { "type": "string" },
+                    "dry_run": { "type": "boolean" },
+                    "expected_sha256": {
+                        "type": "string",
+                        "description": "sha256 from the last read; the write fails with a conflict if the file changed since."
+                    },
+                    "line_ending": {
+                        "type": "string",
+                        "enum": ["auto", "lf", "crlf", "as_is"],
+                        "description": "auto (default) follows the existing file; as_is writes content untouched."
+                    },
+                    "bom": { "type": "boolean" },
+                    "trailing_newline": { "type": "boolean" }
                 },
                 "required": ["path", "content"]
             }),
@@ -292,12 +704,42 @@ fn main() {
                     .get("content")
                     .and_then(|v| v.as_str())
                     .ok_or("content is required".to_string())?;
-                let target = fs_ops.resolve_path(path)?;
+                let dry_run = args.get("dry_run").and_then(|v| v.as_bool()).unwrap_or(false);
+                let overrides = parse_overrides(&args)?;
+                let target = fs_ops.check_write(path, content.len())?;
                 let before_snapshot =
                     read_text_for_diff(&target, max_file_bytes).unwrap_or_else(DiffInput::omitted);
-                let result = fs_ops.write_file(path, content)?;
+                let existing = before_snapshot.text.as_deref().and_then(text_format::detect);
+                let (content, normalization) = text_format::normalize(content, existing, &overrides);
+                let content = content.as_str();
+                fs_ops.check_write(path, content.len())?;
+                if let Some(expected) = args.get("expected_sha256").and_then(|v| v.as_str()) {
+                    let proposed = Snapshot::Content(content.as_bytes().to_vec());
+                    check_expected_sha256(&fs_ops, &change_log.borrow(), path, expected, Some(&proposed))?;
+                }
                 let after_snapshot = DiffInput::text(content.to_string());
-                let diff = build_diff(before_snapshot, after_snapshot);
+                if dry_run {
+                    let diff = build_diff(before_snapshot, after_snapshot);
+                    return Ok(text_result(json!({
+                        "dry_run": true,
+                        "result": {
+                            "path": path,
+                            "bytes": content.len(),
+                            "sha256": sha256_bytes(content.as_bytes())
+                        },
+                        "normalization": normalization,
+                        "diff": diff
+                    })));
+                }
+                let before = fs_ops.snapshot(&target);
+                let result = fs_ops.write_file(path, content)?;
+                let external = check_external(&tracker, &fs_ops, &change_log.borrow(), &session_id, &run_id, &result.path, &before)?;
+                let after = Snapshot::Content(content.as_bytes().to_vec());
+                let diff = if before.is_binary() {
+                    snapshot_diff(&before, &after)
+                } else {
+                    build_diff(before_snapshot, after_snapshot)
+                };
                 let record = change_log
                     .borrow()
                     .log_change(
@@ -308,22 +750,35 @@ fn main() {
                         &session_id,
                         &run_id,
                         diff,
+                        &before,
+                        &after,
                     )?;
-                Ok(text_result(json!({ "result": result, "change": record })))
+                let mut response = json!({ "result": result, "normalization": normalization, "change": record });
+                if let Some(outcome) = run_hooks(&hooks, &fs_ops, &change_log.borrow(), &session_id, &run_id, &result.path)? {
+                    response["hooks"] = json!(outcome);
+                }
+                reindex(search_index.as_deref(), &fs_ops, &server_name, [result.path.as_str()]);
+                tracker.remember(&fs_ops, &result.path);
+                if let Some(external) = external {
+                    response["external_change"] = json!(external);
+                }
+                Ok(text_result(response))
             }),
         );
     }
 
     {
         let fs_ops = fs_ops.clone();
+        let tracker = tracker.clone();
         let change_log = change_log.clone();
         let session_id = session_id.clone();
         let run_id = run_id.clone();
-        let max_file_bytes = max_file_bytes;
+        let search_index = search_index.clone();
+        let server_name = server_name.clone();
         server.register_tool(
             "append_file",
             &format!(
-                "Append content to file.\nMax write bytes: {}.\n{}.\n{workspace_note}",
+                "Append content to file.\nMax write bytes: {}.\nLine endings, BOM and trailing newline follow the existing file unless overridden.\nSet dry_run to preview the diff without writing or logging.\n{}.\n{workspace_note}",
                 format_bytes(max_write_bytes),
                 if allow_writes { "Writes enabled" } else { "Writes disabled" }
             ),
@@ -331,7 +786,19 @@ fn main() {
                 "type": "object",
                 "properties": {
                     "path": { "type": "string" },
-                    "content": { "type": "string" }
+                    "content": { "type": "string" },
+                    "dry_run": { "type": "boolean" },
+                    "expected_sha256": {
+                        "type": "string",
+                        "description": "sha256 from the last read; the write fails with a conflict if the file changed since."
+                    },
+                    "line_ending": {
+                        "type": "string",
+                        "enum": ["auto", "lf", "crlf", "as_is"],
+                        "description": "auto (default) follows the existing file; as_is writes content untouched."
+                    },
+                    "bom": { "type": "boolean" },
+                    "trailing_newline": { "type": "boolean" }
                 },
                 "required": ["path", "content"]
             }),
@@ -344,9 +811,16 @@ fn main() {
                     .get("content")
                     .and_then(|v| v.as_str())
                     .ok_or("content is required".to_string())?;
-                let target = fs_ops.resolve_path(path)?;
+                let dry_run = args.get("dry_run").and_then(|v| v.as_bool()).unwrap_or(false);
+                let overrides = parse_overrides(&args)?;
+                let target = fs_ops.check_write(path, content.len())?;
                 let before_snapshot =
                     read_text_for_diff(&target, max_file_bytes).unwrap_or_else(DiffInput::omitted);
+                let existing = before_snapshot.text.as_deref().and_then(text_format::detect);
+                let (content, normalization) =
+                    text_format::normalize_append(content, existing, &overrides);
+                let content = content.as_str();
+                fs_ops.check_write(path, content.len())?;
                 let after_snapshot = if let Some(reason) = before_snapshot.reason.clone() {
                     DiffInput::omitted(reason)
                 } else {
@@ -354,7 +828,29 @@ fn main() {
                     next.push_str(content);
                     DiffInput::text(next)
                 };
+                if let Some(expected) = args.get("expected_sha256").and_then(|v| v.as_str()) {
+                    let proposed = after_snapshot
+                        .text
+                        .as_ref()
+                        .map(|text| Snapshot::Content(text.as_bytes().to_vec()));
+                    check_expected_sha256(&fs_ops, &change_log.borrow(), path, expected, proposed.as_ref())?;
+                }
+                if dry_run {
+                    let diff = build_diff(before_snapshot, after_snapshot);
+                    return Ok(text_result(json!({
+                        "dry_run": true,
+                        "result": {
+                            "path": path,
+                            "bytes": content.len(),
+                            "sha256": sha256_bytes(content.as_bytes())
+                        },
+                        "normalization": normalization,
+                        "diff": diff
+                    })));
+                }
+                let before = fs_ops.snapshot(&target);
                 let result = fs_ops.append_file(path, content)?;
+                let external = check_external(&tracker, &fs_ops, &change_log.borrow(), &session_id, &run_id, &result.path, &before)?;
                 let diff = build_diff(before_snapshot, after_snapshot);
                 let record = change_log
                     .borrow()
@@ -366,28 +862,127 @@ fn main() {
                         &session_id,
                         &run_id,
                         diff,
+                        &before,
+                        &fs_ops.snapshot(&target),
                     )?;
-                Ok(text_result(json!({ "result": result, "change": record })))
+                reindex(search_index.as_deref(), &fs_ops, &server_name, [result.path.as_str()]);
+                tracker.remember(&fs_ops, &result.path);
+                let mut response = json!({ "result": result, "normalization": normalization, "change": record });
+                if let Some(external) = external {
+                    response["external_change"] = json!(external);
+                }
+                Ok(text_result(response))
+            }),
+        );
+    }
+
+    {
+        let fs_ops = fs_ops.clone();
+        let tracker = tracker.clone();
+        let change_log = change_log.clone();
+        let session_id = session_id.clone();
+        let run_id = run_id.clone();
+        let search_index = search_index.clone();
+        let server_name = server_name.clone();
+        server.register_tool(
+            "write_file_base64",
+            &format!(
+                "Write base64-decoded bytes to a file (overwrite), for binary assets such as images and fixtures.\nMax write bytes: {}.\nSet dry_run to preview the change without writing or logging.\n{}.\n{workspace_note}",
+                format_bytes(max_write_bytes),
+                if allow_writes { "Writes enabled" } else { "Writes disabled" }
+            ),
+            json!({
+                "type": "object",
+                "properties": {
+                    "path": { "type": "string" },
+                    "content_base64": { "type": "string" },
+                    "dry_run": { "type": "boolean" },
+                    "expected_sha256": {
+                        "type": "string",
+                        "description": "sha256 from the last read; the write fails with a conflict if the file changed since."
+                    }
+                },
+                "required": ["path", "content_base64"]
+            }),
+            Box::new(move |args| {
+                let path = args
+                    .get("path")
+                    .and_then(|v| v.as_str())
+                    .ok_or("path is required".to_string())?;
+                let encoded = args
+                    .get("content_base64")
+                    .and_then(|v| v.as_str())
+                    .ok_or("content_base64 is required".to_string())?;
+                let bytes = BASE64
+                    .decode(encoded.trim())
+                    .map_err(|err| format!("Invalid base64: {err}"))?;
+                let dry_run = args.get("dry_run").and_then(|v| v.as_bool()).unwrap_or(false);
+                let target = fs_ops.check_write(path, bytes.len())?;
+                let after = Snapshot::Content(bytes.clone());
+                if let Some(expected) = args.get("expected_sha256").and_then(|v| v.as_str()) {
+                    check_expected_sha256(&fs_ops, &change_log.borrow(), path, expected, Some(&after))?;
+                }
+                let before = fs_ops.snapshot(&target);
+                let diff = snapshot_diff(&before, &after);
+                let meta = json!({
+                    "mime": sniff_mime(&target, &bytes),
+                    "image": image_size(&bytes)
+                });
+                if dry_run {
+                    return Ok(text_result(json!({
+                        "dry_run": true,
+                        "result": {
+                            "path": path,
+                            "bytes": bytes.len(),
+                            "sha256": sha256_bytes(&bytes)
+                        },
+                        "metadata": meta,
+                        "diff": diff
+                    })));
+                }
+                let result = fs_ops.write_bytes(path, &bytes)?;
+                let external = check_external(&tracker, &fs_ops, &change_log.borrow(), &session_id, &run_id, &result.path, &before)?;
+                let record = change_log.borrow().log_change(
+                    &result.path,
+                    "write",
+                    result.bytes,
+                    &result.sha256,
+                    &session_id,
+                    &run_id,
+                    diff,
+                    &before,
+                    &after,
+                )?;
+                reindex(search_index.as_deref(), &fs_ops, &server_name, [result.path.as_str()]);
+                tracker.remember(&fs_ops, &result.path);
+                let mut response = json!({ "result": result, "metadata": meta, "change": record });
+                if let Some(external) = external {
+                    response["external_change"] = json!(external);
+                }
+                Ok(text_result(response))
             }),
         );
     }
 
     {
         let fs_ops = fs_ops.clone();
+        let tracker = tracker.clone();
         let change_log = change_log.clone();
         let session_id = session_id.clone();
         let run_id = run_id.clone();
-        let max_file_bytes = max_file_bytes;
+        let search_index = search_index.clone();
+        let server_name = server_name.clone();
         server.register_tool(
             "delete_path",
             &format!(
-                "Delete a file or directory.\n{}.\n{workspace_note}",
+                "Delete a file or directory.\nSet dry_run to preview the diff without writing or logging.\n{}.\n{workspace_note}",
                 if allow_writes { "Writes enabled" } else { "Writes disabled" }
             ),
             json!({
                 "type": "object",
                 "properties": {
-                    "path": { "type": "string" }
+                    "path": { "type": "string" },
+                    "dry_run": { "type": "boolean" }
                 },
                 "required": ["path"]
             }),
@@ -396,7 +991,8 @@ fn main() {
                     .get("path")
                     .and_then(|v| v.as_str())
                     .ok_or("path is required".to_string())?;
-                let target = fs_ops.resolve_path(path)?;
+                let dry_run = args.get("dry_run").and_then(|v| v.as_bool()).unwrap_or(false);
+                let target = fs_ops.check_delete_tree(path)?;
                 let before_snapshot =
                     read_text_for_diff(&target, max_file_bytes).unwrap_or_else(DiffInput::omitted);
                 let after_snapshot = if let Some(reason) = before_snapshot.reason.clone() {
@@ -404,76 +1000,1183 @@ fn main() {
                 } else {
                     DiffInput::text(String::new())
                 };
+                if dry_run {
+                    let diff = build_diff(before_snapshot, after_snapshot);
+                    return Ok(text_result(json!({
+                        "dry_run": true,
+                        "result": { "path": path, "exists": target.exists() },
+                        "diff": diff
+                    })));
+                }
+                let before = fs_ops.snapshot(&target);
                 let deleted_path = fs_ops.delete_path(path)?;
-                let diff = build_diff(before_snapshot, after_snapshot);
+                let external = check_external(&tracker, &fs_ops, &change_log.borrow(), &session_id, &run_id, &deleted_path, &before)?;
+                let diff = if before.is_binary() {
+                    snapshot_diff(&before, &Snapshot::Missing)
+                } else {
+                    build_diff(before_snapshot, after_snapshot)
+                };
+                let record = change_log.borrow().log_change(
+                    &deleted_path,
+                    "delete",
+                    0,
+                    "",
+                    &session_id,
+                    &run_id,
+                    diff,
+                    &before,
+                    &Snapshot::Missing,
+                )?;
+                reindex(search_index.as_deref(), &fs_ops, &server_name, [deleted_path.as_str()]);
+                tracker.remember(&fs_ops, &deleted_path);
+                let mut response = json!({ "result": { "path": deleted_path }, "change": record });
+                if let Some(external) = external {
+                    response["external_change"] = json!(external);
+                }
+                Ok(text_result(response))
+            }),
+        );
+    }
+
+    {
+        let fs_ops = fs_ops.clone();
+        let change_log = change_log.clone();
+        let session_id = session_id.clone();
+        let run_id = run_id.clone();
+        let search_index = search_index.clone();
+        let server_name = server_name.clone();
+        server.register_tool(
+            "move_path",
+            &format!(
+                "Move or rename a file or directory. Missing parent directories are created.\nFails if the destination exists unless overwrite is set (files only).\nSet dry_run to validate without moving or logging.\n{}.\n{workspace_note}",
+                if allow_writes { "Writes enabled" } else { "Writes disabled" }
+            ),
+            json!({
+                "type": "object",
+                "properties": {
+                    "from": { "type": "string" },
+                    "to": { "type": "string" },
+                    "overwrite": { "type": "boolean" },
+                    "dry_run": { "type": "boolean" }
+                },
+                "required": ["from", "to"]
+            }),
+            Box::new(move |args| {
+                let (from, to) = transfer_paths(&args)?;
+                let overwrite = args.get("overwrite").and_then(|v| v.as_bool()).unwrap_or(false);
+                let dry_run = args.get("dry_run").and_then(|v| v.as_bool()).unwrap_or(false);
+                let (source, dest) = fs_ops.check_move(from, to, overwrite)?;
+                if dry_run {
+                    return Ok(text_result(json!({
+                        "dry_run": true,
+                        "result": { "from": from, "to": to, "overwrites": dest.exists() }
+                    })));
+                }
+                let before = fs_ops.snapshot(&dest);
+                let moved = fs_ops.snapshot(&source);
+                fs_ops.move_path(from, to, overwrite)?;
+                let (bytes, hash) = match &moved {
+                    Snapshot::Content(content) => (content.len() as i64, sha256_bytes(content)),
+                    _ => (0, String::new()),
+                };
+                let diff = Some(format!("rename from {from}\nrename to {to}\n"));
                 let record = change_log
                     .borrow()
-                    .log_change(&deleted_path, "delete", 0, "", &session_id, &run_id, diff)?;
-                Ok(text_result(json!({ "result": { "path": deleted_path }, "change": record })))
+                    .log_transfer(to, from, "move", bytes, &hash, &session_id, &run_id, diff, &before, &moved)?;
+                reindex_changes(search_index.as_deref(), &fs_ops, &server_name, [&record]);
+                Ok(text_result(json!({ "result": { "from": from, "to": to }, "change": record })))
             }),
         );
     }
 
     {
-        let change_log = change_log.clone();
         let fs_ops = fs_ops.clone();
+        let change_log = change_log.clone();
         let session_id = session_id.clone();
         let run_id = run_id.clone();
+        let search_index = search_index.clone();
+        let server_name = server_name.clone();
         server.register_tool(
-            "apply_patch",
+            "copy_path",
             &format!(
-                "Apply a patch to one or more files.\nPatch format uses *** Begin Patch / *** Update File / *** Add File / *** Delete File / *** End Patch.\n{}.\n{workspace_note}",
+                "Copy a file or directory tree. Symlinks, special files and denied paths inside copied directories are skipped and listed in result.skipped.\nFails if the destination exists unless overwrite is set (files only).\nMax write bytes per file: {}.\nSet dry_run to validate without copying or logging.\n{}.\n{workspace_note}",
+                format_bytes(max_write_bytes),
                 if allow_writes { "Writes enabled" } else { "Writes disabled" }
             ),
             json!({
                 "type": "object",
                 "properties": {
-                    "patch": { "type": "string", "minLength": 1 }
+                    "from": { "type": "string" },
+                    "to": { "type": "string" },
+                    "overwrite": { "type": "boolean" },
+                    "dry_run": { "type": "boolean" }
                 },
-                "required": ["patch"]
+                "required": ["from", "to"]
             }),
             Box::new(move |args| {
-                let patch_text = args
-                    .get("patch")
-                    .and_then(|v| v.as_str())
-                    .ok_or("patch is required".to_string())?;
-                let patch_diffs: HashMap<String, String> = extract_patch_diffs(patch_text);
-                let result = apply_patch(&root, patch_text, allow_writes)?;
-                let mut hashes = Vec::new();
-
-                for path in result.updated.iter().chain(result.added.iter()) {
-                    let full_path = fs_ops.resolve_path(path)?;
-                    let content = std::fs::read(&full_path).map_err(|err| err.to_string())?;
-                    let hash = sha256_bytes(&content);
-                    let diff = patch_diffs.get(path).cloned();
-                    change_log.borrow().log_change(
-                        path,
-                        "write",
-                        content.len() as i64,
-                        &hash,
-                        &session_id,
-                        &run_id,
-                        diff,
-                    )?;
-                    hashes.push(json!({ "path": path, "sha256": hash }));
+                let (from, to) = transfer_paths(&args)?;
+                let overwrite = args.get("overwrite").and_then(|v| v.as_bool()).unwrap_or(false);
+                let dry_run = args.get("dry_run").and_then(|v| v.as_bool()).unwrap_or(false);
+                let (_, dest) = fs_ops.check_transfer(from, to, overwrite)?;
+                if dry_run {
+                    return Ok(text_result(json!({
+                        "dry_run": true,
+                        "result": { "from": from, "to": to, "overwrites": dest.exists() }
+                    })));
                 }
+                let before = fs_ops.snapshot(&dest);
+                let copied = fs_ops.copy_path(from, to, overwrite)?;
+                let after = fs_ops.snapshot(&dest);
+                let hash = match &after {
+                    Snapshot::Content(content) => sha256_bytes(content),
+                    _ => String::new(),
+                };
+                let diff = snapshot_diff(&before, &after)
+                    .map(|diff| format!("copy from {from}\ncopy to {to}\n{diff}"));
+                let record = change_log.borrow().log_transfer(
+                    to,
+                    from,
+                    "copy",
+                    copied.bytes as i64,
+                    &hash,
+                    &session_id,
+                    &run_id,
+                    diff,
+                    &before,
+                    &after,
+                )?;
+                reindex_changes(search_index.as_deref(), &fs_ops, &server_name, [&record]);
+                Ok(text_result(json!({
+                    "result": { "from": from, "to": to, "bytes": copied.bytes, "skipped": copied.skipped },
+                    "change": record
+                })))
+            }),
+        );
+    }
 
-                for path in &result.deleted {
-                    let diff = patch_diffs.get(path).cloned();
-                    change_log
-                        .borrow()
-                        .log_change(path, "delete", 0, "", &session_id, &run_id, diff)?;
+    {
+        let fs_ops = fs_ops.clone();
+        let change_log = change_log.clone();
+        let session_id = session_id.clone();
+        let run_id = run_id.clone();
+        server.register_tool(
+            "make_dir",
+            &format!(
+                "Create a directory, including missing parents. Succeeds without logging if it already exists.\n{}.\n{workspace_note}",
+                if allow_writes { "Writes enabled" } else { "Writes disabled" }
+            ),
+            json!({
+                "type": "object",
+                "properties": {
+                    "path": { "type": "string" }
+                },
+                "required": ["path"]
+            }),
+            Box::new(move |args| {
+                let path = args
+                    .get("path")
+                    .and_then(|v| v.as_str())
+                    .ok_or("path is required".to_string())?;
+                if !fs_ops.make_dir(path)? {
+                    return Ok(text_result(json!({ "result": { "path": path, "created": false } })));
                 }
-
-                Ok(text_result(json!({ "result": result, "files": hashes })))
+                let log = change_log.borrow();
+                let record = log.log_change(
+                    path,
+                    "mkdir",
+                    0,
+                    "",
+                    &session_id,
+                    &run_id,
+                    None,
+                    &Snapshot::Missing,
+                    &Snapshot::Unknown,
+                )?;
+                Ok(text_result(json!({ "result": { "path": path, "created": true }, "change": record })))
             }),
         );
     }
 
-    if let Err(err) = server.run_stdio() {
-        eprintln!("[{server_name}] Server crashed: {err}");
-        std::process::exit(1);
-    }
+    {
+        let change_log = change_log.clone();
+        let fs_ops = fs_ops.clone();
+        let tracker = tracker.clone();
+        let session_id = session_id.clone();
+        let run_id = run_id.clone();
+        let hooks = hooks.clone();
+        let search_index = search_index.clone();
+        let server_name = server_name.clone();
+        server.register_tool(
+            "apply_patch",
+            &format!(
+                "Apply a patch to one or more files.\nPatch format uses *** Begin Patch / *** Update File / *** Add File / *** Delete File / *** End Patch.\nUnified diffs (diff --git, ---/+++/@@ hunks, renames, new and deleted files) are also accepted.\nHunk context is matched exactly first, then ignoring trailing whitespace, indentation and Unicode punctuation; result.hunks reports the fuzz level each hunk needed.\nAll files are staged and validated before anything is written; on failure every file is restored.\nSet dry_run to preview the diff without writing or logging.\n{}.\n{workspace_note}",
+                if allow_writes { "Writes enabled" } else { "Writes disabled" }
+            ),
+            json!({
+                "type": "object",
+                "properties": {
+                    "patch": { "type": "string", "minLength": 1 },
+                    "dry_run": { "type": "boolean" },
+                    "expected_sha256": {
+                        "type": "object",
+                        "description": "Map of file path to the sha256 it must still have.",
+                        "additionalProperties": { "type": "string" }
+                    }
+                },
+                "required": ["patch"]
+            }),
+            Box::new(move |args| {
+                let patch_text = args
+                    .get("patch")
+                    .and_then(|v| v.as_str())
+                    .ok_or("patch is required".to_string())?;
+                let dry_run = args.get("dry_run").and_then(|v| v.as_bool()).unwrap_or(false);
+                if let Some(expected) = args.get("expected_sha256").and_then(|v| v.as_object()) {
+                    // The patch may not apply to the changed file; the conflict
+                    // then only carries a diff if the expected version is known.
+                    let staged = preview_patch(&fs_ops, patch_text)
+                        .map(|(_, staged)| staged)
+                        .unwrap_or_default();
+                    for (path, hash) in expected {
+                        let hash = hash
+                            .as_str()
+                            .ok_or_else(|| format!("expected_sha256 for {path} must be a string"))?;
+                        let target = fs_ops.resolve_path(path)?;
+                        let proposed = staged.iter().find(|file| file.target == target).map(|file| {
+                            file.content
+                                .as_ref()
+                                .map(|content| Snapshot::Content(content.as_bytes().to_vec()))
+                                .unwrap_or(Snapshot::Missing)
+                        });
+                        check_expected_sha256(&fs_ops, &change_log.borrow(), path, hash, proposed.as_ref())?;
+                    }
+                }
+                if dry_run {
+                    let (result, staged) = preview_patch(&fs_ops, patch_text)?;
+                    let files: Vec<serde_json::Value> = staged
+                        .into_iter()
+                        .map(|file| {
+                            let before_snapshot = read_text_for_diff(&file.target, max_file_bytes)
+                                .unwrap_or_else(DiffInput::omitted);
+                            let (action, sha256, after_snapshot) = match file.content {
+                                Some(content) => (
+                                    "write",
+                                    sha256_bytes(content.as_bytes()),
+                                    DiffInput::text(content),
+                                ),
+                                None => ("delete", String::new(), DiffInput::text(String::new())),
+                            };
+                            json!({
+                                "path": file.path,
+                                "action": action,
+                                "sha256": sha256,
+                                "diff": build_diff(before_snapshot, after_snapshot)
+                            })
+                        })
+                        .collect();
+                    return Ok(text_result(json!({ "dry_run": true, "result": result, "files": files })));
+                }
+                let patch_diffs: HashMap<String, String> = extract_patch_diffs(patch_text);
+                let result = apply_patch(&fs_ops, patch_text)?;
+                let mut hashes = Vec::new();
+                let mut external_changes = Vec::new();
+
+                for file in &result.changes {
+                    let diff = patch_diffs.get(&file.path).cloned();
+                    let before = file
+                        .before
+                        .clone()
+                        .map(Snapshot::Content)
+                        .unwrap_or(Snapshot::Missing);
+                    let external = ExternalCheck {
+                        tracker: &tracker,
+                        fs_ops: &fs_ops,
+                        change_log: &change_log.borrow(),
+                        session_id: &session_id,
+                        run_id: &run_id,
+                        record: record_external,
+                    }
+                    .compare(&file.path, before.clone())?;
+                    external_changes.extend(external);
+                    match &file.after {
+                        Some(content) => {
+                            let hash = sha256_bytes(content.as_bytes());
+                            change_log.borrow().log_change(
+                                &file.path,
+                                "write",
+                                content.len() as i64,
+                                &hash,
+                                &session_id,
+                                &run_id,
+                                diff,
+                                &before,
+                                &Snapshot::Content(content.as_bytes().to_vec()),
+                            )?;
+                            hashes.push(json!({ "path": file.path, "sha256": hash }));
+                        }
+                        None => {
+                            change_log.borrow().log_change(
+                                &file.path,
+                                "delete",
+                                0,
+                                "",
+                                &session_id,
+                                &run_id,
+                                diff,
+                                &before,
+                                &Snapshot::Missing,
+                            )?;
+                        }
+                    }
+                }
+
+                let mut response = json!({ "result": result, "files": hashes });
+                let mut outcomes = Vec::new();
+                for file in result.changes.iter().filter(|file| file.after.is_some()) {
+                    if let Some(outcome) = run_hooks(&hooks, &fs_ops, &change_log.borrow(), &session_id, &run_id, &file.path)? {
+                        outcomes.push(outcome);
+                    }
+                }
+                if !outcomes.is_empty() {
+                    response["hooks"] = json!(outcomes);
+                }
+                reindex(
+                    search_index.as_deref(),
+                    &fs_ops,
+                    &server_name,
+                    result.changes.iter().map(|file| file.path.as_str()),
+                );
+                for file in &result.changes {
+                    tracker.remember(&fs_ops, &file.path);
+                }
+                if !external_changes.is_empty() {
+                    response["external_changes"] = json!(external_changes);
+                }
+                Ok(text_result(response))
+            }),
+        );
+    }
+
+    {
+        let fs_ops = fs_ops.clone();
+        let tracker = tracker.clone();
+        let change_log = change_log.clone();
+        let session_id = session_id.clone();
+        let run_id = run_id.clone();
+        let hooks = hooks.clone();
+        let search_index = search_index.clone();
+        let server_name = server_name.clone();
+        server.register_tool(
+            "edit_file",
+            &format!(
+                "Replace exact text in a file. old_string must match exactly once unless replace_all is set.\nPass edits (a list of old_string/new_string/replace_all) to apply several edits to one file atomically.\nEdits match against the text with \\n line breaks and no BOM; the file's line endings and BOM are kept.\nMax write bytes: {}.\nSet dry_run to preview the diff without writing or logging.\n{}.\n{workspace_note}",
+                format_bytes(max_write_bytes),
+                if allow_writes { "Writes enabled" } else { "Writes disabled" }
+            ),
+            json!({
+                "type": "object",
+                "properties": {
+                    "path": { "type": "string" },
+                    "old_string": { "type": "string", "minLength": 1 },
+                    "new_string": { "type": "string" },
+                    "replace_all": { "type": "boolean" },
+                    "edits": {
+                        "type": "array",
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "old_string": { "type": "string", "minLength": 1 },
+                                "new_string": { "type": "string" },
+                                "replace_all": { "type": "boolean" }
+                            },
+                            "required": ["old_string", "new_string"]
+                        }
+                    },
+                    "dry_run": { "type": "boolean" },
+                    "expected_sha256": {
+                        "type": "string",
+                        "description": "sha256 from the last read; the edit fails with a conflict if the file changed since."
+                    },
+                    "line_ending": {
+                        "type": "string",
+                        "enum": ["auto", "lf", "crlf", "as_is"],
+                        "description": "auto (default) keeps the file's line endings; as_is matches and writes text untouched."
+                    },
+                    "bom": { "type": "boolean" },
+                    "trailing_newline": { "type": "boolean" }
+                },
+                "required": ["path"]
+            }),
+            Box::new(move |args| {
+                let path = args
+                    .get("path")
+                    .and_then(|v| v.as_str())
+                    .ok_or("path is required".to_string())?;
+                let edits = parse_edits(&args)?;
+                let dry_run = args.get("dry_run").and_then(|v| v.as_bool()).unwrap_or(false);
+                let overrides = parse_overrides(&args)?;
+                let (_, _, _, original) = fs_ops.read_file_raw(path)?;
+                let existing = text_format::detect(&original);
+                let logical = existing
+                    .map(|style| style.line_ending != Some(LineEnding::Mixed) && !overrides.as_is)
+                    .unwrap_or(false);
+                let edited = if logical {
+                    let edits: Vec<Edit> = edits
+                        .into_iter()
+                        .map(|edit| Edit {
+                            old_string: text_format::to_logical(&edit.old_string),
+                            new_string: text_format::to_logical(&edit.new_string),
+                            ..edit
+                        })
+                        .collect();
+                    apply_edits(path, &text_format::to_logical(&original), &edits)
+                } else {
+                    apply_edits(path, &original, &edits)
+                };
+                // Edits that no longer match the changed file still get the
+                // conflict error, just without the proposed content.
+                let edited = edited.map(|(content, matches)| {
+                    let (content, normalization) = text_format::normalize(&content, existing, &overrides);
+                    (content, matches, normalization)
+                });
+                if let Some(expected) = args.get("expected_sha256").and_then(|v| v.as_str()) {
+                    let proposed = edited
+                        .as_ref()
+                        .ok()
+                        .map(|(content, _, _)| Snapshot::Content(content.as_bytes().to_vec()));
+                    check_expected_sha256(&fs_ops, &change_log.borrow(), path, expected, proposed.as_ref())?;
+                }
+                let (content, matches, normalization) = edited?;
+                let target = fs_ops.check_write(path, content.len())?;
+                let diff = build_diff(DiffInput::text(original.clone()), DiffInput::text(content.clone()));
+                if dry_run {
+                    return Ok(text_result(json!({
+                        "dry_run": true,
+                        "result": {
+                            "path": path,
+                            "bytes": content.len(),
+                            "sha256": sha256_bytes(content.as_bytes())
+                        },
+                        "matches": matches,
+                        "normalization": normalization,
+                        "diff": diff
+                    })));
+                }
+                let before = fs_ops.snapshot(&target);
+                let result = fs_ops.write_file(path, &content)?;
+                let external = check_external(&tracker, &fs_ops, &change_log.borrow(), &session_id, &run_id, &result.path, &before)?;
+                let record = change_log.borrow().log_change(
+                    &result.path,
+                    "edit",
+                    result.bytes,
+                    &result.sha256,
+                    &session_id,
+                    &run_id,
+                    diff.clone(),
+                    &before,
+                    &Snapshot::Content(content.into_bytes()),
+                )?;
+                let mut response = json!({
+                    "result": result,
+                    "matches": matches,
+                    "normalization": normalization,
+                    "diff": diff,
+                    "change": record
+                });
+                if let Some(outcome) = run_hooks(&hooks, &fs_ops, &change_log.borrow(), &session_id, &run_id, &result.path)? {
+                    response["hooks"] = json!(outcome);
+                }
+                reindex(search_index.as_deref(), &fs_ops, &server_name, [result.path.as_str()]);
+                tracker.remember(&fs_ops, &result.path);
+                if let Some(external) = external {
+                    response["external_change"] = json!(external);
+                }
+                Ok(text_result(response))
+            }),
+        );
+    }
+
+    {
+        let fs_ops = fs_ops.clone();
+        let change_log = change_log.clone();
+        let session_id = session_id.clone();
+        let run_id = run_id.clone();
+        let search_index = search_index.clone();
+        let server_name = server_name.clone();
+        server.register_tool(
+            "revert_change",
+            &format!(
+                "Restore the files touched by a logged change to their content before that change; a move is undone at both paths, and a moved directory is moved back. Each restored file is logged as a revert change.\nFails if a file changed since, unless force is set.\n{}.\n{workspace_note}",
+                if allow_writes { "Writes enabled" } else { "Writes disabled" }
+            ),
+            json!({
+                "type": "object",
+                "properties": {
+                    "change_id": { "type": "string" },
+                    "force": { "type": "boolean" }
+                },
+                "required": ["change_id"]
+            }),
+            Box::new(move |args| {
+                let change_id = args
+                    .get("change_id")
+                    .and_then(|v| v.as_str())
+                    .ok_or("change_id is required".to_string())?;
+                let force = args.get("force").and_then(|v| v.as_bool()).unwrap_or(false);
+                let store = change_log.borrow();
+                let reverter = Reverter {
+                    fs_ops: &fs_ops,
+                    change_log: &store,
+                    session_id: &session_id,
+                    run_id: &run_id,
+                };
+                let outcomes = reverter.revert_change(change_id, force)?;
+                reindex_changes(
+                    search_index.as_deref(),
+                    &fs_ops,
+                    &server_name,
+                    outcomes.iter().filter_map(|outcome| outcome.change.as_ref()),
+                );
+                Ok(text_result(json!({ "files": outcomes })))
+            }),
+        );
+    }
+
+    {
+        let fs_ops = fs_ops.clone();
+        let change_log = change_log.clone();
+        let session_id = session_id.clone();
+        let run_id = run_id.clone();
+        let search_index = search_index.clone();
+        let server_name = server_name.clone();
+        server.register_tool(
+            "revert_session",
+            &format!(
+                "Restore every file changed in a session to its state before the session's first change. Each restored file is logged as a revert change.\nFiles changed since the session are skipped unless force is set.\n{}.\n{workspace_note}",
+                if allow_writes { "Writes enabled" } else { "Writes disabled" }
+            ),
+            json!({
+                "type": "object",
+                "properties": {
+                    "session_id": { "type": "string" },
+                    "force": { "type": "boolean" }
+                },
+                "required": ["session_id"]
+            }),
+            Box::new(move |args| {
+                let target_session = args
+                    .get("session_id")
+                    .and_then(|v| v.as_str())
+                    .ok_or("session_id is required".to_string())?;
+                let force = args.get("force").and_then(|v| v.as_bool()).unwrap_or(false);
+                let store = change_log.borrow();
+                let reverter = Reverter {
+                    fs_ops: &fs_ops,
+                    change_log: &store,
+                    session_id: &session_id,
+                    run_id: &run_id,
+                };
+                let outcomes = reverter.revert_session(target_session, force)?;
+                reindex_changes(
+                    search_index.as_deref(),
+                    &fs_ops,
+                    &server_name,
+                    outcomes.iter().filter_map(|outcome| outcome.change.as_ref()),
+                );
+                let reverted = outcomes.iter().filter(|o| o.status == "reverted").count();
+                Ok(text_result(json!({
+                    "reverted": reverted,
+                    "skipped": outcomes.len() - reverted,
+                    "files": outcomes
+                })))
+            }),
+        );
+    }
+
+    {
+        let fs_ops = fs_ops.clone();
+        let change_log = change_log.clone();
+        let session_id = session_id.clone();
+        let run_id = run_id.clone();
+        let tracker = tracker.clone();
+        server.register_tool(
+            "changed_since_read",
+            &format!(
+                "List files this session has read or written that were since modified or deleted outside this server (by a shell, an editor, git checkout, ...), detected from mtime/size and sha256. Changes made through this server's own tools are not reported. Set record to log each one as an external change in the change log and treat it as seen (default {}); otherwise a file stays listed until it is read or written again.\n{workspace_note}",
+                if record_external { "on" } else { "off" }
+            ),
+            json!({
+                "type": "object",
+                "properties": {
+                    "record": { "type": "boolean" }
+                }
+            }),
+            Box::new(move |args| {
+                let record = args
+                    .get("record")
+                    .and_then(|v| v.as_bool())
+                    .unwrap_or(record_external);
+                let changes = ExternalCheck {
+                    tracker: &tracker,
+                    fs_ops: &fs_ops,
+                    change_log: &change_log.borrow(),
+                    session_id: &session_id,
+                    run_id: &run_id,
+                    record,
+                }
+                .all()?;
+                Ok(text_result(json!({
+                    "tracked": tracker.tracked(),
+                    "count": changes.len(),
+                    "changes": changes
+                })))
+            }),
+        );
+    }
+
+    {
+        let change_log = change_log.clone();
+        let session_id = session_id.clone();
+        server.register_tool(
+            "summarize_changes",
+            &format!(
+                "Summarize what changed in a session and/or run: one net entry per file (added, modified, deleted) with net line counts and the number of touches, plus a combined net diff from each file's first before-state to its last after-state.\nDefaults to the current session when neither session_id nor run_id is given. Set include_diff to false to omit the diff.\n{workspace_note}"
+            ),
+            json!({
+                "type": "object",
+                "properties": {
+                    "session_id": { "type": "string" },
+                    "run_id": { "type": "string" },
+                    "include_diff": { "type": "boolean" }
+                }
+            }),
+            Box::new(move |args| {
+                let target_session = args.get("session_id").and_then(|v| v.as_str());
+                let target_run = args.get("run_id").and_then(|v| v.as_str());
+                let include_diff = args.get("include_diff").and_then(|v| v.as_bool()).unwrap_or(true);
+                let target_session = match (target_session, target_run) {
+                    (None, None) => Some(session_id.as_str()),
+                    (session, _) => session,
+                };
+                let summary = summarize_changes(&change_log.borrow(), target_session, target_run, include_diff)?;
+                Ok(text_result(json!({
+                    "session_id": target_session,
+                    "run_id": target_run,
+                    "summary": summary
+                })))
+            }),
+        );
+    }
+
+    {
+        let change_log = change_log.clone();
+        let session_id = session_id.clone();
+        server.register_tool(
+            "export_changes",
+            &format!(
+                "Export the changes of a session and/or run as one document: format \"patch\" (default) gives the net diff of every touched file in git diff layout, \"jsonl\" gives one change record per line with its diff.\nDefaults to the current session when neither session_id nor run_id is given.\n{workspace_note}"
+            ),
+            json!({
+                "type": "object",
+                "properties": {
+                    "session_id": { "type": "string" },
+                    "run_id": { "type": "string" },
+                    "format": { "type": "string", "enum": ["patch", "jsonl"] }
+                }
+            }),
+            Box::new(move |args| {
+                let target_session = args.get("session_id").and_then(|v| v.as_str());
+                let target_run = args.get("run_id").and_then(|v| v.as_str());
+                let format = ExportFormat::parse(args.get("format").and_then(|v| v.as_str()))?;
+                let target_session = match (target_session, target_run) {
+                    (None, None) => Some(session_id.as_str()),
+                    (session, _) => session,
+                };
+                let export = export_changes(&change_log.borrow(), target_session, target_run, format)?;
+                Ok(text_result(json!({
+                    "session_id": target_session,
+                    "run_id": target_run,
+                    "format": format.extension(),
+                    "changes": export.changes,
+                    "bytes": export.content.len(),
+                    "content": export.content
+                })))
+            }),
+        );
+    }
+
+    {
+        let change_log = change_log.clone();
+        let retention = retention.clone();
+        server.register_tool(
+            "compact_change_log",
+            &format!(
+                "Maintain the change log database: delete the oldest changes beyond the retention limits (max_age_days, max_changes, max_db_mb; defaults to the server's --retain-* settings), drop unreferenced content blobs, re-encode stored diffs with the configured codec ({}), and optionally VACUUM to shrink the file.\nA limit of 0 deletes every change and is rejected unless force is set.\n{}.\n{workspace_note}",
+                diff_codec.as_str(),
+                if allow_writes { "Writes enabled" } else { "Writes disabled" }
+            ),
+            json!({
+                "type": "object",
+                "properties": {
+                    "max_age_days": { "type": "integer", "minimum": 0 },
+                    "max_changes": { "type": "integer", "minimum": 0 },
+                    "max_db_mb": { "type": "integer", "minimum": 1 },
+                    "recompress": { "type": "boolean" },
+                    "vacuum": { "type": "boolean" },
+                    "force": { "type": "boolean" }
+                }
+            }),
+            Box::new(move |args| {
+                if !allow_writes {
+                    return Err("Writes are disabled.".to_string());
+                }
+                let limit = |key: &str| args.get(key).and_then(|v| v.as_i64());
+                let force = args.get("force").and_then(|v| v.as_bool()).unwrap_or(false);
+                for key in ["max_age_days", "max_changes"] {
+                    if limit(key) == Some(0) && !force {
+                        return Err(format!("{key} 0 deletes every logged change; pass force to confirm."));
+                    }
+                }
+                let policy = RetentionPolicy {
+                    max_age_days: limit("max_age_days").or(retention.max_age_days),
+                    max_changes: limit("max_changes").or(retention.max_changes),
+                    max_db_bytes: limit("max_db_mb")
+                        .map(|mb| mb * 1024 * 1024)
+                        .or(retention.max_db_bytes),
+                };
+                let recompress = args.get("recompress").and_then(|v| v.as_bool()).unwrap_or(true);
+                let vacuum = args.get("vacuum").and_then(|v| v.as_bool()).unwrap_or(false);
+                let report = compact_store(&change_log.borrow(), &policy, recompress, vacuum)?;
+                Ok(text_result(report))
+            }),
+        );
+    }
+
+    {
+        let fs_ops = fs_ops.clone();
+        let change_log = change_log.clone();
+        server.register_tool(
+            "file_at",
+            &format!(
+                "Return a file's content as it was right after a logged change (change_id) or at a point in time (timestamp, RFC 3339), replayed from the change log.\nBinary content is returned as content_base64.\n{workspace_note}"
+            ),
+            json!({
+                "type": "object",
+                "properties": {
+                    "path": { "type": "string" },
+                    "change_id": { "type": "string" },
+                    "timestamp": { "type": "string" }
+                },
+                "required": ["path"]
+            }),
+            Box::new(move |args| {
+                let path = args
+                    .get("path")
+                    .and_then(|v| v.as_str())
+                    .ok_or("path is required".to_string())?;
+                let target = fs_ops.resolve_path(path)?;
+                let at = parse_as_of(
+                    args.get("change_id").and_then(|v| v.as_str()),
+                    args.get("timestamp").and_then(|v| v.as_str()),
+                )?;
+                let result = file_at(&change_log.borrow(), &fs_ops.relative(&target), &at)?;
+                Ok(text_result(result))
+            }),
+        );
+    }
+
+    {
+        let fs_ops = fs_ops.clone();
+        let change_log = change_log.clone();
+        let session_id = session_id.clone();
+        let run_id = run_id.clone();
+        server.register_tool(
+            "create_checkpoint",
+            &format!(
+                "Snapshot every tracked text file under the root into the change log store under a unique name, so the workspace can be diffed against or restored to it later. Works without git.\nFiles excluded by ignore files or the path policy, binary files and files over {} are skipped.\n{workspace_note}",
+                format_bytes(max_write_bytes)
+            ),
+            json!({
+                "type": "object",
+                "properties": {
+                    "name": { "type": "string", "minLength": 1 }
+                },
+                "required": ["name"]
+            }),
+            Box::new(move |args| {
+                let name = args
+                    .get("name")
+                    .and_then(|v| v.as_str())
+                    .ok_or("name is required".to_string())?;
+                let store = change_log.borrow();
+                let checkpoints = Checkpoints {
+                    fs_ops: &fs_ops,
+                    change_log: &store,
+                    session_id: &session_id,
+                    run_id: &run_id,
+                };
+                let (checkpoint, skipped) = checkpoints.create(name)?;
+                Ok(text_result(json!({ "checkpoint": checkpoint, "skipped_files": skipped })))
+            }),
+        );
+    }
+
+    {
+        let change_log = change_log.clone();
+        server.register_tool(
+            "list_checkpoints",
+            &format!("List checkpoints, newest first.\n{workspace_note}"),
+            json!({
+                "type": "object",
+                "properties": {}
+            }),
+            Box::new(move |_args| {
+                let checkpoints = change_log.borrow().list_checkpoints()?;
+                Ok(text_result(json!({ "count": checkpoints.len(), "checkpoints": checkpoints })))
+            }),
+        );
+    }
+
+    {
+        let fs_ops = fs_ops.clone();
+        let change_log = change_log.clone();
+        let session_id = session_id.clone();
+        let run_id = run_id.clone();
+        server.register_tool(
+            "diff_checkpoint",
+            &format!(
+                "Show files added, modified or deleted since a checkpoint, with a diff from the checkpoint to the current content.\nA checkpointed file that is now binary or over the write limit is reported as modified with comparable false.\nSet include_diff to false for the file list only.\n{workspace_note}"
+            ),
+            json!({
+                "type": "object",
+                "properties": {
+                    "name": { "type": "string", "minLength": 1 },
+                    "include_diff": { "type": "boolean" }
+                },
+                "required": ["name"]
+            }),
+            Box::new(move |args| {
+                let name = args
+                    .get("name")
+                    .and_then(|v| v.as_str())
+                    .ok_or("name is required".to_string())?;
+                let include_diff = args.get("include_diff").and_then(|v| v.as_bool()).unwrap_or(true);
+                let store = change_log.borrow();
+                let checkpoints = Checkpoints {
+                    fs_ops: &fs_ops,
+                    change_log: &store,
+                    session_id: &session_id,
+                    run_id: &run_id,
+                };
+                let files = checkpoints.diff(name, include_diff)?;
+                Ok(text_result(json!({ "name": name, "count": files.len(), "files": files })))
+            }),
+        );
+    }
+
+    {
+        let fs_ops = fs_ops.clone();
+        let change_log = change_log.clone();
+        let session_id = session_id.clone();
+        let run_id = run_id.clone();
+        let search_index = search_index.clone();
+        let server_name = server_name.clone();
+        server.register_tool(
+            "restore_checkpoint",
+            &format!(
+                "Restore the workspace to a checkpoint: changed and deleted files get their checkpoint content back, and tracked files created since are deleted unless keep_new_files is set. Each restored file is logged as a restore change.\nSet dry_run to list what would change.\n{}.\n{workspace_note}",
+                if allow_writes { "Writes enabled" } else { "Writes disabled" }
+            ),
+            json!({
+                "type": "object",
+                "properties": {
+                    "name": { "type": "string", "minLength": 1 },
+                    "keep_new_files": { "type": "boolean" },
+                    "dry_run": { "type": "boolean" }
+                },
+                "required": ["name"]
+            }),
+            Box::new(move |args| {
+                let name = args
+                    .get("name")
+                    .and_then(|v| v.as_str())
+                    .ok_or("name is required".to_string())?;
+                let keep_new_files = args.get("keep_new_files").and_then(|v| v.as_bool()).unwrap_or(false);
+                let dry_run = args.get("dry_run").and_then(|v| v.as_bool()).unwrap_or(false);
+                let store = change_log.borrow();
+                let checkpoints = Checkpoints {
+                    fs_ops: &fs_ops,
+                    change_log: &store,
+                    session_id: &session_id,
+                    run_id: &run_id,
+                };
+                let files = checkpoints.restore(name, keep_new_files, dry_run)?;
+                reindex_changes(
+                    search_index.as_deref(),
+                    &fs_ops,
+                    &server_name,
+                    files.iter().filter_map(|file| file.change.as_ref()),
+                );
+                Ok(text_result(json!({
+                    "name": name,
+                    "dry_run": dry_run,
+                    "count": files.len(),
+                    "files": files
+                })))
+            }),
+        );
+    }
+
+    {
+        let fs_ops = fs_ops.clone();
+        server.register_tool(
+            "file_outline",
+            &format!(
+                "List the functions, structs/classes, traits/interfaces, impls and methods declared in a source file with their line ranges, so read_file_range can fetch just the part you need. Supported extensions: {SUPPORTED_EXTENSIONS}.\n{workspace_note}"
+            ),
+            json!({
+                "type": "object",
+                "properties": {
+                    "path": { "type": "string" }
+                },
+                "required": ["path"]
+            }),
+            Box::new(move |args| {
+                let path = args
+                    .get("path")
+                    .and_then(|v| v.as_str())
+                    .ok_or("path is required".to_string())?;
+                let language = Language::from_path(Path::new(path)).ok_or_else(|| {
+                    format!("Outline is not supported for {path} (supported extensions: {SUPPORTED_EXTENSIONS}).")
+                })?;
+                let (path, size, sha256, content) = fs_ops.read_file_raw(path)?;
+                let symbols = outline(language, &content);
+                Ok(text_result(json!({
+                    "path": path,
+                    "size_bytes": size,
+                    "sha256": sha256,
+                    "total_lines": content.lines().count(),
+                    "symbols": symbols
+                })))
+            }),
+        );
+    }
+
+    {
+        let fs_ops = fs_ops.clone();
+        let db_path = db_path.clone();
+        server.register_tool(
+            "find_symbol",
+            &format!(
+                "Find declarations by name across the workspace ({SUPPORTED_EXTENSIONS} files) using a symbol index kept next to the change log and refreshed from file mtimes on each call. Matches are case-insensitive substrings, exact and prefix matches first; use Parent::name or Parent.name to match members of a type. Optional kind filters (function, method, struct, enum, trait, impl, class, interface, type, ...).\n{workspace_note}"
+            ),
+            json!({
+                "type": "object",
+                "properties": {
+                    "name": { "type": "string" },
+                    "kind": { "type": "string" },
+                    "limit": { "type": "integer", "minimum": 1, "maximum": 500 }
+                },
+                "required": ["name"]
+            }),
+            Box::new(move |args| {
+                let name = args
+                    .get("name")
+                    .and_then(|v| v.as_str())
+                    .ok_or("name is required".to_string())?;
+                let kind = args.get("kind").and_then(|v| v.as_str()).filter(|v| !v.is_empty());
+                let limit = args
+                    .get("limit")
+                    .and_then(|v| v.as_u64())
+                    .unwrap_or(50)
+                    .clamp(1, 500) as usize;
+                let index = SymbolIndex::open(&db_path, &fs_ops)?;
+                let refresh = index.refresh(&fs_ops)?;
+                let symbols = index.find(name, kind, limit)?;
+                Ok(text_result(json!({
+                    "name": name,
+                    "index": refresh,
+                    "count": symbols.len(),
+                    "symbols": symbols
+                })))
+            }),
+        );
+    }
+
+    {
+        let fs_ops = fs_ops.clone();
+        server.register_tool(
+            "git_status",
+            &format!(
+                "Show the git status of the workspace: branch, ahead/behind, and changed, staged and untracked files (from porcelain v2). Paths are relative to the workspace root; denied paths are omitted.\n{workspace_note}"
+            ),
+            json!({ "type": "object", "properties": {} }),
+            Box::new(move |_args| {
+                let git = Git::new(&fs_ops, max_file_bytes as usize);
+                Ok(text_result(git.status()?))
+            }),
+        );
+    }
+
+    {
+        let fs_ops = fs_ops.clone();
+        server.register_tool(
+            "git_diff",
+            &format!(
+                "Show uncommitted changes as a unified diff with per-file line counts. Set staged to diff the index against HEAD instead of the working tree. Optional path limits the diff to a file or directory.\n{workspace_note}"
+            ),
+            json!({
+                "type": "object",
+                "properties": {
+                    "path": { "type": "string" },
+                    "staged": { "type": "boolean" }
+                }
+            }),
+            Box::new(move |args| {
+                let path = args.get("path").and_then(|v| v.as_str());
+                let staged = args.get("staged").and_then(|v| v.as_bool()).unwrap_or(false);
+                let git = Git::new(&fs_ops, max_file_bytes as usize);
+                Ok(text_result(git.diff(path, staged)?))
+            }),
+        );
+    }
+
+    {
+        let fs_ops = fs_ops.clone();
+        server.register_tool(
+            "git_log",
+            &format!(
+                "List recent commits (hash, author, date, subject, changed files). Optional path limits the log to commits touching it and follows renames of a single file.\n{workspace_note}"
+            ),
+            json!({
+                "type": "object",
+                "properties": {
+                    "path": { "type": "string" },
+                    "limit": { "type": "integer", "minimum": 1, "maximum": 200 }
+                }
+            }),
+            Box::new(move |args| {
+                let path = args.get("path").and_then(|v| v.as_str());
+                let limit = args
+                    .get("limit")
+                    .and_then(|v| v.as_u64())
+                    .unwrap_or(20)
+                    .clamp(1, 200) as usize;
+                let git = Git::new(&fs_ops, max_file_bytes as usize);
+                Ok(text_result(git.log(path, limit)?))
+            }),
+        );
+    }
+
+    {
+        let fs_ops = fs_ops.clone();
+        server.register_tool(
+            "git_blame",
+            &format!(
+                "Show which commit last changed each line of a file, optionally for a 1-based inclusive line range.\n{workspace_note}"
+            ),
+            json!({
+                "type": "object",
+                "properties": {
+                    "path": { "type": "string" },
+                    "start_line": { "type": "integer", "minimum": 1 },
+                    "end_line": { "type": "integer", "minimum": 1 }
+                },
+                "required": ["path"]
+            }),
+            Box::new(move |args| {
+                let path = args
+                    .get("path")
+                    .and_then(|v| v.as_str())
+                    .ok_or("path is required".to_string())?;
+                let start_line = args.get("start_line").and_then(|v| v.as_u64()).map(|v| v as usize);
+                let end_line = args.get("end_line").and_then(|v| v.as_u64()).map(|v| v as usize);
+                let git = Git::new(&fs_ops, max_file_bytes as usize);
+                Ok(text_result(git.blame(path, start_line, end_line)?))
+            }),
+        );
+    }
+
+    {
+        let fs_ops = fs_ops.clone();
+        server.register_tool(
+            "git_show",
+            &format!(
+                "Show a commit (message, changed files and patch) given a revision such as HEAD~1, or a file as of a revision given rev:path (e.g. HEAD:src/main.rs; the path is relative to the workspace root).\n{workspace_note}"
+            ),
+            json!({
+                "type": "object",
+                "properties": {
+                    "rev": { "type": "string", "description": "Revision, or rev:path for file content." }
+                },
+                "required": ["rev"]
+            }),
+            Box::new(move |args| {
+                let rev = args
+                    .get("rev")
+                    .and_then(|v| v.as_str())
+                    .ok_or("rev is required".to_string())?;
+                let git = Git::new(&fs_ops, max_file_bytes as usize);
+                Ok(text_result(git.show(rev)?))
+            }),
+        );
+    }
+
+    {
+        let fs_ops = fs_ops.clone();
+        server.register_tool(
+            "git_stage",
+            &format!(
+                "Stage paths (including deletions) for the next commit, or unstage them with unstage=true. Paths, and every file under a staged directory, must be writable under the path policy.\n{}.\n{workspace_note}",
+                if allow_writes { "Writes enabled" } else { "Writes disabled" }
+            ),
+            json!({
+                "type": "object",
+                "properties": {
+                    "paths": { "type": "array", "items": { "type": "string" } },
+                    "unstage": { "type": "boolean" }
+                },
+                "required": ["paths"]
+            }),
+            Box::new(move |args| {
+                let paths = string_list(args.get("paths"));
+                let unstage = args.get("unstage").and_then(|v| v.as_bool()).unwrap_or(false);
+                let git = Git::new(&fs_ops, max_file_bytes as usize);
+                Ok(text_result(git.stage(&paths, unstage)?))
+            }),
+        );
+    }
+
+    {
+        let fs_ops = fs_ops.clone();
+        server.register_tool(
+            "git_commit",
+            &format!(
+                "Commit the staged changes with a message. Fails if anything staged is outside the workspace root or not writable under the path policy.\n{}.\n{workspace_note}",
+                if allow_writes { "Writes enabled" } else { "Writes disabled" }
+            ),
+            json!({
+                "type": "object",
+                "properties": {
+                    "message": { "type": "string" }
+                },
+                "required": ["message"]
+            }),
+            Box::new(move |args| {
+                let message = args
+                    .get("message")
+                    .and_then(|v| v.as_str())
+                    .ok_or("message is required".to_string())?;
+                let git = Git::new(&fs_ops, max_file_bytes as usize);
+                Ok(text_result(git.commit(message)?))
+            }),
+        );
+    }
+
+    if let Err(err) = server.run_stdio() {
+        eprintln!("[{server_name}] Server crashed: {err}");
+        std::process::exit(1);
+    }
 }
 
 fn text_result(data: serde_json::Value) -> serde_json::Value {
@@ -489,9 +2192,147 @@ fn text_result(data: serde_json::Value) -> serde_json::Value {
     })
 }
 
+fn transfer_paths(args: &serde_json::Value) -> Result<(&str, &str), String> {
+    let from = args
+        .get("from")
+        .and_then(|v| v.as_str())
+        .ok_or("from is required".to_string())?;
+    let to = args
+        .get("to")
+        .and_then(|v| v.as_str())
+        .ok_or("to is required".to_string())?;
+    Ok((from, to))
+}
+
+fn comma_list(value: Option<&String>) -> Vec<String> {
+    value
+        .map(|raw| {
+            raw.split(',')
+                .map(|item| item.trim().to_string())
+                .filter(|item| !item.is_empty())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Accepts either a single string or an array of strings.
+fn string_list(value: Option<&serde_json::Value>) -> Vec<String> {
+    match value {
+        Some(serde_json::Value::String(item)) if !item.trim().is_empty() => vec![item.trim().to_string()],
+        Some(serde_json::Value::Array(items)) => items
+            .iter()
+            .filter_map(|item| item.as_str())
+            .map(|item| item.trim().to_string())
+            .filter(|item| !item.is_empty())
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Applies `policy`, re-encodes diffs and optionally vacuums, reporting the
+/// store before and after.
+fn compact_store(
+    store: &ChangeLogStore,
+    policy: &RetentionPolicy,
+    recompress: bool,
+    vacuum: bool,
+) -> Result<Value, String> {
+    let before = store.stats()?;
+    let pruned = store.prune(policy)?;
+    let recompressed = if recompress { store.recompress_diffs()? } else { 0 };
+    if vacuum {
+        store.vacuum()?;
+    }
+    Ok(json!({
+        "retention": policy,
+        "pruned": pruned,
+        "recompressed": recompressed,
+        "vacuumed": vacuum,
+        "before": before,
+        "after": store.stats()?
+    }))
+}
+
+/// Runs the post-write hooks for `path` and logs any reformatting.
+fn run_hooks(
+    hooks: &PostWriteHooks,
+    fs_ops: &FsOps,
+    change_log: &ChangeLogStore,
+    session_id: &str,
+    run_id: &str,
+    path: &str,
+) -> Result<Option<HookOutcome>, String> {
+    HookRunner {
+        hooks,
+        fs_ops,
+        change_log,
+        session_id,
+        run_id,
+    }
+    .after_write(path)
+}
+
+/// Checks `before`, the content `path` had just before a write that
+/// succeeded, for edits made outside the server since the session last saw
+/// it, recording them when the tracker does.
+fn check_external(
+    tracker: &ReadTracker,
+    fs_ops: &FsOps,
+    change_log: &ChangeLogStore,
+    session_id: &str,
+    run_id: &str,
+    path: &str,
+    before: &Snapshot,
+) -> Result<Option<ExternalChange>, String> {
+    ExternalCheck {
+        tracker,
+        fs_ops,
+        change_log,
+        session_id,
+        run_id,
+        record: tracker.records(),
+    }
+    .compare(path, before.clone())
+}
+
+/// Brings the search index up to date for files a tool just wrote. The write
+/// already happened, so failures are only logged; the next search's mtime
+/// refresh catches up.
+fn reindex<'p>(
+    search_index: Option<&SearchIndex>,
+    fs_ops: &FsOps,
+    server_name: &str,
+    paths: impl IntoIterator<Item = &'p str>,
+) {
+    let Some(index) = search_index else {
+        return;
+    };
+    for path in paths {
+        if let Err(err) = index.update_path(fs_ops, path) {
+            eprintln!("[{server_name}] search index update failed for {path}: {err}");
+        }
+    }
+}
+
+/// `reindex` for every path the logged changes touch, including the source
+/// of a move.
+fn reindex_changes<'r>(
+    search_index: Option<&SearchIndex>,
+    fs_ops: &FsOps,
+    server_name: &str,
+    records: impl IntoIterator<Item = &'r ChangeRecord>,
+) {
+    let paths: Vec<&str> = records
+        .into_iter()
+        .flat_map(|record| record.effects())
+        .map(|effect| effect.path)
+        .collect();
+    reindex(search_index, fs_ops, server_name, paths);
+}
+
 fn print_help() {
     println!(
-        "Usage: code-maintainer-mcp-server-rs [--root <path>] [--name <id>] [--write] [--mode <text>] [--session-id <id>] [--run-id <id>]\n\nOptions:\n  --root <path>            Workspace root (default cwd)\n  --name <id>              MCP server name (default code_maintainer)\n  --write                  Allow write operations\n  --mode <text>            If contains 'write' then enables writes\n  --max-bytes <n>          Max file bytes to read (default 256 KB)\n  --max-write-bytes <n>    Max write bytes (default 5 MB)\n  --max-search-results <n> Max search results (default 40)\n  --db <path>              SQLite path for change log\n  --session-id <id>        Session ID override\n  --run-id <id>            Run ID override\n  --admin-port <p>         Start admin HTTP server on port p\n  --admin-host <h>         Admin HTTP bind host (default 127.0.0.1)\n  --admin-ui-root <path>   Admin UI dist directory\n  --help                   Show help"
+        "Usage: code-maintainer-mcp-server-rs [--root <path>] [--name <id>] [--write] [--mode <text>] [--session-id <id>] [--run-id <id>]\n\nOptions:\n  --root <path>            Workspace root (default cwd)\n  --name <id>              MCP server name (default code_maintainer)\n  --write                  Allow write operations\n  --mode <text>            If contains 'write' then enables writes\n  --max-bytes <n>          Max file bytes to read (default 256 KB)\n  --max-write-bytes <n>    Max write bytes (default 5 MB)\n  --max-search-results <n> Max search results (default 40)\n  --diff-context <n>       Context lines around diff hunks (default 3)\n  --policy <path>          JSON path policy file with deny/readonly/writable glob lists\n  --deny <globs>           Comma-separated globs that cannot be read or written\n  --readonly <globs>       Comma-separated globs that cannot be written\n  --writable <globs>       Comma-separated globs; when set, only these can be written\n  --hooks <path>           JSON post-write hooks: [{{\"glob\": \"*.rs\", \"command\": \"rustfmt {{path}}\"}}]\n  --hook <glob=command>    Post-write hook, repeatable, e.g. \"*.ts=prettier --write {{path}}\"\n  --hook-timeout <secs>    Hook timeout in seconds (default 30); a hook that runs over is killed with everything it started\n  --allow-external <paths> Comma-separated directories outside the root that symlinks may point into\n  --db <path>              SQLite path for change log\n  --diff-codec <c>         Store new diffs as none, zstd or deflate (default none)\n  --retain-days <n>        Drop changes older than n days at startup\n  --retain-changes <n>     Keep at most n changes\n  --retain-mb <n>          Drop the oldest changes while the log uses more than n MB\n  --force-retention        Allow --retain-days 0 or --retain-changes 0, which delete every change\n  --compact                Apply retention, re-encode diffs, VACUUM the log and exit\n  --record-external        Log edits made outside the server to files it has seen as external changes\n  --search-index           Keep a full-text index of the workspace in the db to speed up search_text\n  --session-id <id>        Session ID override\n  --run-id <id>            Run ID override\n  --admin-port <p>         Start admin HTTP server on port p\n  --admin-host <h>         Admin HTTP bind host (default 127.0.0.1)\n  --admin-ui-root <path>   Admin UI dist directory\n  --help                   Show help"
     );
 }
 
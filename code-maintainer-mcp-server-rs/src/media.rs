@@ -0,0 +1,114 @@
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+pub struct ImageSize {
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Guesses a MIME type from magic bytes, falling back to the file extension.
+pub fn sniff_mime(path: &Path, bytes: &[u8]) -> String {
+    let by_magic = if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some("image/png")
+    } else if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        Some("image/jpeg")
+    } else if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+        Some("image/gif")
+    } else if bytes.len() >= 12 && &bytes[0..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+        Some("image/webp")
+    } else if bytes.starts_with(b"BM") && bytes.len() >= 26 {
+        Some("image/bmp")
+    } else if bytes.starts_with(&[0, 0, 1, 0]) {
+        Some("image/x-icon")
+    } else if bytes.starts_with(b"%PDF-") {
+        Some("application/pdf")
+    } else if bytes.starts_with(b"PK\x03\x04") {
+        Some("application/zip")
+    } else if bytes.starts_with(&[0x1F, 0x8B]) {
+        Some("application/gzip")
+    } else if bytes.starts_with(b"\0asm") {
+        Some("application/wasm")
+    } else if bytes.starts_with(b"wOFF") {
+        Some("font/woff")
+    } else if bytes.starts_with(b"wOF2") {
+        Some("font/woff2")
+    } else {
+        None
+    };
+    if let Some(mime) = by_magic {
+        return mime.to_string();
+    }
+    let ext = path
+        .extension()
+        .and_then(|v| v.to_str())
+        .unwrap_or("")
+        .to_lowercase();
+    match ext.as_str() {
+        "svg" => "image/svg+xml",
+        "json" => "application/json",
+        "html" | "htm" => "text/html",
+        "css" => "text/css",
+        "js" | "mjs" => "text/javascript",
+        "md" => "text/markdown",
+        "ttf" => "font/ttf",
+        "otf" => "font/otf",
+        _ if crate::utils::is_binary_buffer(bytes) => "application/octet-stream",
+        _ => "text/plain",
+    }
+    .to_string()
+}
+
+/// Reads pixel dimensions from PNG, GIF, JPEG, BMP, WebP and ICO headers.
+pub fn image_size(bytes: &[u8]) -> Option<ImageSize> {
+    let be16 = |at: usize| Some(u16::from_be_bytes([*bytes.get(at)?, *bytes.get(at + 1)?]) as u32);
+    let le16 = |at: usize| Some(u16::from_le_bytes([*bytes.get(at)?, *bytes.get(at + 1)?]) as u32);
+    let be32 = |at: usize| Some(u32::from_be_bytes(bytes.get(at..at + 4)?.try_into().ok()?));
+    let le32 = |at: usize| Some(i32::from_le_bytes(bytes.get(at..at + 4)?.try_into().ok()?).unsigned_abs());
+    let size = |width: u32, height: u32| Some(ImageSize { width, height });
+
+    if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        return size(be32(16)?, be32(20)?);
+    }
+    if bytes.starts_with(b"GIF8") {
+        return size(le16(6)?, le16(8)?);
+    }
+    if bytes.starts_with(b"BM") {
+        return size(le32(18)?, le32(22)?);
+    }
+    if bytes.starts_with(&[0, 0, 1, 0]) {
+        let width = *bytes.get(6)? as u32;
+        let height = *bytes.get(7)? as u32;
+        return size(if width == 0 { 256 } else { width }, if height == 0 { 256 } else { height });
+    }
+    if bytes.len() >= 30 && &bytes[0..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+        return match &bytes[12..16] {
+            b"VP8 " => size(le16(26)? & 0x3FFF, le16(28)? & 0x3FFF),
+            b"VP8L" => {
+                let bits = u32::from_le_bytes(bytes.get(21..25)?.try_into().ok()?);
+                size((bits & 0x3FFF) + 1, ((bits >> 14) & 0x3FFF) + 1)
+            }
+            b"VP8X" => {
+                let width = u32::from_le_bytes([bytes[24], bytes[25], bytes[26], 0]) + 1;
+                let height = u32::from_le_bytes([bytes[27], bytes[28], bytes[29], 0]) + 1;
+                size(width, height)
+            }
+            _ => None,
+        };
+    }
+    if bytes.starts_with(&[0xFF, 0xD8]) {
+        let mut at = 2usize;
+        while at + 9 < bytes.len() {
+            if bytes[at] != 0xFF {
+                at += 1;
+                continue;
+            }
+            let marker = bytes[at + 1];
+            let is_frame = matches!(marker, 0xC0..=0xCF) && !matches!(marker, 0xC4 | 0xC8 | 0xCC);
+            if is_frame {
+                return size(be16(at + 7)?, be16(at + 5)?);
+            }
+            at += 2 + be16(at + 2)? as usize;
+        }
+    }
+    None
+}
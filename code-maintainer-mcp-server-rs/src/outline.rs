@@ -0,0 +1,607 @@
+use regex::Regex;
+use std::path::Path;
+use std::sync::OnceLock;
+
+pub const SUPPORTED_EXTENSIONS: &str = "rs, ts, tsx, mts, cts, js, jsx, mjs, cjs, py, pyi";
+
+/// Longest signature kept per symbol.
+const MAX_SIGNATURE_CHARS: usize = 200;
+
+/// A declaration with its 1-based, inclusive line range.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct Symbol {
+    pub name: String,
+    /// function, method, struct, enum, union, trait, impl, type, mod, macro,
+    /// const, static, class, interface or namespace.
+    pub kind: String,
+    /// Enclosing impl, trait, mod, class or namespace.
+    pub parent: Option<String>,
+    pub start_line: usize,
+    pub end_line: usize,
+    /// The declaration's first line.
+    pub signature: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    Rust,
+    TypeScript,
+    Python,
+}
+
+impl Language {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?.to_lowercase();
+        match ext.as_str() {
+            "rs" => Some(Self::Rust),
+            "ts" | "tsx" | "mts" | "cts" | "js" | "jsx" | "mjs" | "cjs" => Some(Self::TypeScript),
+            "py" | "pyi" => Some(Self::Python),
+            _ => None,
+        }
+    }
+}
+
+/// Lists the declarations in `text`. This is a line-based scan, not a full
+/// parser: it understands comments, strings and brace (or, for Python,
+/// indentation) nesting well enough to find top-level items and the members
+/// of impls, traits, modules and classes, but not items nested in bodies.
+pub fn outline(language: Language, text: &str) -> Vec<Symbol> {
+    match language {
+        Language::Python => python_outline(text),
+        _ => brace_outline(language, text),
+    }
+}
+
+struct Decl {
+    name: String,
+    kind: &'static str,
+    /// Whether members declared in its body are listed.
+    container: bool,
+}
+
+struct Open {
+    index: usize,
+    depth: i32,
+    container: bool,
+}
+
+/// A declaration whose body has not started yet.
+struct Pending {
+    index: usize,
+    container: bool,
+    parens: i32,
+    last_line: usize,
+}
+
+fn brace_outline(language: Language, text: &str) -> Vec<Symbol> {
+    let mut lexer = Lexer::new(language);
+    let mut symbols: Vec<Symbol> = Vec::new();
+    let mut stack: Vec<Open> = Vec::new();
+    let mut pending: Option<Pending> = None;
+    let mut depth = 0;
+    let mut last_line = 0;
+    for (n, raw) in text.lines().enumerate() {
+        let line_no = n + 1;
+        last_line = line_no;
+        let code = lexer.strip(raw);
+        let trimmed = code.trim();
+        if trimmed.is_empty() {
+            // A declaration without a body (e.g. an arrow function with no
+            // semicolon) ends at the first blank line.
+            if let Some(open) = pending.take_if(|p| p.parens <= 0) {
+                symbols[open.index].end_line = open.last_line;
+            }
+            continue;
+        }
+        let parent = match stack.last() {
+            None => Some(None),
+            Some(top) if top.container && depth == top.depth + 1 => Some(Some(top.index)),
+            Some(_) => None,
+        };
+        if let Some(parent) = parent {
+            let parent_kind = parent.map(|index| symbols[index].kind.as_str());
+            let decl = match language {
+                Language::Rust => rust_decl(trimmed, parent_kind),
+                _ => ts_decl(trimmed, parent_kind),
+            };
+            if let Some(decl) = decl {
+                if let Some(open) = pending.take() {
+                    symbols[open.index].end_line = open.last_line;
+                }
+                pending = Some(Pending {
+                    index: symbols.len(),
+                    container: decl.container,
+                    parens: 0,
+                    last_line: line_no,
+                });
+                symbols.push(Symbol {
+                    name: decl.name,
+                    kind: decl.kind.to_string(),
+                    parent: parent.map(|index| symbols[index].name.clone()),
+                    start_line: line_no,
+                    end_line: line_no,
+                    signature: signature(raw),
+                });
+            }
+        }
+        for ch in code.chars() {
+            match ch {
+                '(' | '[' => {
+                    if let Some(open) = pending.as_mut() {
+                        open.parens += 1;
+                    }
+                }
+                ')' | ']' => {
+                    if let Some(open) = pending.as_mut() {
+                        open.parens -= 1;
+                    }
+                }
+                '{' => {
+                    if let Some(open) = pending.take_if(|p| p.parens <= 0) {
+                        stack.push(Open {
+                            index: open.index,
+                            depth,
+                            container: open.container,
+                        });
+                    }
+                    depth += 1;
+                }
+                '}' => {
+                    depth -= 1;
+                    if stack.last().map(|top| top.depth == depth).unwrap_or(false) {
+                        if let Some(top) = stack.pop() {
+                            symbols[top.index].end_line = line_no;
+                        }
+                    }
+                }
+                ';' => {
+                    if let Some(open) = pending.take_if(|p| p.parens <= 0) {
+                        symbols[open.index].end_line = line_no;
+                    }
+                }
+                _ => {}
+            }
+        }
+        if let Some(open) = pending.as_mut() {
+            open.last_line = line_no;
+        }
+    }
+    for open in stack {
+        symbols[open.index].end_line = last_line;
+    }
+    if let Some(open) = pending {
+        symbols[open.index].end_line = open.last_line;
+    }
+    symbols
+}
+
+fn rust_decl(line: &str, parent_kind: Option<&str>) -> Option<Decl> {
+    static ITEM: OnceLock<Regex> = OnceLock::new();
+    static FN: OnceLock<Regex> = OnceLock::new();
+    static MACRO: OnceLock<Regex> = OnceLock::new();
+    let vis = r"^(?:pub(?:\s*\([^)]*\))?\s+)?";
+    let fn_re = FN.get_or_init(|| {
+        Regex::new(&format!(
+            r#"{vis}(?:default\s+)?(?:const\s+)?(?:async\s+)?(?:unsafe\s+)?(?:extern\s+(?:"[^"]*"\s+)?)?fn\s+([A-Za-z_]\w*)"#
+        ))
+        .expect("valid regex")
+    });
+    if let Some(caps) = fn_re.captures(line) {
+        let in_type = matches!(parent_kind, Some("impl") | Some("trait"));
+        return Some(Decl {
+            name: caps[1].to_string(),
+            kind: if in_type { "method" } else { "function" },
+            container: false,
+        });
+    }
+    if let Some(rest) = line
+        .strip_prefix("unsafe ")
+        .unwrap_or(line)
+        .strip_prefix("impl")
+        .filter(|rest| rest.starts_with(['<', ' ']))
+    {
+        return Some(Decl {
+            name: impl_target(rest),
+            kind: "impl",
+            container: true,
+        });
+    }
+    let item_re = ITEM.get_or_init(|| {
+        Regex::new(&format!(
+            r"{vis}(?:unsafe\s+)?(?:auto\s+)?(struct|enum|union|trait|type|mod|const|static)\s+(?:mut\s+)?([A-Za-z_]\w*)"
+        ))
+        .expect("valid regex")
+    });
+    if let Some(caps) = item_re.captures(line) {
+        let kind = match &caps[1] {
+            "struct" => "struct",
+            "enum" => "enum",
+            "union" => "union",
+            "trait" => "trait",
+            "type" => "type",
+            "mod" => "mod",
+            "const" => "const",
+            _ => "static",
+        };
+        return Some(Decl {
+            name: caps[2].to_string(),
+            kind,
+            container: matches!(kind, "trait" | "mod"),
+        });
+    }
+    let macro_re = MACRO.get_or_init(|| Regex::new(r"^macro_rules!\s*([A-Za-z_]\w*)").expect("valid regex"));
+    macro_re.captures(line).map(|caps| Decl {
+        name: caps[1].to_string(),
+        kind: "macro",
+        container: false,
+    })
+}
+
+/// The implementing type of an `impl` header: `<T> Display for Wrapper<T>`
+/// gives `Wrapper`.
+fn impl_target(rest: &str) -> String {
+    let mut rest = rest.trim_start();
+    if rest.starts_with('<') {
+        let mut depth = 0;
+        for (i, ch) in rest.char_indices() {
+            match ch {
+                '<' => depth += 1,
+                '>' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        rest = &rest[i + 1..];
+                        break;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    let header = rest.split(['{', ';']).next().unwrap_or("");
+    let header = header.split(" where ").next().unwrap_or(header);
+    let target = header.rsplit(" for ").next().unwrap_or(header).trim();
+    let target = target.trim_start_matches('&').trim_start_matches("dyn ").trim();
+    let name = target.split('<').next().unwrap_or(target).trim();
+    name.rsplit("::").next().unwrap_or(name).to_string()
+}
+
+fn ts_decl(line: &str, parent_kind: Option<&str>) -> Option<Decl> {
+    static ITEM: OnceLock<Regex> = OnceLock::new();
+    static VALUE: OnceLock<Regex> = OnceLock::new();
+    static MEMBER: OnceLock<Regex> = OnceLock::new();
+    const NOT_MEMBERS: [&str; 10] = ["if", "for", "while", "switch", "catch", "return", "function", "new", "super", "this"];
+
+    if parent_kind == Some("class") {
+        let member_re = MEMBER.get_or_init(|| {
+            Regex::new(
+                r"^(?:(?:public|private|protected|static|async|readonly|abstract|override|declare|get|set|accessor)\s+)*\*?\s*(#?[A-Za-z_$][\w$]*)\s*\??\s*(?:<[^>]*>\s*)?(\(|(?::[^=]+)?=\s*(?:async\s+)?(?:\(|[A-Za-z_$][\w$]*\s*=>|function\b))",
+            )
+            .expect("valid regex")
+        });
+        return member_re
+            .captures(line)
+            .filter(|caps| !NOT_MEMBERS.contains(&&caps[1]))
+            .map(|caps| Decl {
+                name: caps[1].to_string(),
+                kind: "method",
+                container: false,
+            });
+    }
+    let item_re = ITEM.get_or_init(|| {
+        Regex::new(
+            r"^(?:export\s+)?(?:default\s+)?(?:declare\s+)?(?:abstract\s+)?(?:async\s+)?(function\s*\*?|class|interface|type|(?:const\s+)?enum|namespace|module)\s*([A-Za-z_$][\w$.]*)",
+        )
+        .expect("valid regex")
+    });
+    if let Some(caps) = item_re.captures(line) {
+        let keyword = caps[1].trim_end_matches('*').trim();
+        let (kind, container) = match keyword {
+            "class" => ("class", true),
+            "interface" => ("interface", false),
+            "type" => ("type", false),
+            "namespace" | "module" => ("namespace", true),
+            k if k.ends_with("enum") => ("enum", false),
+            _ => ("function", false),
+        };
+        return Some(Decl {
+            name: caps[2].to_string(),
+            kind,
+            container,
+        });
+    }
+    let value_re = VALUE.get_or_init(|| {
+        Regex::new(
+            r"^(?:export\s+)?(?:const|let|var)\s+([A-Za-z_$][\w$]*)\s*(?::[^=]+)?=\s*(?:async\s+)?(function\b|[A-Za-z_$][\w$]*\s*=>|\(.*=>|\($|\([^)]*$|<)",
+        )
+        .expect("valid regex")
+    });
+    value_re.captures(line).map(|caps| Decl {
+        name: caps[1].to_string(),
+        kind: "function",
+        container: false,
+    })
+}
+
+fn python_outline(text: &str) -> Vec<Symbol> {
+    static DEF: OnceLock<Regex> = OnceLock::new();
+    let def_re = DEF.get_or_init(|| {
+        Regex::new(r"^(async\s+def|def|class)\s+([A-Za-z_]\w*)").expect("valid regex")
+    });
+    let mut symbols: Vec<Symbol> = Vec::new();
+    // (symbol index, indentation, is class)
+    let mut stack: Vec<(usize, usize, bool)> = Vec::new();
+    let mut in_string: Option<&str> = None;
+    let mut decorator_start: Option<usize> = None;
+    let mut last_code_line = 0;
+    for (n, line) in text.lines().enumerate() {
+        let line_no = n + 1;
+        if let Some(quote) = in_string {
+            if line.matches(quote).count() % 2 == 1 {
+                in_string = None;
+            }
+            last_code_line = line_no;
+            continue;
+        }
+        let trimmed = line.trim_start();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        let indent = line.len() - trimmed.len();
+        while let Some(&(index, open_indent, _)) = stack.last() {
+            if indent > open_indent {
+                break;
+            }
+            symbols[index].end_line = last_code_line;
+            stack.pop();
+        }
+        in_string = ["\"\"\"", "'''"]
+            .into_iter()
+            .find(|quote| trimmed.matches(quote).count() % 2 == 1);
+        last_code_line = line_no;
+        if trimmed.starts_with('@') {
+            decorator_start.get_or_insert(line_no);
+            continue;
+        }
+        let start_line = decorator_start.take().unwrap_or(line_no);
+        let Some(caps) = def_re.captures(trimmed) else {
+            continue;
+        };
+        let parent = stack.last().copied();
+        if parent.map(|(_, _, is_class)| !is_class).unwrap_or(false) {
+            // Nested in a function body; the function is still tracked so its
+            // end line is right.
+            continue;
+        }
+        let is_class = &caps[1] == "class";
+        let kind = match (is_class, parent) {
+            (true, _) => "class",
+            (false, Some(_)) => "method",
+            (false, None) => "function",
+        };
+        stack.push((symbols.len(), indent, is_class));
+        symbols.push(Symbol {
+            name: caps[2].to_string(),
+            kind: kind.to_string(),
+            parent: parent.map(|(index, _, _)| symbols[index].name.clone()),
+            start_line,
+            end_line: line_no,
+            signature: signature(line),
+        });
+    }
+    for (index, _, _) in stack {
+        symbols[index].end_line = last_code_line;
+    }
+    symbols
+}
+
+fn signature(line: &str) -> String {
+    let line = line.trim();
+    match line.char_indices().nth(MAX_SIGNATURE_CHARS) {
+        Some((end, _)) => format!("{}…", &line[..end]),
+        None => line.to_string(),
+    }
+}
+
+#[derive(Clone, Copy)]
+enum LexState {
+    Code,
+    BlockComment,
+    Str(char),
+    RawStr(usize),
+}
+
+/// Blanks out comments and string contents so braces and keywords inside
+/// them are not mistaken for code. State carries across lines.
+struct Lexer {
+    language: Language,
+    state: LexState,
+}
+
+impl Lexer {
+    fn new(language: Language) -> Self {
+        Self {
+            language,
+            state: LexState::Code,
+        }
+    }
+
+    fn strip(&mut self, line: &str) -> String {
+        let rust = self.language == Language::Rust;
+        let chars: Vec<char> = line.chars().collect();
+        let mut out = String::with_capacity(line.len());
+        let mut i = 0;
+        while i < chars.len() {
+            let ch = chars[i];
+            let next = chars.get(i + 1).copied();
+            match self.state {
+                LexState::BlockComment => {
+                    if ch == '*' && next == Some('/') {
+                        self.state = LexState::Code;
+                        i += 1;
+                    }
+                }
+                LexState::Str(quote) => {
+                    if ch == '\\' {
+                        out.push(' ');
+                        i += 1;
+                    } else if ch == quote {
+                        out.push(ch);
+                        self.state = LexState::Code;
+                    } else {
+                        out.push(' ');
+                    }
+                }
+                LexState::RawStr(hashes) => {
+                    if ch == '"' && chars[i + 1..].iter().take(hashes).filter(|c| **c == '#').count() == hashes {
+                        out.push(ch);
+                        self.state = LexState::Code;
+                        i += hashes;
+                    } else {
+                        out.push(' ');
+                    }
+                }
+                LexState::Code => {
+                    if ch == '/' && next == Some('/') {
+                        break;
+                    }
+                    if ch == '/' && next == Some('*') {
+                        self.state = LexState::BlockComment;
+                        i += 2;
+                        continue;
+                    }
+                    let after_ident = i > 0 && (chars[i - 1].is_alphanumeric() || chars[i - 1] == '_');
+                    if rust && ch == 'r' && !after_ident && matches!(next, Some('"') | Some('#')) {
+                        let hashes = chars[i + 1..].iter().take_while(|c| **c == '#').count();
+                        if chars.get(i + 1 + hashes) == Some(&'"') {
+                            out.push('"');
+                            self.state = LexState::RawStr(hashes);
+                            i += hashes + 2;
+                            continue;
+                        }
+                    }
+                    if rust && ch == '\'' {
+                        // A char literal ('x', '\n'); anything else is a lifetime.
+                        let len = if next == Some('\\') {
+                            chars[i + 2..].iter().position(|c| *c == '\'').map(|p| p + 3)
+                        } else if chars.get(i + 2) == Some(&'\'') {
+                            Some(3)
+                        } else {
+                            None
+                        };
+                        if let Some(len) = len {
+                            out.push_str("' '");
+                            i += len;
+                            continue;
+                        }
+                    }
+                    let is_quote = ch == '"' || (!rust && (ch == '\'' || ch == '`'));
+                    if is_quote {
+                        self.state = LexState::Str(ch);
+                    }
+                    out.push(ch);
+                }
+            }
+            i += 1;
+        }
+        // Only Rust strings and JS template literals span lines.
+        if let LexState::Str(quote) = self.state {
+            if !rust && quote != '`' {
+                self.state = LexState::Code;
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// (kind, name, parent, start_line, end_line) for each symbol.
+    fn summary(language: Language, text: &str) -> Vec<(String, String, Option<String>, usize, usize)> {
+        outline(language, text)
+            .into_iter()
+            .map(|symbol| (symbol.kind, symbol.name, symbol.parent, symbol.start_line, symbol.end_line))
+            .collect()
+    }
+
+    fn row(kind: &str, name: &str, parent: Option<&str>, start: usize, end: usize) -> (String, String, Option<String>, usize, usize) {
+        (kind.to_string(), name.to_string(), parent.map(str::to_string), start, end)
+    }
+
+    #[test]
+    fn detects_languages_from_extensions() {
+        assert_eq!(Language::from_path(Path::new("src/main.rs")), Some(Language::Rust));
+        assert_eq!(Language::from_path(Path::new("App.TSX")), Some(Language::TypeScript));
+        assert_eq!(Language::from_path(Path::new("tool.mjs")), Some(Language::TypeScript));
+        assert_eq!(Language::from_path(Path::new("stubs.pyi")), Some(Language::Python));
+        assert_eq!(Language::from_path(Path::new("README.md")), None);
+        assert_eq!(Language::from_path(Path::new("Makefile")), None);
+    }
+
+    #[test]
+    fn outlines_rust_items_and_members() {
+        let text = r#"use std::fmt;
+
+/// A point.
+pub struct Point {
+    x: i32,
+}
+
+impl fmt::Display for Point {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = "{ not a block";
+        write!(f, "{}", self.x)
+    }
+}
+
+pub(crate) async fn run() {
+    fn nested() {}
+}
+
+// fn commented_out() {}
+const LIMIT: usize = 3;
+"#;
+        assert_eq!(
+            summary(Language::Rust, text),
+            vec![
+                row("struct", "Point", None, 4, 6),
+                row("impl", "Point", None, 8, 13),
+                row("method", "fmt", Some("Point"), 9, 12),
+                row("function", "run", None, 15, 17),
+                row("const", "LIMIT", None, 20, 20),
+            ]
+        );
+        let symbols = outline(Language::Rust, text);
+        assert_eq!(symbols[3].signature, "pub(crate) async fn run() {");
+        assert_eq!(impl_target("<T: Clone> From<T> for crate::Wrapper<T> where T: Send {"), "Wrapper");
+    }
+
+    #[test]
+    fn outlines_typescript_classes_and_exports() {
+        let text = "export interface Props {\n  name: string;\n}\n\nexport default class Widget {\n  render(): string {\n    return `}`;\n  }\n}\n\nexport const load = async (id: string) => {\n  return id;\n};\n";
+        assert_eq!(
+            summary(Language::TypeScript, text),
+            vec![
+                row("interface", "Props", None, 1, 3),
+                row("class", "Widget", None, 5, 9),
+                row("method", "render", Some("Widget"), 6, 8),
+                row("function", "load", None, 11, 13),
+            ]
+        );
+    }
+
+    #[test]
+    fn outlines_python_by_indentation() {
+        let text = "import os\n\nclass Repo:\n    \"\"\"def not_a_method(): ...\"\"\"\n\n    def open(self):\n        def helper():\n            pass\n        return helper\n\n    async def close(self):\n        pass\n\ndef main():\n    Repo().open()\n";
+        assert_eq!(
+            summary(Language::Python, text),
+            vec![
+                row("class", "Repo", None, 3, 12),
+                row("method", "open", Some("Repo"), 6, 9),
+                row("method", "close", Some("Repo"), 11, 12),
+                row("function", "main", None, 14, 15),
+            ]
+        );
+    }
+}
@@ -1,54 +1,269 @@
-use crate::utils::ensure_path_inside_root;
+use crate::diff::{hunk_old_range, is_unified_diff, parse_unified_diff, PatchOp};
+use crate::fs_ops::FsOps;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 #[derive(Debug, Default, serde::Serialize)]
 pub struct ApplyPatchResult {
     pub updated: Vec<String>,
     pub added: Vec<String>,
     pub deleted: Vec<String>,
+    pub hunks: Vec<HunkMatch>,
+    /// Every file written or removed, in commit order, with its prior content.
+    #[serde(skip)]
+    pub changes: Vec<CommittedFile>,
 }
 
-enum PatchOp {
-    Update {
-        path: String,
-        move_to: Option<String>,
-        hunks: Vec<String>,
-    },
-    Add {
-        path: String,
-        lines: Vec<String>,
-    },
-    Delete {
-        path: String,
-    },
+#[derive(Debug, Clone)]
+pub struct CommittedFile {
+    pub path: String,
+    pub before: Option<Vec<u8>>,
+    pub after: Option<String>,
 }
 
-pub fn apply_patch(root: &Path, patch: &str, allow_writes: bool) -> Result<ApplyPatchResult, String> {
-    if !allow_writes {
-        return Err("Writes are disabled.".to_string());
+impl ApplyPatchResult {
+    /// Drops earlier entries for a path so each file is reported once, in its final state.
+    fn forget(&mut self, path: &str) {
+        self.updated.retain(|p| p != path);
+        self.added.retain(|p| p != path);
+        self.deleted.retain(|p| p != path);
     }
+}
+
+/// A file's final state after every patch operation has been applied in memory.
+/// `content: None` means the file is removed.
+pub struct StagedFile {
+    pub path: String,
+    pub target: PathBuf,
+    pub content: Option<String>,
+}
+
+#[derive(Default)]
+struct StagedPatch {
+    files: Vec<StagedFile>,
+    /// Directories deleted by the patch, removed once their files are gone.
+    dirs: Vec<PathBuf>,
+}
+
+impl StagedPatch {
+    fn current(&self, target: &Path) -> Result<Option<String>, String> {
+        if let Some(staged) = self.files.iter().find(|file| file.target == target) {
+            return Ok(staged.content.clone());
+        }
+        if target.is_file() {
+            fs::read_to_string(target)
+                .map(Some)
+                .map_err(|err| format!("{}: {err}", target.display()))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn stage(&mut self, path: &str, target: PathBuf, content: Option<String>) {
+        if let Some(staged) = self.files.iter_mut().find(|file| file.target == target) {
+            staged.content = content;
+        } else {
+            self.files.push(StagedFile {
+                path: path.to_string(),
+                target,
+                content,
+            });
+        }
+    }
+
+    /// Writes every staged file, restoring the original contents of all files
+    /// touched so far if any write fails.
+    fn commit(self) -> Result<Vec<CommittedFile>, String> {
+        let backups = commit_files(
+            self.files
+                .iter()
+                .map(|file| (file.path.as_str(), file.target.as_path(), file.content.as_deref().map(str::as_bytes))),
+        )?;
+        let mut removed: Vec<&PathBuf> = Vec::new();
+        for dir in &self.dirs {
+            if let Err(err) = fs::remove_dir(dir) {
+                for dir in removed.iter().rev() {
+                    let _ = fs::create_dir(dir);
+                }
+                let rolled_back = rollback(&backups);
+                return Err(format!("Failed to delete {}: {err}. {rolled_back}", dir.display()));
+            }
+            removed.push(dir);
+        }
+        Ok(self
+            .files
+            .into_iter()
+            .zip(backups)
+            .map(|(file, backup)| CommittedFile {
+                path: file.path,
+                before: backup.original,
+                after: file.content,
+            })
+            .collect())
+    }
+}
+
+/// A file to write (`Some`) or remove (`None`) as part of `write_files`.
+pub struct FileWrite {
+    pub path: String,
+    pub target: PathBuf,
+    pub content: Option<Vec<u8>>,
+}
+
+/// Writes or removes every file in order, the way a patch is committed: if
+/// any step fails, the files touched so far get their original content back.
+pub fn write_files(files: &[FileWrite]) -> Result<(), String> {
+    commit_files(
+        files
+            .iter()
+            .map(|file| (file.path.as_str(), file.target.as_path(), file.content.as_deref())),
+    )
+    .map(|_| ())
+}
+
+struct Backup {
+    target: PathBuf,
+    original: Option<Vec<u8>>,
+    created_dirs: Vec<PathBuf>,
+}
+
+/// Commits (path, target, content) entries in order and returns a backup per
+/// entry; on failure everything written so far is rolled back.
+fn commit_files<'a>(
+    files: impl Iterator<Item = (&'a str, &'a Path, Option<&'a [u8]>)>,
+) -> Result<Vec<Backup>, String> {
+    let mut backups: Vec<Backup> = Vec::new();
+    for (path, target, content) in files {
+        if let Err(err) = commit_file(target, content, &mut backups) {
+            let rolled_back = rollback(&backups);
+            return Err(format!("Failed to write {path}: {err}. {rolled_back}"));
+        }
+    }
+    Ok(backups)
+}
+
+fn commit_file(target: &Path, content: Option<&[u8]>, backups: &mut Vec<Backup>) -> Result<(), String> {
+    let original = if target.is_file() {
+        Some(fs::read(target).map_err(|err| err.to_string())?)
+    } else {
+        None
+    };
+    let mut backup = Backup {
+        target: target.to_path_buf(),
+        original,
+        created_dirs: Vec::new(),
+    };
+    let outcome = match content {
+        Some(content) => create_parent_dirs(target, &mut backup.created_dirs)
+            .and_then(|_| fs::write(target, content).map_err(|err| err.to_string())),
+        None if backup.original.is_some() => fs::remove_file(target).map_err(|err| err.to_string()),
+        None => Ok(()),
+    };
+    backups.push(backup);
+    outcome
+}
+
+fn create_parent_dirs(target: &Path, created: &mut Vec<PathBuf>) -> Result<(), String> {
+    let mut missing = Vec::new();
+    let mut current = target.parent();
+    while let Some(dir) = current {
+        if dir.exists() {
+            break;
+        }
+        missing.push(dir.to_path_buf());
+        current = dir.parent();
+    }
+    for dir in missing.into_iter().rev() {
+        fs::create_dir(&dir).map_err(|err| err.to_string())?;
+        created.push(dir);
+    }
+    Ok(())
+}
+
+/// Restores every backup, newest first, and describes the outcome for the
+/// error message: a failed restore leaves the workspace partly written.
+fn rollback(backups: &[Backup]) -> String {
+    let mut failures = Vec::new();
+    for backup in backups.iter().rev() {
+        let restored = match &backup.original {
+            Some(bytes) => fs::write(&backup.target, bytes),
+            None if backup.target.is_file() => fs::remove_file(&backup.target),
+            None => Ok(()),
+        };
+        if let Err(err) = restored {
+            failures.push(format!("{}: {err}", backup.target.display()));
+        }
+        for dir in backup.created_dirs.iter().rev() {
+            let _ = fs::remove_dir(dir);
+        }
+    }
+    if failures.is_empty() {
+        "All changes were rolled back.".to_string()
+    } else {
+        format!(
+            "Rollback failed for {}; the workspace is left partly written.",
+            failures.join(", ")
+        )
+    }
+}
+
+pub fn apply_patch(fs_ops: &FsOps, patch: &str) -> Result<ApplyPatchResult, String> {
+    let ops = parse_patch(patch)?;
+    let (staged, mut result) = stage_patch(fs_ops, ops)?;
+    result.changes = staged.commit()?;
+    Ok(result)
+}
+
+/// Parses and matches the patch exactly like `apply_patch`, returning the
+/// final content of every touched file without writing anything.
+pub fn preview_patch(
+    fs_ops: &FsOps,
+    patch: &str,
+) -> Result<(ApplyPatchResult, Vec<StagedFile>), String> {
     let ops = parse_patch(patch)?;
+    let (staged, result) = stage_patch(fs_ops, ops)?;
+    Ok((result, staged.files))
+}
+
+/// Every path goes through `FsOps` write checks, so writes-disabled, root
+/// confinement and the path policy apply exactly as for the other write tools.
+fn stage_patch(fs_ops: &FsOps, ops: Vec<PatchOp>) -> Result<(StagedPatch, ApplyPatchResult), String> {
+    let mut staged = StagedPatch::default();
     let mut result = ApplyPatchResult::default();
 
     for op in ops {
         match op {
             PatchOp::Add { path, lines } => {
-                let target = ensure_path_inside_root(root, Path::new(&path))?;
-                if let Some(parent) = target.parent() {
-                    fs::create_dir_all(parent).map_err(|err| err.to_string())?;
-                }
                 let content = lines.join("\n");
-                fs::write(&target, content).map_err(|err| err.to_string())?;
+                let target = fs_ops.check_write(&path, content.len())?;
+                if target.is_dir() {
+                    return Err(format!("Cannot add {path}: target is a directory."));
+                }
+                staged.stage(&path, target, Some(content));
+                result.forget(&path);
                 result.added.push(path);
             }
             PatchOp::Delete { path } => {
-                let target = ensure_path_inside_root(root, Path::new(&path))?;
+                let target = fs_ops.check_delete(&path)?;
                 if target.is_dir() {
-                    fs::remove_dir_all(&target).map_err(|err| err.to_string())?;
-                } else if target.exists() {
-                    fs::remove_file(&target).map_err(|err| err.to_string())?;
+                    let (files, dirs) = dir_contents(&target)?;
+                    for file in files {
+                        let rel = fs_ops.relative(&file);
+                        fs_ops.check_delete(&rel)?;
+                        staged.stage(&rel, file, None);
+                        result.forget(&rel);
+                    }
+                    for dir in dirs {
+                        if !staged.dirs.contains(&dir) {
+                            staged.dirs.push(dir);
+                        }
+                    }
+                    result.forget(&path);
+                    result.deleted.push(path);
+                    continue;
                 }
+                staged.stage(&path, target, None);
+                result.forget(&path);
                 result.deleted.push(path);
             }
             PatchOp::Update {
@@ -56,34 +271,64 @@ pub fn apply_patch(root: &Path, patch: &str, allow_writes: bool) -> Result<Apply
                 move_to,
                 hunks,
             } => {
-                let target = ensure_path_inside_root(root, Path::new(&path))?;
-                let original = if target.exists() {
-                    fs::read_to_string(&target).map_err(|err| err.to_string())?
-                } else {
-                    String::new()
-                };
+                let target = fs_ops.check_write(&path, 0)?;
+                let original = staged.current(&target)?.unwrap_or_default();
                 let (orig_lines, eol, ends_with_eol) = split_lines(&original);
-                let next_lines = apply_hunks(&orig_lines, &hunks)?;
+                let (next_lines, matches) = apply_hunks(&path, &orig_lines, &hunks)?;
                 let output = join_lines(&next_lines, &eol, ends_with_eol);
-                if let Some(parent) = target.parent() {
-                    fs::create_dir_all(parent).map_err(|err| err.to_string())?;
-                }
-                fs::write(&target, output).map_err(|err| err.to_string())?;
                 if let Some(move_to) = move_to {
-                    let moved = ensure_path_inside_root(root, Path::new(&move_to))?;
-                    if let Some(parent) = moved.parent() {
-                        fs::create_dir_all(parent).map_err(|err| err.to_string())?;
+                    let moved = fs_ops.check_write(&move_to, output.len())?;
+                    if moved != target {
+                        fs_ops.check_delete(&path)?;
+                        staged.stage(&path, target, None);
+                        result.forget(&path);
                     }
-                    fs::rename(&target, &moved).map_err(|err| err.to_string())?;
+                    result.forget(&move_to);
+                    staged.stage(&move_to, moved, Some(output));
+                    result.hunks.extend(matches.into_iter().map(|hunk| HunkMatch {
+                        path: move_to.clone(),
+                        ..hunk
+                    }));
                     result.updated.push(move_to);
                 } else {
+                    fs_ops.check_write(&path, output.len())?;
+                    staged.stage(&path, target, Some(output));
+                    result.forget(&path);
+                    result.hunks.extend(matches);
                     result.updated.push(path);
                 }
             }
         }
     }
 
-    Ok(result)
+    Ok((staged, result))
+}
+
+/// Files under `dir` and the directories to remove afterwards, deepest first.
+/// Symlinks are listed as files and not followed.
+fn dir_contents(dir: &Path) -> Result<(Vec<PathBuf>, Vec<PathBuf>), String> {
+    let mut files = Vec::new();
+    let mut dirs = vec![dir.to_path_buf()];
+    let mut index = 0;
+    while index < dirs.len() {
+        let mut entries = fs::read_dir(&dirs[index])
+            .map_err(|err| err.to_string())?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| err.to_string())?;
+        entries.sort();
+        for entry in entries {
+            let metadata = fs::symlink_metadata(&entry).map_err(|err| err.to_string())?;
+            if metadata.is_dir() {
+                dirs.push(entry);
+            } else {
+                files.push(entry);
+            }
+        }
+        index += 1;
+    }
+    dirs.reverse();
+    Ok((files, dirs))
 }
 
 fn parse_patch(input: &str) -> Result<Vec<PatchOp>, String> {
@@ -93,7 +338,10 @@ fn parse_patch(input: &str) -> Result<Vec<PatchOp>, String> {
     let mut ops: Vec<PatchOp> = Vec::new();
 
     if lines.get(i).map(|l| l.trim()).unwrap_or("") != "*** Begin Patch" {
-        return Err("Patch must start with \"*** Begin Patch\"".to_string());
+        if is_unified_diff(&text) {
+            return parse_unified_diff(&text).map(|files| files.into_iter().map(|(op, _)| op).collect());
+        }
+        return Err("Patch must start with \"*** Begin Patch\" or be a unified diff".to_string());
     }
     i += 1;
 
@@ -117,7 +365,7 @@ fn parse_patch(input: &str) -> Result<Vec<PatchOp>, String> {
                 }
             }
             let mut hunks: Vec<String> = Vec::new();
-            while i < lines.len() && !lines[i].starts_with("*** End Patch") {
+            while i < lines.len() && !is_section_end(lines[i]) {
                 hunks.push(lines[i].to_string());
                 i += 1;
             }
@@ -131,10 +379,10 @@ fn parse_patch(input: &str) -> Result<Vec<PatchOp>, String> {
             let path = require_line(&lines, i, "*** Add File: ")?;
             i += 1;
             let mut add_lines: Vec<String> = Vec::new();
-            while i < lines.len() && !lines[i].starts_with("*** End Patch") {
+            while i < lines.len() && !is_section_end(lines[i]) {
                 let raw = lines[i];
-                if raw.starts_with('+') {
-                    add_lines.push(raw[1..].to_string());
+                if let Some(added) = raw.strip_prefix('+') {
+                    add_lines.push(added.to_string());
                 }
                 i += 1;
             }
@@ -147,7 +395,7 @@ fn parse_patch(input: &str) -> Result<Vec<PatchOp>, String> {
         if line.starts_with("*** Delete File: ") {
             let path = require_line(&lines, i, "*** Delete File: ")?;
             i += 1;
-            while i < lines.len() && !lines[i].starts_with("*** End Patch") {
+            while i < lines.len() && !is_section_end(lines[i]) {
                 i += 1;
             }
             if i < lines.len() && lines[i].starts_with("*** End Patch") {
@@ -166,6 +414,13 @@ fn parse_patch(input: &str) -> Result<Vec<PatchOp>, String> {
     Ok(ops)
 }
 
+fn is_section_end(line: &str) -> bool {
+    line.starts_with("*** End Patch")
+        || line.starts_with("*** Update File: ")
+        || line.starts_with("*** Add File: ")
+        || line.starts_with("*** Delete File: ")
+}
+
 fn require_line(lines: &[&str], index: usize, prefix: &str) -> Result<String, String> {
     let line = lines.get(index).ok_or_else(|| {
         format!("Invalid patch format at line {}: expected {}", index + 1, prefix)
@@ -202,60 +457,150 @@ fn join_lines(lines: &[String], eol: &str, ends_with_eol: bool) -> String {
     }
 }
 
-fn apply_hunks(original: &[String], hunk_lines: &[String]) -> Result<Vec<String>, String> {
+/// How loosely a hunk's context had to be compared before it matched the file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FuzzLevel {
+    Exact,
+    TrailingWhitespace,
+    Indentation,
+    Unicode,
+}
+
+const FUZZ_LEVELS: [FuzzLevel; 4] = [
+    FuzzLevel::Exact,
+    FuzzLevel::TrailingWhitespace,
+    FuzzLevel::Indentation,
+    FuzzLevel::Unicode,
+];
+
+impl FuzzLevel {
+    fn lines_match(self, actual: &str, expected: &str) -> bool {
+        match self {
+            FuzzLevel::Exact => actual == expected,
+            FuzzLevel::TrailingWhitespace => actual.trim_end() == expected.trim_end(),
+            FuzzLevel::Indentation => actual.trim() == expected.trim(),
+            FuzzLevel::Unicode => normalize_punctuation(actual.trim()) == normalize_punctuation(expected.trim()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct HunkMatch {
+    pub path: String,
+    pub hunk: usize,
+    pub line: usize,
+    pub fuzz: FuzzLevel,
+}
+
+fn apply_hunks(
+    path: &str,
+    original: &[String],
+    hunk_lines: &[String],
+) -> Result<(Vec<String>, Vec<HunkMatch>), String> {
     let mut out: Vec<String> = Vec::new();
+    let mut matches: Vec<HunkMatch> = Vec::new();
     let mut pos: usize = 0;
     let hunks = split_hunks(hunk_lines);
 
-    for hunk in hunks {
-        let expected: Vec<String> = hunk
+    for (index, hunk) in hunks.into_iter().enumerate() {
+        let body = hunk_body(&hunk);
+        let expected: Vec<&str> = body
             .iter()
-            .filter(|line| line.starts_with(' ') || line.starts_with('-'))
-            .map(|line| line[1..].to_string())
+            .filter_map(|line| line.strip_prefix(' ').or_else(|| line.strip_prefix('-')))
             .collect();
 
-        let start_idx = if expected.is_empty() {
-            pos
+        let header = hunk.first().map(String::as_str).unwrap_or("");
+        // Unified diff headers carry the original line number; trust it when
+        // the context matches exactly there.
+        let hinted = hunk_old_range(header)
+            .map(|(start, count)| if count == 0 { start } else { start.saturating_sub(1) })
+            .filter(|&idx| idx >= pos && idx + expected.len() <= original.len())
+            .filter(|&idx| {
+                expected
+                    .iter()
+                    .enumerate()
+                    .all(|(j, line)| original[idx + j] == *line)
+            });
+        let anchor = hunk_anchor(header).and_then(|text| find_anchor(original, text, pos));
+        let (start_idx, fuzz) = if let Some(idx) = hinted {
+            (idx, FuzzLevel::Exact)
+        } else if expected.is_empty() {
+            (anchor.map(|idx| idx + 1).unwrap_or(pos), FuzzLevel::Exact)
         } else {
-            find_sequence(original, &expected, pos)?
+            anchor
+                .and_then(|idx| find_sequence(original, &expected, idx))
+                .or_else(|| find_sequence(original, &expected, pos))
+                .ok_or_else(|| {
+                    format!("Patch context not found in file {path} (hunk {}).", index + 1)
+                })?
         };
         out.extend_from_slice(&original[pos..start_idx]);
+        matches.push(HunkMatch {
+            path: path.to_string(),
+            hunk: index + 1,
+            line: start_idx + 1,
+            fuzz,
+        });
         let mut idx = start_idx;
 
-        for line in hunk {
-            if line.starts_with("@@") {
-                continue;
-            }
+        for line in body {
             if line.starts_with(' ') {
-                let content = &line[1..];
-                if original.get(idx).map(|l| l.as_str()) != Some(content) {
-                    return Err("Patch context mismatch.".to_string());
-                }
+                // Keep the file's own version of context lines when matching was fuzzy.
                 out.push(original[idx].clone());
                 idx += 1;
-                continue;
-            }
-            if line.starts_with('-') {
-                let content = &line[1..];
-                if original.get(idx).map(|l| l.as_str()) != Some(content) {
-                    return Err("Patch removal mismatch.".to_string());
-                }
+            } else if line.starts_with('-') {
                 idx += 1;
-                continue;
-            }
-            if line.starts_with('+') {
-                out.push(line[1..].to_string());
-                continue;
-            }
-            if line.starts_with('\\') {
-                continue;
+            } else if let Some(added) = line.strip_prefix('+') {
+                out.push(added.to_string());
             }
         }
         pos = idx;
     }
 
     out.extend_from_slice(&original[pos..]);
-    Ok(out)
+    Ok((out, matches))
+}
+
+/// Returns the hunk's change lines, treating bare empty lines between them as
+/// blank context (models often drop the leading space) and ignoring the
+/// `@@` header, `\ No newline` markers and trailing blank lines.
+fn hunk_body(hunk: &[String]) -> Vec<String> {
+    let mut body: Vec<String> = hunk
+        .iter()
+        .filter(|line| !line.starts_with("@@") && !line.starts_with('\\') && !line.starts_with("*** "))
+        .map(|line| if line.is_empty() { " ".to_string() } else { line.clone() })
+        .filter(|line| line.starts_with(' ') || line.starts_with('-') || line.starts_with('+'))
+        .collect();
+    while body.last().map(|line| line.trim().is_empty()).unwrap_or(false) {
+        body.pop();
+    }
+    body
+}
+
+/// Extracts the hint text of an `@@` header, e.g. `@@ fn main` or
+/// `@@ -10,4 +10,5 @@ impl Foo`.
+fn hunk_anchor(header: &str) -> Option<&str> {
+    let rest = header.strip_prefix("@@")?;
+    let text = match rest.split_once("@@") {
+        Some((_, after)) => after,
+        None => rest,
+    };
+    let text = text.trim();
+    if text.is_empty() {
+        None
+    } else {
+        Some(text)
+    }
+}
+
+fn find_anchor(haystack: &[String], anchor: &str, start: usize) -> Option<usize> {
+    let anchor = normalize_punctuation(anchor);
+    let candidates = || haystack.iter().enumerate().skip(start);
+    candidates()
+        .find(|(_, line)| normalize_punctuation(line.trim()) == anchor)
+        .or_else(|| candidates().find(|(_, line)| normalize_punctuation(line).contains(&anchor)))
+        .map(|(idx, _)| idx)
 }
 
 fn split_hunks(lines: &[String]) -> Vec<Vec<String>> {
@@ -278,24 +623,104 @@ fn split_hunks(lines: &[String]) -> Vec<Vec<String>> {
     hunks
 }
 
-fn find_sequence(haystack: &[String], needle: &[String], start: usize) -> Result<usize, String> {
+fn find_sequence(haystack: &[String], needle: &[&str], start: usize) -> Option<(usize, FuzzLevel)> {
     if needle.is_empty() {
-        return Ok(start);
+        return Some((start, FuzzLevel::Exact));
     }
-    if haystack.len() < needle.len() {
-        return Err("Patch context not found in file.".to_string());
+    if haystack.len() < needle.len() || start > haystack.len() - needle.len() {
+        return None;
     }
-    for i in start..=haystack.len() - needle.len() {
-        let mut matches = true;
-        for (j, expected) in needle.iter().enumerate() {
-            if haystack[i + j] != *expected {
-                matches = false;
-                break;
-            }
-        }
-        if matches {
-            return Ok(i);
+    FUZZ_LEVELS.iter().find_map(|level| {
+        (start..=haystack.len() - needle.len())
+            .find(|&i| {
+                needle
+                    .iter()
+                    .enumerate()
+                    .all(|(j, expected)| level.lines_match(&haystack[i + j], expected))
+            })
+            .map(|i| (i, *level))
+    })
+}
+
+fn normalize_punctuation(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '\u{2018}' | '\u{2019}' | '\u{201A}' | '\u{201B}' | '\u{2032}' => out.push('\''),
+            '\u{201C}' | '\u{201D}' | '\u{201E}' | '\u{201F}' | '\u{2033}' => out.push('"'),
+            '\u{2010}' | '\u{2011}' | '\u{2012}' | '\u{2013}' | '\u{2014}' | '\u{2015}' | '\u{2212}' => out.push('-'),
+            '\u{00A0}' | '\u{2002}' | '\u{2003}' | '\u{2009}' | '\u{202F}' | '\u{3000}' => out.push(' '),
+            '\u{2026}' => out.push_str("..."),
+            _ => out.push(ch),
         }
     }
-    Err("Patch context not found in file.".to_string())
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(text: &str) -> Vec<String> {
+        text.lines().map(str::to_string).collect()
+    }
+
+    fn apply(original: &str, hunk: &str) -> Result<(Vec<String>, Vec<HunkMatch>), String> {
+        apply_hunks("f.txt", &lines(original), &lines(hunk))
+    }
+
+    #[test]
+    fn exact_context_matches_first() {
+        let (out, matches) = apply("a\nb\nc\n", "@@\n a\n-b\n+B\n c\n").unwrap();
+        assert_eq!(out, lines("a\nB\nc"));
+        assert_eq!(matches[0].fuzz, FuzzLevel::Exact);
+        assert_eq!(matches[0].line, 1);
+    }
+
+    #[test]
+    fn fuzz_levels_are_tried_in_order() {
+        let (_, matches) = apply("fn a() {  \n}\n", "@@\n-fn a() {\n+fn b() {\n").unwrap();
+        assert_eq!(matches[0].fuzz, FuzzLevel::TrailingWhitespace);
+
+        let (out, matches) = apply("    let x = 1;\n", "@@\n let x = 1;\n+let y = 2;\n").unwrap();
+        assert_eq!(matches[0].fuzz, FuzzLevel::Indentation);
+        assert_eq!(out, lines("    let x = 1;\nlet y = 2;"));
+    }
+
+    #[test]
+    fn unicode_punctuation_is_normalized() {
+        let (out, matches) = apply("say \"hi\" - ok...\n", "@@\n-say \u{201C}hi\u{201D} \u{2014} ok\u{2026}\n+done\n").unwrap();
+        assert_eq!(matches[0].fuzz, FuzzLevel::Unicode);
+        assert_eq!(out, lines("done"));
+        assert_eq!(normalize_punctuation("wait\u{2026}"), "wait...");
+    }
+
+    #[test]
+    fn missing_context_is_an_error() {
+        let err = apply("a\nb\n", "@@\n-zzz\n+y\n").unwrap_err();
+        assert!(err.contains("hunk 1"), "{err}");
+    }
+
+    #[test]
+    fn anchor_selects_the_later_occurrence() {
+        let original = "fn a() {\n    x\n}\nfn b() {\n    x\n}\n";
+        let (out, matches) = apply(original, "@@ fn b() {\n-    x\n+    y\n").unwrap();
+        assert_eq!(matches[0].line, 5);
+        assert_eq!(out, lines("fn a() {\n    x\n}\nfn b() {\n    y\n}"));
+    }
+    #[test]
+    fn rollback_reports_files_it_could_not_restore() {
+        assert_eq!(rollback(&[]), "All changes were rolled back.");
+        let target = std::env::temp_dir()
+            .join(crate::utils::generate_id("rollback"))
+            .join("gone.txt");
+        let backups = [Backup {
+            target: target.clone(),
+            original: Some(b"old".to_vec()),
+            created_dirs: Vec::new(),
+        }];
+        let outcome = rollback(&backups);
+        assert!(outcome.starts_with(&format!("Rollback failed for {}: ", target.display())), "{outcome}");
+        assert!(outcome.ends_with("the workspace is left partly written."), "{outcome}");
+    }
 }
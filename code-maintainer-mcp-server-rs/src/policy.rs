@@ -0,0 +1,198 @@
+use globset::{GlobBuilder, GlobMatcher};
+use std::fs;
+use std::path::Path;
+
+/// Glob rules relative to the workspace root, as read from `--policy` or the
+/// `--deny` / `--readonly` / `--writable` flags.
+#[derive(Debug, Clone, Default, serde::Deserialize, serde::Serialize)]
+pub struct PolicyRules {
+    #[serde(default)]
+    pub deny: Vec<String>,
+    #[serde(default)]
+    pub readonly: Vec<String>,
+    #[serde(default)]
+    pub writable: Vec<String>,
+}
+
+impl PolicyRules {
+    /// Reads a JSON policy file: `{"deny": [...], "readonly": [...], "writable": [...]}`.
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        let raw = fs::read_to_string(path)
+            .map_err(|err| format!("Failed to read policy file {}: {err}", path.display()))?;
+        serde_json::from_str(&raw)
+            .map_err(|err| format!("Invalid policy file {}: {err}", path.display()))
+    }
+
+    pub fn extend(&mut self, other: PolicyRules) {
+        self.deny.extend(other.deny);
+        self.readonly.extend(other.readonly);
+        self.writable.extend(other.writable);
+    }
+}
+
+/// Read-only on top of any policy: a write into repository metadata (hooks,
+/// config) could make the next git command run arbitrary programs.
+const BUILTIN_READONLY: [&str; 1] = ["**/.git"];
+
+/// Access rules checked on every path `FsOps` touches. `deny` hides a path
+/// from reads, listings and searches; `readonly` blocks writes; when any
+/// `writable` rule exists, writes are only allowed on paths matching one.
+/// A rule matching a directory applies to everything below it. `.git`
+/// directories are always read-only.
+#[derive(Debug, Default)]
+pub struct PathPolicy {
+    rules: PolicyRules,
+    deny: Vec<(String, GlobMatcher)>,
+    readonly: Vec<(String, GlobMatcher)>,
+    writable: Vec<(String, GlobMatcher)>,
+}
+
+impl PathPolicy {
+    pub fn new(rules: PolicyRules) -> Result<Self, String> {
+        let mut readonly = compile(&rules.readonly)?;
+        readonly.extend(compile(&BUILTIN_READONLY.map(String::from))?);
+        Ok(Self {
+            deny: compile(&rules.deny)?,
+            readonly,
+            writable: compile(&rules.writable)?,
+            rules,
+        })
+    }
+
+    pub fn rules(&self) -> &PolicyRules {
+        &self.rules
+    }
+
+    /// Fails if `rel_path` (relative to the root, `/`-separated) is denied.
+    pub fn check_read(&self, rel_path: &str) -> Result<(), String> {
+        match first_match(&self.deny, rel_path) {
+            Some(rule) => Err(format!("Policy denies access to {rel_path} (deny rule {rule}).")),
+            None => Ok(()),
+        }
+    }
+
+    pub fn check_write(&self, rel_path: &str) -> Result<(), String> {
+        self.check_read(rel_path)?;
+        if let Some(rule) = first_match(&self.readonly, rel_path) {
+            return Err(format!("Policy makes {rel_path} read-only (readonly rule {rule})."));
+        }
+        if !self.writable.is_empty() && first_match(&self.writable, rel_path).is_none() {
+            return Err(format!(
+                "Policy does not allow writes to {rel_path} (writable rules: {}).",
+                self.rules.writable.join(", ")
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn is_denied(&self, rel_path: &str) -> bool {
+        first_match(&self.deny, rel_path).is_some()
+    }
+}
+
+fn compile(patterns: &[String]) -> Result<Vec<(String, GlobMatcher)>, String> {
+    patterns
+        .iter()
+        .map(|pattern| {
+            let glob = GlobBuilder::new(pattern)
+                .literal_separator(true)
+                .build()
+                .map_err(|err| format!("Invalid policy glob {pattern}: {err}"))?;
+            Ok((pattern.clone(), glob.compile_matcher()))
+        })
+        .collect()
+}
+
+/// The first rule matching `rel_path` or one of its parent directories.
+fn first_match<'a>(rules: &'a [(String, GlobMatcher)], rel_path: &str) -> Option<&'a str> {
+    if rel_path.is_empty() {
+        return None;
+    }
+    let mut candidate = Some(Path::new(rel_path));
+    while let Some(path) = candidate.filter(|p| !p.as_os_str().is_empty()) {
+        if let Some((rule, _)) = rules.iter().find(|(_, matcher)| matcher.is_match(path)) {
+            return Some(rule);
+        }
+        candidate = path.parent();
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(deny: &[&str], readonly: &[&str], writable: &[&str]) -> PathPolicy {
+        let list = |rules: &[&str]| rules.iter().map(|rule| rule.to_string()).collect();
+        PathPolicy::new(PolicyRules {
+            deny: list(deny),
+            readonly: list(readonly),
+            writable: list(writable),
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn deny_covers_everything_below_a_match() {
+        let policy = policy(&["secrets", "**/.env"], &[], &[]);
+        assert!(policy.is_denied("secrets"));
+        assert!(policy.is_denied("secrets/prod/key.pem"));
+        assert!(policy.is_denied(".env"));
+        assert!(policy.is_denied("app/.env"));
+        assert!(!policy.is_denied("app/.envrc"));
+        assert!(!policy.is_denied("src/secrets.rs"));
+        assert!(policy.check_read("secrets/a").unwrap_err().contains("deny rule secrets"));
+        assert!(policy.check_write("app/.env").is_err());
+    }
+
+    #[test]
+    fn globs_do_not_cross_directories() {
+        let policy = policy(&["*.key"], &[], &[]);
+        assert!(policy.is_denied("root.key"));
+        assert!(!policy.is_denied("nested/dir.key"));
+    }
+
+    #[test]
+    fn readonly_blocks_writes_but_not_reads() {
+        let policy = policy(&[], &["vendor/**", "Cargo.lock"], &[]);
+        assert!(policy.check_read("vendor/lib/mod.rs").is_ok());
+        let err = policy.check_write("vendor/lib/mod.rs").unwrap_err();
+        assert!(err.contains("read-only"), "{err}");
+        assert!(policy.check_write("Cargo.lock").is_err());
+        assert!(policy.check_write("src/main.rs").is_ok());
+    }
+
+    #[test]
+    fn git_metadata_is_always_read_only() {
+        let policy = policy(&[], &[], &[]);
+        assert!(policy.check_read(".git/config").is_ok());
+        assert!(policy.check_write(".git/hooks/pre-commit").is_err());
+        assert!(policy.check_write("vendor/lib/.git/config").is_err());
+        assert!(policy.check_write(".git").is_err());
+        assert!(policy.check_write(".gitignore").is_ok());
+        assert!(policy.check_write(".github/workflows/ci.yml").is_ok());
+    }
+
+    #[test]
+    fn writable_rules_allow_only_matching_paths() {
+        let policy = policy(&[], &["src/generated"], &["src", "docs/*.md"]);
+        assert!(policy.check_write("src/lib.rs").is_ok());
+        assert!(policy.check_write("docs/guide.md").is_ok());
+        assert!(policy.check_write("src/generated/out.rs").is_err());
+        let err = policy.check_write("README.md").unwrap_err();
+        assert!(err.contains("writable rules: src, docs/*.md"), "{err}");
+    }
+
+    #[test]
+    fn root_and_invalid_globs() {
+        let policy = policy(&["**"], &[], &[]);
+        assert!(policy.check_read("").is_ok());
+        assert!(policy.is_denied("anything"));
+        let err = PathPolicy::new(PolicyRules {
+            deny: vec!["src/[".to_string()],
+            ..PolicyRules::default()
+        })
+        .unwrap_err();
+        assert!(err.contains("Invalid policy glob src/["), "{err}");
+    }
+}
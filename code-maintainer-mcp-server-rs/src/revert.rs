@@ -0,0 +1,316 @@
+use crate::diff::snapshot_diff;
+use crate::fs_ops::FsOps;
+use crate::storage::{ChangeLogStore, ChangeRecord, PathEffect, Snapshot};
+use crate::utils::sha256_bytes;
+use std::fs;
+
+#[derive(Debug, serde::Serialize)]
+pub struct RevertOutcome {
+    pub path: String,
+    pub reverted: Vec<String>,
+    pub status: String,
+    pub reason: Option<String>,
+    pub change: Option<ChangeRecord>,
+}
+
+pub struct Reverter<'a> {
+    pub fs_ops: &'a FsOps,
+    pub change_log: &'a ChangeLogStore,
+    pub session_id: &'a str,
+    pub run_id: &'a str,
+}
+
+impl Reverter<'_> {
+    /// Restores the paths touched by `change_id` to their content before that
+    /// change. Every path is checked before any is written.
+    pub fn revert_change(&self, change_id: &str, force: bool) -> Result<Vec<RevertOutcome>, String> {
+        let record = self
+            .change_log
+            .get_change(change_id)?
+            .ok_or_else(|| format!("Change not found: {change_id}"))?;
+        if is_directory_move(&record) {
+            return self.revert_directory_move(&record).map(|outcome| vec![outcome]);
+        }
+        let mut planned = Vec::new();
+        for effect in record.effects() {
+            let before = self.change_log.load_snapshot(effect.before)?;
+            if before == Snapshot::Unknown {
+                return Err(format!(
+                    "Change {change_id} has no stored before-content for {} and cannot be reverted.",
+                    effect.path
+                ));
+            }
+            self.check_unchanged(effect.path, effect.after, force)?;
+            planned.push((effect.path.to_string(), before));
+        }
+        planned
+            .into_iter()
+            .map(|(path, before)| {
+                let change = self.restore(&path, &before)?;
+                Ok(RevertOutcome {
+                    path,
+                    reverted: vec![record.id.clone()],
+                    status: "reverted".to_string(),
+                    reason: None,
+                    change: Some(change),
+                })
+            })
+            .collect()
+    }
+
+    /// Restores every file touched in `session_id` to its state before the
+    /// session's first change. Files that cannot be restored are reported as skipped.
+    pub fn revert_session(&self, session_id: &str, force: bool) -> Result<Vec<RevertOutcome>, String> {
+        let changes = self.change_log.session_changes(session_id)?;
+        if changes.is_empty() {
+            return Err(format!("No changes recorded for session {session_id}"));
+        }
+        let mut paths: Vec<(String, Vec<PathEffect>)> = Vec::new();
+        for effect in changes.iter().flat_map(ChangeRecord::effects) {
+            match paths.iter_mut().find(|(path, _)| *path == effect.path) {
+                Some((_, items)) => items.push(effect),
+                None => paths.push((effect.path.to_string(), vec![effect])),
+            }
+        }
+
+        let mut outcomes = Vec::new();
+        for (path, items) in paths.into_iter().rev() {
+            let reverted: Vec<String> = items.iter().map(|effect| effect.record.id.clone()).collect();
+            let first = &items[0];
+            let last = &items[items.len() - 1];
+            let outcome = self
+                .change_log
+                .load_snapshot(first.before)
+                .and_then(|before| {
+                    // Both ends of a directory move: the destination's empty
+                    // "before" would otherwise delete the moved tree.
+                    if items.iter().any(|effect| is_directory_move(effect.record)) {
+                        return Err("directory move; undo it with revert_change".to_string());
+                    }
+                    if before == Snapshot::Unknown {
+                        return Err("no stored before-content".to_string());
+                    }
+                    self.check_unchanged(&path, last.after, force)?;
+                    self.restore(&path, &before)
+                });
+            outcomes.push(match outcome {
+                Ok(change) => RevertOutcome {
+                    path,
+                    reverted,
+                    status: "reverted".to_string(),
+                    reason: None,
+                    change: Some(change),
+                },
+                Err(reason) => RevertOutcome {
+                    path,
+                    reverted,
+                    status: "skipped".to_string(),
+                    reason: Some(reason),
+                    change: None,
+                },
+            });
+        }
+        Ok(outcomes)
+    }
+
+    /// Directories have no snapshots, so a directory move is undone by moving
+    /// it back. Fails if the old path has been taken since.
+    fn revert_directory_move(&self, record: &ChangeRecord) -> Result<RevertOutcome, String> {
+        let source = record
+            .source_path
+            .as_deref()
+            .ok_or_else(|| format!("Change {} has no source path and cannot be reverted.", record.id))?;
+        if !self.fs_ops.resolve_path(&record.path)?.is_dir() {
+            return Err(format!("{} is no longer a directory and cannot be moved back.", record.path));
+        }
+        self.fs_ops.move_path(&record.path, source, false)?;
+        let diff = Some(format!("rename from {}\nrename to {source}\n", record.path));
+        let change = self.change_log.log_transfer(
+            source,
+            &record.path,
+            "move",
+            0,
+            "",
+            self.session_id,
+            self.run_id,
+            diff,
+            &Snapshot::Missing,
+            &Snapshot::Unknown,
+        )?;
+        Ok(RevertOutcome {
+            path: source.to_string(),
+            reverted: vec![record.id.clone()],
+            status: "reverted".to_string(),
+            reason: None,
+            change: Some(change),
+        })
+    }
+
+    fn check_unchanged(&self, path: &str, expected: Option<&str>, force: bool) -> Result<(), String> {
+        if force {
+            return Ok(());
+        }
+        let target = self.fs_ops.resolve_path(path)?;
+        let Some(expected) = expected else {
+            // Directories have no snapshot; only remove ones that are still empty.
+            let has_entries = fs::read_dir(&target)
+                .map(|mut entries| entries.next().is_some())
+                .unwrap_or(false);
+            if has_entries {
+                return Err(format!("{path} is not empty; pass force to remove it."));
+            }
+            return Ok(());
+        };
+        let current = match self.fs_ops.snapshot(&target) {
+            Snapshot::Missing => String::new(),
+            Snapshot::Content(bytes) => sha256_bytes(&bytes),
+            Snapshot::Unknown => return Err(format!("{path} cannot be compared with the change log.")),
+        };
+        if current != expected {
+            return Err(format!(
+                "{path} was modified after the change was logged; pass force to overwrite it."
+            ));
+        }
+        Ok(())
+    }
+
+    fn restore(&self, path: &str, snapshot: &Snapshot) -> Result<ChangeRecord, String> {
+        let target = self.fs_ops.check_delete(path)?;
+        let current = self.fs_ops.snapshot(&target);
+        let (bytes, hash) = match snapshot {
+            Snapshot::Content(content) => {
+                let result = self.fs_ops.write_bytes(path, content)?;
+                (result.bytes, result.sha256)
+            }
+            _ => {
+                self.fs_ops.delete_path(path)?;
+                (0, String::new())
+            }
+        };
+        let diff = snapshot_diff(&current, snapshot);
+        self.change_log.log_change(
+            path,
+            "revert",
+            bytes,
+            &hash,
+            self.session_id,
+            self.run_id,
+            diff,
+            &current,
+            snapshot,
+        )
+    }
+}
+
+/// Directory moves are logged without snapshots, as a `move` with no after-content.
+fn is_directory_move(record: &ChangeRecord) -> bool {
+    record.action == "move" && record.after_sha256.is_none()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::Scratch;
+
+    fn text(content: &str) -> Snapshot {
+        Snapshot::Content(content.as_bytes().to_vec())
+    }
+
+    /// Writes `content` to `path` and logs it like the write tools do.
+    fn write(fs_ops: &FsOps, store: &ChangeLogStore, path: &str, content: &str) -> ChangeRecord {
+        let before = fs_ops.snapshot(&fs_ops.resolve_path(path).unwrap());
+        let result = fs_ops.write_file(path, content).unwrap();
+        store
+            .log_change(path, "write", result.bytes, &result.sha256, "s1", "", None, &before, &text(content))
+            .unwrap()
+    }
+
+    #[test]
+    fn revert_change_restores_the_before_content() {
+        let scratch = Scratch::new();
+        let fs_ops = scratch.fs_ops();
+        let store = ChangeLogStore::new(":memory:").unwrap();
+        let reverter = Reverter { fs_ops: &fs_ops, change_log: &store, session_id: "s2", run_id: "" };
+        write(&fs_ops, &store, "a.txt", "one\n");
+        let second = write(&fs_ops, &store, "a.txt", "two\n");
+        let created = write(&fs_ops, &store, "new.txt", "fresh\n");
+
+        let outcomes = reverter.revert_change(&second.id, false).unwrap();
+        assert_eq!(outcomes[0].status, "reverted");
+        assert_eq!(outcomes[0].reverted, vec![second.id.clone()]);
+        assert_eq!(fs::read_to_string(scratch.0.join("a.txt")).unwrap(), "one\n");
+        let logged = outcomes[0].change.as_ref().unwrap();
+        assert_eq!((logged.action.as_str(), logged.session_id.as_str()), ("revert", "s2"));
+
+        reverter.revert_change(&created.id, false).unwrap();
+        assert!(!scratch.0.join("new.txt").exists());
+    }
+
+    #[test]
+    fn revert_change_refuses_files_modified_since() {
+        let scratch = Scratch::new();
+        let fs_ops = scratch.fs_ops();
+        let store = ChangeLogStore::new(":memory:").unwrap();
+        let reverter = Reverter { fs_ops: &fs_ops, change_log: &store, session_id: "s1", run_id: "" };
+        let change = write(&fs_ops, &store, "a.txt", "one\n");
+        fs::write(scratch.0.join("a.txt"), "edited elsewhere\n").unwrap();
+
+        let err = reverter.revert_change(&change.id, false).unwrap_err();
+        assert!(err.contains("a.txt was modified after the change was logged"), "{err}");
+        assert_eq!(fs::read_to_string(scratch.0.join("a.txt")).unwrap(), "edited elsewhere\n");
+
+        reverter.revert_change(&change.id, true).unwrap();
+        assert!(!scratch.0.join("a.txt").exists());
+    }
+
+    #[test]
+    fn revert_change_needs_stored_before_content() {
+        let scratch = Scratch::new();
+        let fs_ops = scratch.fs_ops();
+        let store = ChangeLogStore::new(":memory:").unwrap();
+        let reverter = Reverter { fs_ops: &fs_ops, change_log: &store, session_id: "s1", run_id: "" };
+        fs_ops.write_file("big.bin", "x").unwrap();
+        let change = store
+            .log_change("big.bin", "write", 1, "", "s1", "", None, &Snapshot::Unknown, &text("x"))
+            .unwrap();
+        let err = reverter.revert_change(&change.id, false).unwrap_err();
+        assert!(err.contains("no stored before-content for big.bin"), "{err}");
+        assert!(reverter.revert_change("change_missing", false).unwrap_err().contains("Change not found"));
+    }
+
+    #[test]
+    fn directory_moves_are_moved_back() {
+        let scratch = Scratch::new();
+        let fs_ops = scratch.fs_ops();
+        let store = ChangeLogStore::new(":memory:").unwrap();
+        let reverter = Reverter { fs_ops: &fs_ops, change_log: &store, session_id: "s1", run_id: "" };
+        fs_ops.write_file("src/lib.rs", "pub fn a() {}\n").unwrap();
+        fs_ops.move_path("src", "lib", false).unwrap();
+        let change = store
+            .log_transfer("lib", "src", "move", 0, "", "s1", "", None, &Snapshot::Missing, &Snapshot::Unknown)
+            .unwrap();
+
+        for force in [false, true] {
+            let session = reverter.revert_session("s1", force).unwrap();
+            assert_eq!(session.len(), 2);
+            for outcome in session {
+                assert_eq!(outcome.status, "skipped");
+                assert!(outcome.reason.as_deref().unwrap().contains("revert_change"));
+            }
+        }
+        assert!(scratch.0.join("lib/lib.rs").is_file());
+
+        fs::create_dir(scratch.0.join("src")).unwrap();
+        fs::write(scratch.0.join("src/taken.rs"), "").unwrap();
+        assert!(reverter.revert_change(&change.id, false).is_err());
+        fs::remove_dir_all(scratch.0.join("src")).unwrap();
+
+        let outcomes = reverter.revert_change(&change.id, false).unwrap();
+        assert_eq!(outcomes[0].path, "src");
+        assert_eq!(outcomes[0].change.as_ref().unwrap().source_path.as_deref(), Some("lib"));
+        assert_eq!(fs::read_to_string(scratch.0.join("src/lib.rs")).unwrap(), "pub fn a() {}\n");
+        assert!(!scratch.0.join("lib").exists());
+        let err = reverter.revert_change(&change.id, false).unwrap_err();
+        assert!(err.contains("lib is no longer a directory"), "{err}");
+    }
+}
@@ -0,0 +1,285 @@
+use crate::fs_ops::{FsOps, SearchOutput, SearchQuery};
+use crate::utils::is_binary_buffer;
+use rusqlite::{params, Connection, OptionalExtension};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+/// Shortest literal the trigram tokenizer can match.
+const MIN_QUERY_CHARS: usize = 3;
+
+/// Full-text index of workspace files in the change-log database. Content
+/// goes into a contentless FTS5 trigram table, so it only narrows down which
+/// files `search_text` has to read; matching still happens on the files.
+pub struct SearchIndex {
+    conn: Connection,
+    root: String,
+}
+
+#[derive(Debug, Default, serde::Serialize)]
+pub struct RefreshStats {
+    pub files: usize,
+    pub updated: usize,
+    pub removed: usize,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct SearchIndexStats {
+    pub files: i64,
+    pub text_files: i64,
+    pub bytes: i64,
+    pub indexed_at: Option<String>,
+}
+
+impl SearchIndex {
+    pub fn open(db_path: &str, root: &Path) -> Result<Self, String> {
+        let conn = Connection::open(db_path).map_err(|err| err.to_string())?;
+        conn.busy_timeout(std::time::Duration::from_secs(5))
+            .map_err(|err| err.to_string())?;
+        conn.execute_batch(
+            r#"
+      CREATE TABLE IF NOT EXISTS search_files (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        root TEXT NOT NULL,
+        path TEXT NOT NULL,
+        mtime_ms INTEGER NOT NULL,
+        size INTEGER NOT NULL,
+        is_text INTEGER NOT NULL,
+        indexed_at TEXT NOT NULL,
+        UNIQUE (root, path)
+      );
+      CREATE VIRTUAL TABLE IF NOT EXISTS search_content USING fts5(
+        content,
+        content = '',
+        contentless_delete = 1,
+        tokenize = 'trigram'
+      );
+      "#,
+        )
+        .map_err(|err| format!("Failed to create search index: {err}"))?;
+        Ok(Self {
+            conn,
+            root: root.to_string_lossy().to_string(),
+        })
+    }
+
+    /// Re-indexes files whose mtime or size changed and forgets files that
+    /// are gone. Ignore files and the path policy apply; files over the read
+    /// limit are left out.
+    pub fn refresh(&self, fs_ops: &FsOps) -> Result<RefreshStats, String> {
+        let mut known: HashMap<String, (i64, i64)> = HashMap::new();
+        {
+            let mut stmt = self
+                .conn
+                .prepare("SELECT path, mtime_ms, size FROM search_files WHERE root = ?1")
+                .map_err(|err| err.to_string())?;
+            let rows = stmt
+                .query_map(params![self.root], |row| Ok((row.get(0)?, (row.get(1)?, row.get(2)?))))
+                .map_err(|err| err.to_string())?;
+            for row in rows {
+                let (path, stamp) = row.map_err(|err| err.to_string())?;
+                known.insert(path, stamp);
+            }
+        }
+        let mut stats = RefreshStats::default();
+        let tx = self.conn.unchecked_transaction().map_err(|err| err.to_string())?;
+        let root = fs_ops.resolve_path(".")?;
+        for entry in fs_ops.walk(&root, &[], &[], true)?.filter_map(|e| e.ok()) {
+            if !entry.file_type().map(|t| t.is_file()).unwrap_or(false) {
+                continue;
+            }
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            let (mtime_ms, size) = stamp(&metadata);
+            if size > fs_ops.max_file_bytes() {
+                continue;
+            }
+            let path = fs_ops.relative(entry.path());
+            stats.files += 1;
+            if known.remove(&path) == Some((mtime_ms, size)) {
+                continue;
+            }
+            self.index_file(&path, entry.path(), mtime_ms, size)?;
+            stats.updated += 1;
+        }
+        for path in known.keys() {
+            self.remove(path)?;
+            stats.removed += 1;
+        }
+        tx.commit().map_err(|err| err.to_string())?;
+        Ok(stats)
+    }
+
+    /// Drops everything indexed for this root and indexes it again.
+    pub fn rebuild(&self, fs_ops: &FsOps) -> Result<RefreshStats, String> {
+        let tx = self.conn.unchecked_transaction().map_err(|err| err.to_string())?;
+        self.conn
+            .execute(
+                "DELETE FROM search_content WHERE rowid IN (SELECT id FROM search_files WHERE root = ?1)",
+                params![self.root],
+            )
+            .map_err(|err| err.to_string())?;
+        self.conn
+            .execute("DELETE FROM search_files WHERE root = ?1", params![self.root])
+            .map_err(|err| err.to_string())?;
+        tx.commit().map_err(|err| err.to_string())?;
+        self.refresh(fs_ops)
+    }
+
+    /// Re-indexes a file or directory right after a tool changed it, and
+    /// forgets indexed files at or below `rel_path` that are gone.
+    pub fn update_path(&self, fs_ops: &FsOps, rel_path: &str) -> Result<(), String> {
+        let target = fs_ops.resolve_path(rel_path)?;
+        let path = fs_ops.relative(&target);
+        let tx = self.conn.unchecked_transaction().map_err(|err| err.to_string())?;
+        for indexed in self.indexed_below(&path)? {
+            self.remove(&indexed)?;
+        }
+        match fs::metadata(&target) {
+            Ok(metadata) if metadata.is_file() => self.update_file(fs_ops, &path, &target, &metadata)?,
+            Ok(metadata) if metadata.is_dir() => {
+                self.remove(&path)?;
+                for entry in fs_ops.walk(&target, &[], &[], true)?.filter_map(|e| e.ok()) {
+                    if !entry.file_type().map(|t| t.is_file()).unwrap_or(false) {
+                        continue;
+                    }
+                    let Ok(metadata) = entry.metadata() else {
+                        continue;
+                    };
+                    self.update_file(fs_ops, &fs_ops.relative(entry.path()), entry.path(), &metadata)?;
+                }
+            }
+            _ => self.remove(&path)?,
+        }
+        tx.commit().map_err(|err| err.to_string())
+    }
+
+    /// Runs `query` against the files the index says can contain it. Returns
+    /// `None` when the index cannot narrow the search: regular expressions,
+    /// literals shorter than three characters, and searches that include
+    /// ignored files.
+    pub fn search(&self, fs_ops: &FsOps, query: &SearchQuery) -> Result<Option<SearchOutput>, String> {
+        let literal = query.pattern.as_str();
+        if query.regex && regex::escape(literal) != literal {
+            return Ok(None);
+        }
+        if literal.chars().count() < MIN_QUERY_CHARS || !query.respect_ignore {
+            return Ok(None);
+        }
+        self.refresh(fs_ops)?;
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT f.path FROM search_content JOIN search_files f ON f.id = search_content.rowid WHERE search_content MATCH ?1 AND f.root = ?2",
+            )
+            .map_err(|err| err.to_string())?;
+        let phrase = format!("\"{}\"", literal.replace('"', "\"\""));
+        let candidates = stmt
+            .query_map(params![phrase, self.root], |row| row.get::<_, String>(0))
+            .map_err(|err| err.to_string())?
+            .collect::<Result<HashSet<_>, _>>()
+            .map_err(|err| err.to_string())?;
+        fs_ops.search_text_in(query, Some(&candidates)).map(Some)
+    }
+
+    pub fn stats(&self) -> Result<SearchIndexStats, String> {
+        self.conn
+            .query_row(
+                "SELECT COUNT(*), COALESCE(SUM(is_text), 0), COALESCE(SUM(size), 0), MAX(indexed_at) FROM search_files WHERE root = ?1",
+                params![self.root],
+                |row| {
+                    Ok(SearchIndexStats {
+                        files: row.get(0)?,
+                        text_files: row.get(1)?,
+                        bytes: row.get(2)?,
+                        indexed_at: row.get(3)?,
+                    })
+                },
+            )
+            .map_err(|err| err.to_string())
+    }
+
+    fn update_file(&self, fs_ops: &FsOps, path: &str, target: &Path, metadata: &fs::Metadata) -> Result<(), String> {
+        let (mtime_ms, size) = stamp(metadata);
+        if size > fs_ops.max_file_bytes() {
+            return self.remove(path);
+        }
+        self.index_file(path, target, mtime_ms, size)
+    }
+
+    /// Indexed paths strictly below the directory `path`.
+    fn indexed_below(&self, path: &str) -> Result<Vec<String>, String> {
+        let prefix = format!("{}/", path.trim_end_matches('/'));
+        let mut stmt = self
+            .conn
+            .prepare("SELECT path FROM search_files WHERE root = ?1 AND substr(path, 1, length(?2)) = ?2")
+            .map_err(|err| err.to_string())?;
+        let paths = stmt
+            .query_map(params![self.root, prefix], |row| row.get(0))
+            .map_err(|err| err.to_string())?
+            .collect::<Result<Vec<String>, _>>()
+            .map_err(|err| err.to_string());
+        paths
+    }
+
+    fn index_file(&self, path: &str, target: &Path, mtime_ms: i64, size: i64) -> Result<(), String> {
+        self.remove(path)?;
+        let buffer = fs::read(target).unwrap_or_default();
+        let is_text = !is_binary_buffer(&buffer);
+        self.conn
+            .execute(
+                "INSERT INTO search_files (root, path, mtime_ms, size, is_text, indexed_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    self.root,
+                    path,
+                    mtime_ms,
+                    size,
+                    is_text,
+                    chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true)
+                ],
+            )
+            .map_err(|err| err.to_string())?;
+        if is_text {
+            self.conn
+                .execute(
+                    "INSERT INTO search_content (rowid, content) VALUES (?1, ?2)",
+                    params![self.conn.last_insert_rowid(), String::from_utf8_lossy(&buffer)],
+                )
+                .map_err(|err| err.to_string())?;
+        }
+        Ok(())
+    }
+
+    fn remove(&self, path: &str) -> Result<(), String> {
+        let id: Option<i64> = self
+            .conn
+            .query_row(
+                "SELECT id FROM search_files WHERE root = ?1 AND path = ?2",
+                params![self.root, path],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|err| err.to_string())?;
+        if let Some(id) = id {
+            self.conn
+                .execute("DELETE FROM search_content WHERE rowid = ?1", params![id])
+                .map_err(|err| err.to_string())?;
+            self.conn
+                .execute("DELETE FROM search_files WHERE id = ?1", params![id])
+                .map_err(|err| err.to_string())?;
+        }
+        Ok(())
+    }
+}
+
+fn stamp(metadata: &fs::Metadata) -> (i64, i64) {
+    let mtime_ms = metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|duration| duration.as_millis() as i64)
+        .unwrap_or(0);
+    (mtime_ms, metadata.len() as i64)
+}
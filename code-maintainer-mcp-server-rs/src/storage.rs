@@ -1,9 +1,110 @@
-use crate::utils::{generate_id, now_iso};
-use rusqlite::{params, Connection, Row};
+use crate::utils::{generate_id, is_binary_buffer, normalize_path, now_iso, sha256_bytes};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use chrono::{Duration, Utc};
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
 use rusqlite::types::Value as SqlValue;
+use std::io::{Read, Write};
+use std::path::Path;
 
 pub struct ChangeLogStore {
     conn: Connection,
+    diff_codec: DiffCodec,
+}
+
+const CHANGE_COLUMNS: &str = "id, path, action, bytes, sha256, diff, diff_codec, diff_packed, session_id, run_id, created_at, before_sha256, after_sha256, source_path";
+const CHANGE_COLUMNS_NO_DIFF: &str = "id, path, action, bytes, sha256, session_id, run_id, created_at, before_sha256, after_sha256, source_path";
+
+/// Values for the (diff, diff_codec, diff_packed) columns.
+type DiffColumns<'d> = (Option<&'d str>, Option<&'static str>, Option<Vec<u8>>);
+
+/// How new diffs are stored. Compressed diffs go to `diff_packed` with the
+/// codec name in `diff_codec`; reads decode whatever each row was written with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DiffCodec {
+    #[default]
+    Plain,
+    Zstd,
+    Deflate,
+}
+
+impl DiffCodec {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value.trim().to_lowercase().as_str() {
+            "" | "none" | "plain" => Ok(Self::Plain),
+            "zstd" => Ok(Self::Zstd),
+            "deflate" => Ok(Self::Deflate),
+            other => Err(format!("Unknown diff codec {other} (expected none, zstd or deflate).")),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Plain => "none",
+            Self::Zstd => "zstd",
+            Self::Deflate => "deflate",
+        }
+    }
+
+    fn encode(&self, text: &str) -> Result<Vec<u8>, String> {
+        match self {
+            Self::Plain => Ok(text.as_bytes().to_vec()),
+            Self::Zstd => zstd::encode_all(text.as_bytes(), 0).map_err(|err| err.to_string()),
+            Self::Deflate => {
+                let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(text.as_bytes()).map_err(|err| err.to_string())?;
+                encoder.finish().map_err(|err| err.to_string())
+            }
+        }
+    }
+
+    fn decode(&self, packed: &[u8]) -> Result<String, String> {
+        let bytes = match self {
+            Self::Plain => packed.to_vec(),
+            Self::Zstd => zstd::decode_all(packed).map_err(|err| err.to_string())?,
+            Self::Deflate => {
+                let mut out = Vec::new();
+                DeflateDecoder::new(packed)
+                    .read_to_end(&mut out)
+                    .map_err(|err| err.to_string())?;
+                out
+            }
+        };
+        String::from_utf8(bytes).map_err(|err| err.to_string())
+    }
+}
+
+/// Limits applied by `ChangeLogStore::prune`; the oldest changes go first.
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct RetentionPolicy {
+    pub max_age_days: Option<i64>,
+    pub max_changes: Option<i64>,
+    pub max_db_bytes: Option<i64>,
+}
+
+impl RetentionPolicy {
+    pub fn is_empty(&self) -> bool {
+        self.max_age_days.is_none() && self.max_changes.is_none() && self.max_db_bytes.is_none()
+    }
+}
+
+#[derive(Debug, Default, serde::Serialize)]
+pub struct PruneReport {
+    pub changes_removed: usize,
+    pub blobs_removed: usize,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct StoreStats {
+    pub changes: i64,
+    pub blobs: i64,
+    pub blob_bytes: i64,
+    pub packed_diffs: i64,
+    pub plain_diffs: i64,
+    /// Size of the database file, including free pages VACUUM would reclaim.
+    pub db_bytes: i64,
+    pub free_bytes: i64,
 }
 
 #[derive(Debug, serde::Serialize)]
@@ -17,6 +118,70 @@ pub struct ChangeRecord {
     pub session_id: String,
     pub run_id: String,
     pub created_at: String,
+    pub before_sha256: Option<String>,
+    pub after_sha256: Option<String>,
+    /// Origin of a `move` or `copy`; `path` is the destination.
+    pub source_path: Option<String>,
+}
+
+/// File state captured around a change. Stored in `before_sha256` /
+/// `after_sha256` as NULL (unknown), "" (missing) or a `file_blobs` hash.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Snapshot {
+    /// Not captured: a directory, too large, or logged before snapshots existed.
+    Unknown,
+    /// The file did not exist.
+    Missing,
+    Content(Vec<u8>),
+}
+
+/// One path's before/after state within a logged change. A move touches two
+/// paths: the source (content to missing) and the destination.
+pub struct PathEffect<'r> {
+    pub path: &'r str,
+    pub before: Option<&'r str>,
+    pub after: Option<&'r str>,
+    pub record: &'r ChangeRecord,
+}
+
+impl ChangeRecord {
+    pub fn effects(&self) -> Vec<PathEffect<'_>> {
+        let mut effects = Vec::new();
+        if self.action == "move" {
+            if let Some(source) = &self.source_path {
+                effects.push(PathEffect {
+                    path: source,
+                    before: self.after_sha256.as_deref(),
+                    after: Some(""),
+                    record: self,
+                });
+            }
+        }
+        effects.push(PathEffect {
+            path: &self.path,
+            before: self.before_sha256.as_deref(),
+            after: self.after_sha256.as_deref(),
+            record: self,
+        });
+        effects
+    }
+}
+
+impl Snapshot {
+    pub fn is_binary(&self) -> bool {
+        matches!(self, Snapshot::Content(bytes) if is_binary_buffer(bytes))
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct CheckpointRecord {
+    pub id: String,
+    pub name: String,
+    pub session_id: String,
+    pub run_id: String,
+    pub created_at: String,
+    pub file_count: i64,
+    pub total_bytes: i64,
 }
 
 #[derive(Debug, Clone)]
@@ -56,16 +221,57 @@ impl ChangeLogStore {
       "#,
         )
         .map_err(|err| err.to_string())?;
-        if let Err(err) = conn.execute("ALTER TABLE file_changes ADD COLUMN diff TEXT", []) {
-            let message = err.to_string();
-            let is_duplicate = message.contains("duplicate column") || message.contains("already exists");
-            if !is_duplicate {
-                return Err(message);
-            }
-        }
-        Ok(Self { conn })
+        conn.execute_batch(
+            r#"
+      CREATE TABLE IF NOT EXISTS file_blobs (
+        sha256 TEXT PRIMARY KEY,
+        size INTEGER NOT NULL,
+        content BLOB NOT NULL,
+        created_at TEXT NOT NULL
+      );
+      "#,
+        )
+        .map_err(|err| err.to_string())?;
+        conn.execute_batch(
+            r#"
+      CREATE TABLE IF NOT EXISTS checkpoints (
+        id TEXT PRIMARY KEY,
+        name TEXT NOT NULL UNIQUE,
+        session_id TEXT NOT NULL,
+        run_id TEXT NOT NULL,
+        created_at TEXT NOT NULL,
+        file_count INTEGER NOT NULL,
+        total_bytes INTEGER NOT NULL
+      );
+      CREATE TABLE IF NOT EXISTS checkpoint_files (
+        checkpoint_id TEXT NOT NULL,
+        path TEXT NOT NULL,
+        sha256 TEXT NOT NULL,
+        PRIMARY KEY (checkpoint_id, path)
+      );
+      "#,
+        )
+        .map_err(|err| err.to_string())?;
+        add_column(&conn, "ALTER TABLE file_changes ADD COLUMN diff TEXT")?;
+        add_column(&conn, "ALTER TABLE file_changes ADD COLUMN before_sha256 TEXT")?;
+        add_column(&conn, "ALTER TABLE file_changes ADD COLUMN after_sha256 TEXT")?;
+        add_column(&conn, "ALTER TABLE file_changes ADD COLUMN source_path TEXT")?;
+        add_column(&conn, "ALTER TABLE file_changes ADD COLUMN diff_codec TEXT")?;
+        add_column(&conn, "ALTER TABLE file_changes ADD COLUMN diff_packed BLOB")?;
+        Ok(Self {
+            conn,
+            diff_codec: DiffCodec::Plain,
+        })
+    }
+
+    pub fn with_diff_codec(mut self, codec: DiffCodec) -> Self {
+        self.diff_codec = codec;
+        self
     }
 
+    /// Logs a change together with its before/after contents, so a change is
+    /// never recorded without the snapshots needed to revert it.
+    #[allow(clippy::too_many_arguments)]
     pub fn log_change(
         &self,
         path: &str,
@@ -75,10 +281,48 @@ impl ChangeLogStore {
         session_id: &str,
         run_id: &str,
         diff: Option<String>,
+        before: &Snapshot,
+        after: &Snapshot,
+    ) -> Result<ChangeRecord, String> {
+        self.insert_change(path, None, action, bytes, sha256, session_id, run_id, diff, before, after)
+    }
+
+    /// `log_change` for a move or copy. The source is written in the same row,
+    /// so a move is never logged as a plain create of its destination.
+    #[allow(clippy::too_many_arguments)]
+    pub fn log_transfer(
+        &self,
+        path: &str,
+        source_path: &str,
+        action: &str,
+        bytes: i64,
+        sha256: &str,
+        session_id: &str,
+        run_id: &str,
+        diff: Option<String>,
+        before: &Snapshot,
+        after: &Snapshot,
     ) -> Result<ChangeRecord, String> {
-        let record = ChangeRecord {
+        self.insert_change(path, Some(source_path), action, bytes, sha256, session_id, run_id, diff, before, after)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn insert_change(
+        &self,
+        path: &str,
+        source_path: Option<&str>,
+        action: &str,
+        bytes: i64,
+        sha256: &str,
+        session_id: &str,
+        run_id: &str,
+        diff: Option<String>,
+        before: &Snapshot,
+        after: &Snapshot,
+    ) -> Result<ChangeRecord, String> {
+        let mut record = ChangeRecord {
             id: generate_id("change"),
-            path: path.to_string(),
+            path: normalize_log_path(path),
             action: action.to_string(),
             bytes,
             sha256: sha256.to_string(),
@@ -86,26 +330,38 @@ impl ChangeLogStore {
             session_id: session_id.to_string(),
             run_id: run_id.to_string(),
             created_at: now_iso(),
+            before_sha256: None,
+            after_sha256: None,
+            source_path: source_path.map(normalize_log_path),
         };
-        self.conn
-            .execute(
-                r#"
-        INSERT INTO file_changes (id, path, action, bytes, sha256, diff, session_id, run_id, created_at)
-        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+        let (plain, codec, packed) = self.pack_diff(record.diff.as_deref())?;
+        let tx = self.conn.unchecked_transaction().map_err(|err| err.to_string())?;
+        record.before_sha256 = self.put_snapshot(before)?;
+        record.after_sha256 = self.put_snapshot(after)?;
+        tx.execute(
+            r#"
+        INSERT INTO file_changes (id, path, action, bytes, sha256, diff, diff_codec, diff_packed, session_id, run_id, created_at, before_sha256, after_sha256, source_path)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)
         "#,
-                params![
-                    record.id,
-                    record.path,
-                    record.action,
-                    record.bytes,
-                    record.sha256,
-                    record.diff,
-                    record.session_id,
-                    record.run_id,
-                    record.created_at
-                ],
-            )
-            .map_err(|err| err.to_string())?;
+            params![
+                record.id,
+                record.path,
+                record.action,
+                record.bytes,
+                record.sha256,
+                plain,
+                codec,
+                packed,
+                record.session_id,
+                record.run_id,
+                record.created_at,
+                record.before_sha256,
+                record.after_sha256,
+                record.source_path
+            ],
+        )
+        .map_err(|err| err.to_string())?;
+        tx.commit().map_err(|err| err.to_string())?;
         Ok(record)
     }
 
@@ -114,12 +370,18 @@ impl ChangeLogStore {
         let mut params: Vec<SqlValue> = Vec::new();
 
         if let Some(path) = query.path {
-            conditions.push("path = ?".to_string());
+            let path = normalize_log_path(&path);
+            conditions.push("(path = ? OR source_path = ?)".to_string());
+            params.push(SqlValue::from(path.clone()));
             params.push(SqlValue::from(path));
         }
         if let Some(prefix) = query.path_prefix {
+            let mut normalized = normalize_log_path(&prefix);
+            if prefix.ends_with('/') && !normalized.is_empty() && !normalized.ends_with('/') {
+                normalized.push('/');
+            }
             conditions.push("path LIKE ?".to_string());
-            params.push(SqlValue::from(format!("{}%", prefix)));
+            params.push(SqlValue::from(format!("{}%", normalized)));
         }
         if let Some(action) = query.action {
             conditions.push("action = ?".to_string());
@@ -140,9 +402,9 @@ impl ChangeLogStore {
             format!("WHERE {}", conditions.join(" AND "))
         };
         let select_columns = if include_diff {
-            "id, path, action, bytes, sha256, diff, session_id, run_id, created_at"
+            CHANGE_COLUMNS
         } else {
-            "id, path, action, bytes, sha256, session_id, run_id, created_at"
+            CHANGE_COLUMNS_NO_DIFF
         };
         let sql = format!(
             "SELECT {} FROM file_changes {} ORDER BY created_at DESC LIMIT ? OFFSET ?",
@@ -161,6 +423,372 @@ impl ChangeLogStore {
         }
         Ok(records)
     }
+
+    fn put_snapshot(&self, snapshot: &Snapshot) -> Result<Option<String>, String> {
+        match snapshot {
+            Snapshot::Unknown => Ok(None),
+            Snapshot::Missing => Ok(Some(String::new())),
+            Snapshot::Content(bytes) => self.put_blob(bytes).map(Some),
+        }
+    }
+
+    pub fn put_blob(&self, content: &[u8]) -> Result<String, String> {
+        let hash = sha256_bytes(content);
+        self.conn
+            .execute(
+                "INSERT OR IGNORE INTO file_blobs (sha256, size, content, created_at) VALUES (?1, ?2, ?3, ?4)",
+                params![hash, content.len() as i64, content, now_iso()],
+            )
+            .map_err(|err| err.to_string())?;
+        Ok(hash)
+    }
+
+    pub fn get_blob(&self, sha256: &str) -> Result<Option<Vec<u8>>, String> {
+        self.conn
+            .query_row(
+                "SELECT content FROM file_blobs WHERE sha256 = ?1",
+                params![sha256],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|err| err.to_string())
+    }
+
+    /// Resolves a `before_sha256` / `after_sha256` column value back to a snapshot.
+    pub fn load_snapshot(&self, sha256: Option<&str>) -> Result<Snapshot, String> {
+        match sha256 {
+            None => Ok(Snapshot::Unknown),
+            Some("") => Ok(Snapshot::Missing),
+            Some(hash) => Ok(self
+                .get_blob(hash)?
+                .map(Snapshot::Content)
+                .unwrap_or(Snapshot::Unknown)),
+        }
+    }
+
+    pub fn get_change(&self, id: &str) -> Result<Option<ChangeRecord>, String> {
+        let mut stmt = self
+            .conn
+            .prepare(&format!("SELECT {CHANGE_COLUMNS} FROM file_changes WHERE id = ?1"))
+            .map_err(|err| err.to_string())?;
+        let mut rows = stmt.query(params![id]).map_err(|err| err.to_string())?;
+        match rows.next().map_err(|err| err.to_string())? {
+            Some(row) => from_row(row, true).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// All changes of a session in the order they were made.
+    pub fn session_changes(&self, session_id: &str) -> Result<Vec<ChangeRecord>, String> {
+        self.ordered_changes(Some(session_id), None, false)
+    }
+
+    /// Changes matching the session and/or run filters, oldest first.
+    pub fn ordered_changes(
+        &self,
+        session_id: Option<&str>,
+        run_id: Option<&str>,
+        include_diff: bool,
+    ) -> Result<Vec<ChangeRecord>, String> {
+        let columns = if include_diff { CHANGE_COLUMNS } else { CHANGE_COLUMNS_NO_DIFF };
+        let mut stmt = self
+            .conn
+            .prepare(&format!(
+                "SELECT {columns} FROM file_changes WHERE (?1 IS NULL OR session_id = ?1) AND (?2 IS NULL OR run_id = ?2) ORDER BY created_at ASC, rowid ASC"
+            ))
+            .map_err(|err| err.to_string())?;
+        let mut rows = stmt.query(params![session_id, run_id]).map_err(|err| err.to_string())?;
+        let mut records = Vec::new();
+        while let Some(row) = rows.next().map_err(|err| err.to_string())? {
+            records.push(from_row(row, include_diff)?);
+        }
+        Ok(records)
+    }
+
+    /// Position of a change in the log as (created_at, rowid).
+    pub fn change_order(&self, id: &str) -> Result<Option<(String, i64)>, String> {
+        self.conn
+            .query_row(
+                "SELECT created_at, rowid FROM file_changes WHERE id = ?1",
+                params![id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()
+            .map_err(|err| err.to_string())
+    }
+
+    /// The last change that touched `path` (directly or as a move source) at
+    /// or before (created_at, rowid), or with `after` set, the first one after it.
+    pub fn change_near(
+        &self,
+        path: &str,
+        created_at: &str,
+        rowid: i64,
+        after: bool,
+    ) -> Result<Option<ChangeRecord>, String> {
+        let (cmp, order) = if after {
+            ("(created_at > ?2 OR (created_at = ?2 AND rowid > ?3))", "ASC")
+        } else {
+            ("(created_at < ?2 OR (created_at = ?2 AND rowid <= ?3))", "DESC")
+        };
+        let sql = format!(
+            "SELECT {CHANGE_COLUMNS} FROM file_changes WHERE (path = ?1 OR (source_path = ?1 AND action = 'move')) AND {cmp} ORDER BY created_at {order}, rowid {order} LIMIT 1"
+        );
+        let mut stmt = self.conn.prepare(&sql).map_err(|err| err.to_string())?;
+        let mut rows = stmt
+            .query(params![normalize_log_path(path), created_at, rowid])
+            .map_err(|err| err.to_string())?;
+        match rows.next().map_err(|err| err.to_string())? {
+            Some(row) => from_row(row, true).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn pack_diff<'d>(&self, diff: Option<&'d str>) -> Result<DiffColumns<'d>, String> {
+        match diff {
+            Some(text) if self.diff_codec != DiffCodec::Plain => Ok((
+                None,
+                Some(self.diff_codec.as_str()),
+                Some(self.diff_codec.encode(text)?),
+            )),
+            _ => Ok((diff, None, None)),
+        }
+    }
+
+    /// Rewrites stored diffs with the configured codec (or back to plain
+    /// text). Returns the number of rows rewritten.
+    pub fn recompress_diffs(&self) -> Result<usize, String> {
+        let target = self.diff_codec.as_str();
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT id, diff, diff_codec, diff_packed FROM file_changes WHERE (diff IS NOT NULL OR diff_packed IS NOT NULL) AND COALESCE(diff_codec, 'none') != ?1",
+            )
+            .map_err(|err| err.to_string())?;
+        let mut rows = stmt.query(params![target]).map_err(|err| err.to_string())?;
+        let mut pending = Vec::new();
+        while let Some(row) = rows.next().map_err(|err| err.to_string())? {
+            let id: String = row.get(0).map_err(|err| err.to_string())?;
+            pending.push((id, read_diff(row, 1)?));
+        }
+        drop(rows);
+        let tx = self.conn.unchecked_transaction().map_err(|err| err.to_string())?;
+        for (id, diff) in &pending {
+            let (plain, codec, packed) = self.pack_diff(diff.as_deref())?;
+            tx.execute(
+                "UPDATE file_changes SET diff = ?1, diff_codec = ?2, diff_packed = ?3 WHERE id = ?4",
+                params![plain, codec, packed, id],
+            )
+            .map_err(|err| err.to_string())?;
+        }
+        tx.commit().map_err(|err| err.to_string())?;
+        Ok(pending.len())
+    }
+
+    /// Deletes the oldest changes until every limit in `policy` holds, then
+    /// drops blobs no remaining change or checkpoint refers to. Size limits
+    /// count pages in use, so run `vacuum` afterwards to shrink the file.
+    pub fn prune(&self, policy: &RetentionPolicy) -> Result<PruneReport, String> {
+        let mut report = PruneReport::default();
+        if let Some(days) = policy.max_age_days {
+            let cutoff = (Utc::now() - Duration::days(days.max(0))).to_rfc3339();
+            report.changes_removed += self
+                .conn
+                .execute("DELETE FROM file_changes WHERE created_at < ?1", params![cutoff])
+                .map_err(|err| err.to_string())?;
+        }
+        if let Some(max) = policy.max_changes {
+            report.changes_removed += self
+                .conn
+                .execute(
+                    "DELETE FROM file_changes WHERE rowid IN (SELECT rowid FROM file_changes ORDER BY created_at DESC, rowid DESC LIMIT -1 OFFSET ?1)",
+                    params![max.max(0)],
+                )
+                .map_err(|err| err.to_string())?;
+        }
+        report.blobs_removed += self.drop_orphan_blobs()?;
+        if let Some(max_bytes) = policy.max_db_bytes {
+            while self.used_bytes()? > max_bytes {
+                let count: i64 = self
+                    .conn
+                    .query_row("SELECT COUNT(*) FROM file_changes", [], |row| row.get(0))
+                    .map_err(|err| err.to_string())?;
+                if count == 0 {
+                    break;
+                }
+                report.changes_removed += self
+                    .conn
+                    .execute(
+                        "DELETE FROM file_changes WHERE rowid IN (SELECT rowid FROM file_changes ORDER BY created_at ASC, rowid ASC LIMIT ?1)",
+                        params![(count / 10).max(1)],
+                    )
+                    .map_err(|err| err.to_string())?;
+                report.blobs_removed += self.drop_orphan_blobs()?;
+            }
+        }
+        Ok(report)
+    }
+
+    fn drop_orphan_blobs(&self) -> Result<usize, String> {
+        self.conn
+            .execute(
+                r#"
+        DELETE FROM file_blobs WHERE sha256 NOT IN (
+          SELECT before_sha256 FROM file_changes WHERE before_sha256 IS NOT NULL
+          UNION SELECT after_sha256 FROM file_changes WHERE after_sha256 IS NOT NULL
+          UNION SELECT sha256 FROM checkpoint_files
+        )
+        "#,
+                [],
+            )
+            .map_err(|err| err.to_string())
+    }
+
+    /// Rebuilds the database file to release free pages. Returns the file size
+    /// before and after.
+    pub fn vacuum(&self) -> Result<(i64, i64), String> {
+        let before = self.stats()?.db_bytes;
+        self.conn.execute_batch("VACUUM").map_err(|err| err.to_string())?;
+        Ok((before, self.stats()?.db_bytes))
+    }
+
+    pub fn stats(&self) -> Result<StoreStats, String> {
+        let pragma = |name: &str| -> Result<i64, String> {
+            self.conn
+                .query_row(&format!("PRAGMA {name}"), [], |row| row.get(0))
+                .map_err(|err| err.to_string())
+        };
+        let page_size = pragma("page_size")?;
+        let (changes, packed_diffs, plain_diffs) = self
+            .conn
+            .query_row(
+                "SELECT COUNT(*), COUNT(diff_packed), COUNT(diff) FROM file_changes",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .map_err(|err| err.to_string())?;
+        let (blobs, blob_bytes) = self
+            .conn
+            .query_row("SELECT COUNT(*), COALESCE(SUM(size), 0) FROM file_blobs", [], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .map_err(|err| err.to_string())?;
+        Ok(StoreStats {
+            changes,
+            blobs,
+            blob_bytes,
+            packed_diffs,
+            plain_diffs,
+            db_bytes: pragma("page_count")? * page_size,
+            free_bytes: pragma("freelist_count")? * page_size,
+        })
+    }
+
+    fn used_bytes(&self) -> Result<i64, String> {
+        let stats = self.stats()?;
+        Ok(stats.db_bytes - stats.free_bytes)
+    }
+
+    /// Records `files` (path, blob hash) under a new checkpoint. The contents
+    /// must already be in `file_blobs` (see `put_blob`), so unchanged files
+    /// cost nothing across checkpoints.
+    pub fn create_checkpoint(
+        &self,
+        name: &str,
+        session_id: &str,
+        run_id: &str,
+        files: &[(String, String)],
+        total_bytes: i64,
+    ) -> Result<CheckpointRecord, String> {
+        if self.get_checkpoint(name)?.is_some() {
+            return Err(format!("Checkpoint already exists: {name}"));
+        }
+        let record = CheckpointRecord {
+            id: generate_id("checkpoint"),
+            name: name.to_string(),
+            session_id: session_id.to_string(),
+            run_id: run_id.to_string(),
+            created_at: now_iso(),
+            file_count: files.len() as i64,
+            total_bytes,
+        };
+        let tx = self.conn.unchecked_transaction().map_err(|err| err.to_string())?;
+        tx.execute(
+            "INSERT INTO checkpoints (id, name, session_id, run_id, created_at, file_count, total_bytes) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                record.id,
+                record.name,
+                record.session_id,
+                record.run_id,
+                record.created_at,
+                record.file_count,
+                record.total_bytes
+            ],
+        )
+        .map_err(|err| err.to_string())?;
+        for (path, hash) in files {
+            tx.execute(
+                "INSERT INTO checkpoint_files (checkpoint_id, path, sha256) VALUES (?1, ?2, ?3)",
+                params![record.id, path, hash],
+            )
+            .map_err(|err| err.to_string())?;
+        }
+        tx.commit().map_err(|err| err.to_string())?;
+        Ok(record)
+    }
+
+    /// All checkpoints, newest first.
+    pub fn list_checkpoints(&self) -> Result<Vec<CheckpointRecord>, String> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT id, name, session_id, run_id, created_at, file_count, total_bytes FROM checkpoints ORDER BY created_at DESC, rowid DESC")
+            .map_err(|err| err.to_string())?;
+        let rows = stmt
+            .query_map([], checkpoint_from_row)
+            .map_err(|err| err.to_string())?;
+        rows.collect::<Result<Vec<_>, _>>().map_err(|err| err.to_string())
+    }
+
+    pub fn get_checkpoint(&self, name: &str) -> Result<Option<CheckpointRecord>, String> {
+        self.conn
+            .query_row(
+                "SELECT id, name, session_id, run_id, created_at, file_count, total_bytes FROM checkpoints WHERE name = ?1",
+                params![name],
+                checkpoint_from_row,
+            )
+            .optional()
+            .map_err(|err| err.to_string())
+    }
+
+    /// (path, sha256) of every file in a checkpoint, sorted by path.
+    pub fn checkpoint_files(&self, checkpoint_id: &str) -> Result<Vec<(String, String)>, String> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT path, sha256 FROM checkpoint_files WHERE checkpoint_id = ?1 ORDER BY path")
+            .map_err(|err| err.to_string())?;
+        let rows = stmt
+            .query_map(params![checkpoint_id], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(|err| err.to_string())?;
+        rows.collect::<Result<Vec<_>, _>>().map_err(|err| err.to_string())
+    }
+}
+
+/// The form change-log paths are stored and looked up in, so `./src//a.rs`
+/// and `src/a.rs` name the same file.
+pub fn normalize_log_path(path: &str) -> String {
+    normalize_path(Path::new(path)).to_string_lossy().into_owned()
+}
+
+fn checkpoint_from_row(row: &Row) -> rusqlite::Result<CheckpointRecord> {
+    Ok(CheckpointRecord {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        session_id: row.get(2)?,
+        run_id: row.get(3)?,
+        created_at: row.get(4)?,
+        file_count: row.get(5)?,
+        total_bytes: row.get(6)?,
+    })
 }
 
 fn from_row(row: &Row, include_diff: bool) -> Result<ChangeRecord, String> {
@@ -171,12 +799,85 @@ fn from_row(row: &Row, include_diff: bool) -> Result<ChangeRecord, String> {
         bytes: row.get("bytes").map_err(|err| err.to_string())?,
         sha256: row.get("sha256").map_err(|err| err.to_string())?,
         diff: if include_diff {
-            row.get("diff").map_err(|err| err.to_string())?
+            read_diff(row, "diff")?
         } else {
             None
         },
         session_id: row.get("session_id").map_err(|err| err.to_string())?,
         run_id: row.get("run_id").map_err(|err| err.to_string())?,
         created_at: row.get("created_at").map_err(|err| err.to_string())?,
+        before_sha256: row.get("before_sha256").map_err(|err| err.to_string())?,
+        after_sha256: row.get("after_sha256").map_err(|err| err.to_string())?,
+        source_path: row.get("source_path").map_err(|err| err.to_string())?,
     })
 }
+
+/// Reads a diff stored either as plain text or packed, given the index of
+/// the `diff` column (followed by `diff_codec` and `diff_packed`).
+fn read_diff<I: rusqlite::RowIndex + Copy>(row: &Row, diff: I) -> Result<Option<String>, String> {
+    if let Some(text) = row.get::<_, Option<String>>(diff).map_err(|err| err.to_string())? {
+        return Ok(Some(text));
+    }
+    let (codec, packed): (Option<String>, Option<Vec<u8>>) = match row.as_ref().column_index("diff_codec") {
+        Ok(index) => (
+            row.get(index).map_err(|err| err.to_string())?,
+            row.get(index + 1).map_err(|err| err.to_string())?,
+        ),
+        Err(_) => (None, None),
+    };
+    match (codec, packed) {
+        (Some(codec), Some(packed)) => DiffCodec::parse(&codec)?.decode(&packed).map(Some),
+        _ => Ok(None),
+    }
+}
+
+fn add_column(conn: &Connection, sql: &str) -> Result<(), String> {
+    if let Err(err) = conn.execute(sql, []) {
+        let message = err.to_string();
+        let is_duplicate = message.contains("duplicate column") || message.contains("already exists");
+        if !is_duplicate {
+            return Err(message);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CODECS: [DiffCodec; 3] = [DiffCodec::Plain, DiffCodec::Zstd, DiffCodec::Deflate];
+
+    #[test]
+    fn codecs_round_trip() {
+        let diff = "--- before\n+++ after\n@@ -1 +1 @@\n-old ünïcode\n+new\n".repeat(50);
+        for codec in CODECS {
+            let packed = codec.encode(&diff).unwrap();
+            assert_eq!(codec.decode(&packed).unwrap(), diff, "{}", codec.as_str());
+            assert_eq!(DiffCodec::parse(codec.as_str()).unwrap(), codec);
+        }
+        assert!(DiffCodec::Zstd.encode(&diff).unwrap().len() < diff.len());
+        assert!(DiffCodec::Deflate.decode(b"not deflate").is_err());
+        assert!(DiffCodec::parse("brotli").is_err());
+    }
+
+    #[test]
+    fn stored_diffs_survive_recompression() {
+        let mut store = ChangeLogStore::new(":memory:").unwrap().with_diff_codec(DiffCodec::Zstd);
+        let diff = "@@ -1 +1 @@\n-a\n+b\n".to_string();
+        let record = store
+            .log_change("./src//a.rs", "write", 2, "", "s", "r", Some(diff.clone()), &Snapshot::Missing, &Snapshot::Unknown)
+            .unwrap();
+        let stored = store.get_change(&record.id).unwrap().unwrap();
+        assert_eq!(stored.path, "src/a.rs");
+        assert_eq!(stored.diff.as_deref(), Some(diff.as_str()));
+
+        for codec in CODECS {
+            store.diff_codec = codec;
+            store.recompress_diffs().unwrap();
+            let stored = store.get_change(&record.id).unwrap().unwrap();
+            assert_eq!(stored.diff.as_deref(), Some(diff.as_str()), "{}", codec.as_str());
+            assert_eq!(store.recompress_diffs().unwrap(), 0);
+        }
+    }
+}
@@ -0,0 +1,210 @@
+use crate::diff::{line_stats, render_complete_diff};
+use crate::storage::{ChangeLogStore, ChangeRecord, PathEffect, Snapshot};
+use crate::utils::is_binary_buffer;
+
+#[derive(Debug, serde::Serialize)]
+pub struct FileSummary {
+    pub path: String,
+    /// "added", "modified", "deleted", or "unchanged" when later changes undid
+    /// earlier ones.
+    pub status: &'static str,
+    pub touches: usize,
+    pub actions: Vec<String>,
+    /// Net line counts; `None` for binary or unrecorded content.
+    pub lines_added: Option<usize>,
+    pub lines_removed: Option<usize>,
+    pub first_change_id: String,
+    pub last_change_id: String,
+}
+
+#[derive(Debug, Default, serde::Serialize)]
+pub struct SummaryTotals {
+    pub changes: usize,
+    pub files: usize,
+    pub added: usize,
+    pub modified: usize,
+    pub deleted: usize,
+    pub lines_added: usize,
+    pub lines_removed: usize,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct ChangeSummary {
+    pub totals: SummaryTotals,
+    pub files: Vec<FileSummary>,
+    /// Net diff of every file from its first before-state to its last
+    /// after-state, as a patch `git apply` accepts. Files without a text diff
+    /// are listed in a comment header instead.
+    pub diff: Option<String>,
+}
+
+/// Collapses the logged changes of a session and/or run into one net entry
+/// per file, comparing the state before the first touch with the state after
+/// the last one.
+pub fn summarize_changes(
+    store: &ChangeLogStore,
+    session_id: Option<&str>,
+    run_id: Option<&str>,
+    include_diff: bool,
+) -> Result<ChangeSummary, String> {
+    let changes = store.ordered_changes(session_id, run_id, false)?;
+    let mut paths: Vec<(&str, Vec<PathEffect>)> = Vec::new();
+    for effect in changes.iter().flat_map(ChangeRecord::effects) {
+        match paths.iter_mut().find(|(path, _)| *path == effect.path) {
+            Some((_, items)) => items.push(effect),
+            None => paths.push((effect.path, vec![effect])),
+        }
+    }
+    paths.sort_by(|a, b| a.0.cmp(b.0));
+
+    let mut totals = SummaryTotals {
+        changes: changes.len(),
+        ..SummaryTotals::default()
+    };
+    let mut files = Vec::new();
+    let mut combined = String::new();
+    let mut omitted = Vec::new();
+    for (path, items) in paths {
+        let first = &items[0];
+        let last = &items[items.len() - 1];
+        let before = store.load_snapshot(first.before)?;
+        let after = store.load_snapshot(last.after)?;
+        let status = match (&before, &after) {
+            (Snapshot::Missing, Snapshot::Missing) => "unchanged",
+            (Snapshot::Missing, _) => "added",
+            (_, Snapshot::Missing) => "deleted",
+            (Snapshot::Content(a), Snapshot::Content(b)) if a == b => "unchanged",
+            _ => "modified",
+        };
+        let (lines_added, lines_removed) = match (text_of(&before), text_of(&after)) {
+            (Some(a), Some(b)) => {
+                let (added, removed) = line_stats(&a, &b);
+                (Some(added), Some(removed))
+            }
+            _ => (None, None),
+        };
+        match status {
+            "added" => totals.added += 1,
+            "modified" => totals.modified += 1,
+            "deleted" => totals.deleted += 1,
+            _ => {}
+        }
+        if status != "unchanged" {
+            totals.files += 1;
+            totals.lines_added += lines_added.unwrap_or(0);
+            totals.lines_removed += lines_removed.unwrap_or(0);
+            if include_diff {
+                append_file_diff(&mut combined, &mut omitted, path, &before, &after);
+            }
+        }
+        let mut actions: Vec<String> = Vec::new();
+        for effect in &items {
+            if !actions.contains(&effect.record.action) {
+                actions.push(effect.record.action.clone());
+            }
+        }
+        files.push(FileSummary {
+            path: path.to_string(),
+            status,
+            touches: items.len(),
+            actions,
+            lines_added,
+            lines_removed,
+            first_change_id: first.record.id.clone(),
+            last_change_id: last.record.id.clone(),
+        });
+    }
+    if !omitted.is_empty() {
+        let header: String = omitted.iter().map(|entry| format!("#   {entry}\n")).collect();
+        combined = format!("# Not included in this patch:\n{header}{combined}");
+    }
+    Ok(ChangeSummary {
+        totals,
+        files,
+        diff: if include_diff && !combined.is_empty() {
+            Some(combined)
+        } else {
+            None
+        },
+    })
+}
+
+fn text_of(snapshot: &Snapshot) -> Option<String> {
+    match snapshot {
+        Snapshot::Missing => Some(String::new()),
+        Snapshot::Content(bytes) if !is_binary_buffer(bytes) => {
+            Some(String::from_utf8_lossy(bytes).to_string())
+        }
+        _ => None,
+    }
+}
+
+/// Appends one file's section in `git diff` layout so the combined diff can
+/// be read, or applied, as a single patch. A change that has no complete text
+/// diff (binary, not recorded, too large) goes to `omitted` with the reason,
+/// never as placeholder text inside a hunk.
+fn append_file_diff(out: &mut String, omitted: &mut Vec<String>, path: &str, before: &Snapshot, after: &Snapshot) {
+    let hunks = match (text_of(before), text_of(after)) {
+        (Some(a), Some(b)) if a == b => Ok(String::new()),
+        (Some(a), Some(b)) => render_complete_diff(&a, &b).ok_or("diff too large"),
+        _ if before.is_binary() || after.is_binary() => Err("binary file"),
+        _ => Err("content not recorded"),
+    };
+    let hunks = match hunks {
+        Ok(hunks) => hunks,
+        Err(reason) => {
+            omitted.push(format!("{path} ({reason})"));
+            return;
+        }
+    };
+    let old = if *before == Snapshot::Missing {
+        "/dev/null".to_string()
+    } else {
+        format!("a/{path}")
+    };
+    let new = if *after == Snapshot::Missing {
+        "/dev/null".to_string()
+    } else {
+        format!("b/{path}")
+    };
+    out.push_str(&format!("diff --git a/{path} b/{path}\n"));
+    if *before == Snapshot::Missing {
+        out.push_str("new file mode 100644\n");
+    } else if *after == Snapshot::Missing {
+        out.push_str("deleted file mode 100644\n");
+    }
+    if let Some(hunks) = hunks.strip_prefix("--- before\n+++ after\n") {
+        out.push_str(&format!("--- {old}\n+++ {new}\n{hunks}"));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(content: &str) -> Snapshot {
+        Snapshot::Content(content.as_bytes().to_vec())
+    }
+
+    #[test]
+    fn combined_diff_lists_files_without_a_text_diff_in_a_header() {
+        let store = ChangeLogStore::new(":memory:").unwrap();
+        let log = |path: &str, before: &Snapshot, after: &Snapshot| {
+            store.log_change(path, "write", 0, "", "s1", "", None, before, after).unwrap();
+        };
+        log("a.txt", &text("one\n"), &text("two\n"));
+        log("image.png", &Snapshot::Missing, &Snapshot::Content(b"\x89PNG\0data".to_vec()));
+        log("big.log", &text("x\n"), &Snapshot::Unknown);
+        log("empty.txt", &Snapshot::Missing, &text(""));
+
+        let diff = summarize_changes(&store, Some("s1"), None, true).unwrap().diff.unwrap();
+        assert!(
+            diff.starts_with("# Not included in this patch:\n#   big.log (content not recorded)\n#   image.png (binary file)\n"),
+            "{diff}"
+        );
+        assert!(diff.contains("diff --git a/a.txt b/a.txt\n--- a/a.txt\n+++ b/a.txt\n@@ -1,1 +1,1 @@\n-one\n+two\n"));
+        assert!(diff.contains("diff --git a/empty.txt b/empty.txt\nnew file mode 100644\n"));
+        assert!(!diff.contains("diff --git a/image.png"));
+        assert!(!diff.contains("omitted") && !diff.contains("binary file changed"));
+    }
+}
@@ -0,0 +1,199 @@
+use crate::fs_ops::FsOps;
+use crate::outline::{outline, Language, Symbol};
+use rusqlite::{params, Connection};
+use std::collections::HashMap;
+use std::fs;
+use std::time::UNIX_EPOCH;
+
+/// Declarations of every supported source file under a workspace root,
+/// kept in the change-log database and refreshed from file mtimes.
+pub struct SymbolIndex {
+    conn: Connection,
+    root: String,
+}
+
+#[derive(Debug, Default, serde::Serialize)]
+pub struct RefreshStats {
+    pub files: usize,
+    pub updated: usize,
+    pub removed: usize,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct IndexedSymbol {
+    pub path: String,
+    #[serde(flatten)]
+    pub symbol: Symbol,
+}
+
+impl SymbolIndex {
+    pub fn open(db_path: &str, fs_ops: &FsOps) -> Result<Self, String> {
+        let conn = Connection::open(db_path).map_err(|err| err.to_string())?;
+        conn.busy_timeout(std::time::Duration::from_secs(5))
+            .map_err(|err| err.to_string())?;
+        conn.execute_batch(
+            r#"
+      CREATE TABLE IF NOT EXISTS symbol_files (
+        root TEXT NOT NULL,
+        path TEXT NOT NULL,
+        mtime_ms INTEGER NOT NULL,
+        size INTEGER NOT NULL,
+        PRIMARY KEY (root, path)
+      );
+      CREATE TABLE IF NOT EXISTS symbols (
+        root TEXT NOT NULL,
+        path TEXT NOT NULL,
+        name TEXT NOT NULL,
+        kind TEXT NOT NULL,
+        parent TEXT,
+        start_line INTEGER NOT NULL,
+        end_line INTEGER NOT NULL,
+        signature TEXT NOT NULL
+      );
+      CREATE INDEX IF NOT EXISTS symbols_name_idx ON symbols(root, name);
+      CREATE INDEX IF NOT EXISTS symbols_path_idx ON symbols(root, path);
+      "#,
+        )
+        .map_err(|err| err.to_string())?;
+        Ok(Self {
+            conn,
+            root: fs_ops.root().to_string_lossy().to_string(),
+        })
+    }
+
+    /// Re-parses files whose mtime or size changed since they were indexed
+    /// and forgets files that are gone. Ignore files and the path policy
+    /// apply; files over the read limit are skipped.
+    pub fn refresh(&self, fs_ops: &FsOps) -> Result<RefreshStats, String> {
+        let mut known: HashMap<String, (i64, i64)> = HashMap::new();
+        {
+            let mut stmt = self
+                .conn
+                .prepare("SELECT path, mtime_ms, size FROM symbol_files WHERE root = ?1")
+                .map_err(|err| err.to_string())?;
+            let rows = stmt
+                .query_map(params![self.root], |row| Ok((row.get(0)?, (row.get(1)?, row.get(2)?))))
+                .map_err(|err| err.to_string())?;
+            for row in rows {
+                let (path, stamp) = row.map_err(|err| err.to_string())?;
+                known.insert(path, stamp);
+            }
+        }
+        let mut stats = RefreshStats::default();
+        let tx = self.conn.unchecked_transaction().map_err(|err| err.to_string())?;
+        let root = fs_ops.resolve_path(".")?;
+        for entry in fs_ops.walk(&root, &[], &[], true)?.filter_map(|e| e.ok()) {
+            if !entry.file_type().map(|t| t.is_file()).unwrap_or(false) {
+                continue;
+            }
+            let Some(language) = Language::from_path(entry.path()) else {
+                continue;
+            };
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            let size = metadata.len() as i64;
+            if size > fs_ops.max_file_bytes() {
+                continue;
+            }
+            let path = fs_ops.relative(entry.path()).replace('\\', "/");
+            let mtime_ms = metadata
+                .modified()
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map(|duration| duration.as_millis() as i64)
+                .unwrap_or(0);
+            stats.files += 1;
+            if known.remove(&path) == Some((mtime_ms, size)) {
+                continue;
+            }
+            let Ok(bytes) = fs::read(entry.path()) else {
+                continue;
+            };
+            let text = String::from_utf8_lossy(&bytes);
+            tx.execute("DELETE FROM symbols WHERE root = ?1 AND path = ?2", params![self.root, path])
+                .map_err(|err| err.to_string())?;
+            for symbol in outline(language, &text) {
+                tx.execute(
+                    "INSERT INTO symbols (root, path, name, kind, parent, start_line, end_line, signature) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                    params![
+                        self.root,
+                        path,
+                        symbol.name,
+                        symbol.kind,
+                        symbol.parent,
+                        symbol.start_line as i64,
+                        symbol.end_line as i64,
+                        symbol.signature
+                    ],
+                )
+                .map_err(|err| err.to_string())?;
+            }
+            tx.execute(
+                "INSERT OR REPLACE INTO symbol_files (root, path, mtime_ms, size) VALUES (?1, ?2, ?3, ?4)",
+                params![self.root, path, mtime_ms, size],
+            )
+            .map_err(|err| err.to_string())?;
+            stats.updated += 1;
+        }
+        for path in known.keys() {
+            tx.execute("DELETE FROM symbols WHERE root = ?1 AND path = ?2", params![self.root, path])
+                .map_err(|err| err.to_string())?;
+            tx.execute("DELETE FROM symbol_files WHERE root = ?1 AND path = ?2", params![self.root, path])
+                .map_err(|err| err.to_string())?;
+            stats.removed += 1;
+        }
+        tx.commit().map_err(|err| err.to_string())?;
+        Ok(stats)
+    }
+
+    /// Symbols whose name contains `query` (case-insensitive), exact matches
+    /// first, then prefix matches. `Parent::name` or `Parent.name` also
+    /// filters on the enclosing type.
+    pub fn find(&self, query: &str, kind: Option<&str>, limit: usize) -> Result<Vec<IndexedSymbol>, String> {
+        let query = query.trim();
+        let (parent, name) = match query.rsplit_once("::").or_else(|| query.rsplit_once('.')) {
+            Some((parent, name)) if !parent.is_empty() && !name.is_empty() => (Some(parent), name),
+            _ => (None, query),
+        };
+        if name.is_empty() {
+            return Err("name is required".to_string());
+        }
+        let pattern = format!("%{}%", escape_like(name));
+        let prefix = format!("{}%", escape_like(name));
+        let mut stmt = self
+            .conn
+            .prepare(
+                r#"
+        SELECT path, name, kind, parent, start_line, end_line, signature FROM symbols
+        WHERE root = ?1 AND name LIKE ?2 ESCAPE '\' AND (?3 IS NULL OR parent = ?3) AND (?4 IS NULL OR kind = ?4)
+        ORDER BY name = ?5 DESC, name LIKE ?6 ESCAPE '\' DESC, length(name), path, start_line
+        LIMIT ?7
+        "#,
+            )
+            .map_err(|err| err.to_string())?;
+        let rows = stmt
+            .query_map(
+                params![self.root, pattern, parent, kind, name, prefix, limit as i64],
+                |row| {
+                    Ok(IndexedSymbol {
+                        path: row.get(0)?,
+                        symbol: Symbol {
+                            name: row.get(1)?,
+                            kind: row.get(2)?,
+                            parent: row.get(3)?,
+                            start_line: row.get::<_, i64>(4)? as usize,
+                            end_line: row.get::<_, i64>(5)? as usize,
+                            signature: row.get(6)?,
+                        },
+                    })
+                },
+            )
+            .map_err(|err| err.to_string())?;
+        rows.collect::<Result<Vec<_>, _>>().map_err(|err| err.to_string())
+    }
+}
+
+fn escape_like(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+}
@@ -0,0 +1,290 @@
+use serde_json::Value;
+
+const BOM: char = '\u{feff}';
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LineEnding {
+    Lf,
+    Crlf,
+    /// Both styles occur; content is left untouched rather than rewriting
+    /// lines the caller did not ask to change.
+    Mixed,
+}
+
+impl LineEnding {
+    fn as_str(self) -> &'static str {
+        match self {
+            LineEnding::Crlf => "\r\n",
+            _ => "\n",
+        }
+    }
+}
+
+/// Line ending, BOM and trailing-newline conventions of a text file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+pub struct TextStyle {
+    pub line_ending: Option<LineEnding>,
+    pub bom: bool,
+    /// Only known once the file has at least one line break.
+    pub trailing_newline: Option<bool>,
+}
+
+/// Caller overrides for `normalize`. `None` means "follow the existing file".
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StyleOverrides {
+    pub line_ending: Option<LineEnding>,
+    pub bom: Option<bool>,
+    pub trailing_newline: Option<bool>,
+    pub as_is: bool,
+}
+
+/// What `normalize` did to the content before it was written.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct Normalization {
+    pub line_ending: Option<LineEnding>,
+    pub bom: bool,
+    pub trailing_newline: Option<bool>,
+    pub applied: Vec<&'static str>,
+}
+
+/// Returns `None` for empty text, which has no conventions to preserve.
+pub fn detect(text: &str) -> Option<TextStyle> {
+    if text.is_empty() {
+        return None;
+    }
+    let crlf = text.matches("\r\n").count();
+    let lf = text.matches('\n').count() - crlf;
+    let line_ending = match (lf, crlf) {
+        (0, 0) => None,
+        (_, 0) => Some(LineEnding::Lf),
+        (0, _) => Some(LineEnding::Crlf),
+        _ => Some(LineEnding::Mixed),
+    };
+    Some(TextStyle {
+        line_ending,
+        bom: text.starts_with(BOM),
+        trailing_newline: line_ending.map(|_| text.ends_with('\n')),
+    })
+}
+
+/// Reads `line_ending` ("auto", "lf", "crlf", "as_is"), `bom` and
+/// `trailing_newline` from tool arguments.
+pub fn parse_overrides(args: &Value) -> Result<StyleOverrides, String> {
+    let mut overrides = StyleOverrides {
+        bom: args.get("bom").and_then(|v| v.as_bool()),
+        trailing_newline: args.get("trailing_newline").and_then(|v| v.as_bool()),
+        ..StyleOverrides::default()
+    };
+    match args.get("line_ending").and_then(|v| v.as_str()).unwrap_or("auto") {
+        "auto" => {}
+        "lf" => overrides.line_ending = Some(LineEnding::Lf),
+        "crlf" => overrides.line_ending = Some(LineEnding::Crlf),
+        "as_is" => overrides.as_is = true,
+        other => {
+            return Err(format!(
+                "Unsupported line_ending: {other} (expected auto, lf, crlf or as_is)."
+            ))
+        }
+    }
+    Ok(overrides)
+}
+
+/// Converts every line break in `text` to `eol`.
+pub fn convert_line_endings(text: &str, eol: LineEnding) -> String {
+    let lf = text.replace("\r\n", "\n");
+    match eol {
+        LineEnding::Crlf => lf.replace('\n', "\r\n"),
+        _ => lf,
+    }
+}
+
+/// Rewrites `content` to follow the conventions of `existing` (the file being
+/// replaced, if any), with `overrides` taking precedence. With no existing
+/// file only explicit overrides are applied.
+pub fn normalize(
+    content: &str,
+    existing: Option<TextStyle>,
+    overrides: &StyleOverrides,
+) -> (String, Normalization) {
+    let mut report = Normalization {
+        line_ending: None,
+        bom: content.starts_with(BOM),
+        trailing_newline: None,
+        applied: Vec::new(),
+    };
+    if overrides.as_is {
+        return (content.to_string(), report);
+    }
+    let mut next = content.to_string();
+
+    let eol = overrides
+        .line_ending
+        .or_else(|| existing.and_then(|style| style.line_ending));
+    if let Some(eol) = eol {
+        report.line_ending = Some(eol);
+        if eol != LineEnding::Mixed {
+            let converted = convert_line_endings(&next, eol);
+            if converted != next {
+                report.applied.push("line_ending");
+                next = converted;
+            }
+        }
+    }
+
+    let bom = overrides.bom.or_else(|| existing.map(|style| style.bom));
+    if let Some(bom) = bom {
+        report.bom = bom;
+        if bom && !next.starts_with(BOM) {
+            next.insert(0, BOM);
+            report.applied.push("bom");
+        } else if !bom && next.starts_with(BOM) {
+            next.remove(0);
+            report.applied.push("bom");
+        }
+    }
+
+    let trailing = overrides
+        .trailing_newline
+        .or_else(|| existing.and_then(|style| style.trailing_newline));
+    if let Some(trailing) = trailing {
+        report.trailing_newline = Some(trailing);
+        let body = next.trim_start_matches(BOM);
+        if trailing && !body.is_empty() && !next.ends_with('\n') {
+            let eol = match eol {
+                Some(LineEnding::Crlf) => LineEnding::Crlf,
+                _ => detect(&next)
+                    .and_then(|style| style.line_ending)
+                    .filter(|eol| *eol == LineEnding::Crlf)
+                    .unwrap_or(LineEnding::Lf),
+            };
+            next.push_str(eol.as_str());
+            report.applied.push("trailing_newline");
+        } else if !trailing && next.ends_with('\n') {
+            next.pop();
+            if next.ends_with('\r') {
+                next.pop();
+            }
+            report.applied.push("trailing_newline");
+        }
+    }
+
+    (next, report)
+}
+
+/// Like `normalize`, but for a chunk appended to a file with style `existing`:
+/// no BOM is added mid-file and the trailing newline follows the file's end.
+pub fn normalize_append(
+    content: &str,
+    existing: Option<TextStyle>,
+    overrides: &StyleOverrides,
+) -> (String, Normalization) {
+    match existing {
+        Some(style) => {
+            let chunk_overrides = StyleOverrides {
+                bom: Some(false),
+                ..*overrides
+            };
+            let (next, mut report) = normalize(content, Some(style), &chunk_overrides);
+            report.bom = style.bom;
+            (next, report)
+        }
+        None => normalize(content, None, overrides),
+    }
+}
+
+/// Strips the BOM and converts line breaks to `\n`, the form in which models
+/// usually quote file content.
+pub fn to_logical(text: &str) -> String {
+    convert_line_endings(text.trim_start_matches(BOM), LineEnding::Lf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn follow(existing: &str, content: &str) -> (String, Normalization) {
+        normalize(content, detect(existing), &StyleOverrides::default())
+    }
+
+    #[test]
+    fn detect_reads_line_endings_bom_and_trailing_newline() {
+        assert_eq!(detect(""), None);
+        let crlf = detect("a\r\nb\r\n").unwrap();
+        assert_eq!(crlf.line_ending, Some(LineEnding::Crlf));
+        assert_eq!((crlf.bom, crlf.trailing_newline), (false, Some(true)));
+
+        let bom = detect("\u{feff}a\nb").unwrap();
+        assert_eq!(bom.line_ending, Some(LineEnding::Lf));
+        assert_eq!((bom.bom, bom.trailing_newline), (true, Some(false)));
+
+        assert_eq!(detect("a\r\nb\n").unwrap().line_ending, Some(LineEnding::Mixed));
+        let single = detect("abc").unwrap();
+        assert_eq!((single.line_ending, single.trailing_newline), (None, None));
+    }
+
+    #[test]
+    fn crlf_files_round_trip_through_logical_text() {
+        let existing = "fn a() {\r\n}\r\n";
+        let (next, report) = follow(existing, &to_logical(existing));
+        assert_eq!(next, existing);
+        assert_eq!(report.applied, vec!["line_ending"]);
+
+        let (next, _) = follow(existing, "fn b() {\n    x\n}\n");
+        assert_eq!(next, "fn b() {\r\n    x\r\n}\r\n");
+        assert_eq!(to_logical(&next), "fn b() {\n    x\n}\n");
+    }
+
+    #[test]
+    fn bom_is_preserved_or_removed() {
+        let (next, report) = follow("\u{feff}a\n", "b\n");
+        assert_eq!(next, "\u{feff}b\n");
+        assert!(report.bom);
+        assert_eq!(report.applied, vec!["bom"]);
+
+        let (next, _) = follow("a\n", "\u{feff}b\n");
+        assert_eq!(next, "b\n");
+        assert_eq!(to_logical("\u{feff}a\r\n"), "a\n");
+    }
+
+    #[test]
+    fn trailing_newline_follows_the_existing_file() {
+        assert_eq!(follow("a\n", "b\nc").0, "b\nc\n");
+        assert_eq!(follow("a\r\nb\r\n", "c\r\nd").0, "c\r\nd\r\n");
+        assert_eq!(follow("a\nb", "c\nd\n").0, "c\nd");
+        assert_eq!(follow("a\r\nb", "c\r\nd\r\n").0, "c\r\nd");
+        // A single line has no known trailing-newline convention.
+        assert_eq!(follow("abc", "x\n").0, "x\n");
+    }
+
+    #[test]
+    fn mixed_line_endings_are_left_alone() {
+        let (next, report) = follow("a\r\nb\n", "c\nd\r\n");
+        assert_eq!(next, "c\nd\r\n");
+        assert_eq!(report.line_ending, Some(LineEnding::Mixed));
+        assert!(report.applied.is_empty());
+    }
+
+    #[test]
+    fn overrides_take_precedence() {
+        let overrides = parse_overrides(&json!({ "line_ending": "lf", "bom": false, "trailing_newline": false })).unwrap();
+        let (next, report) = normalize("\u{feff}a\r\nb\r\n", detect("\u{feff}x\r\n"), &overrides);
+        assert_eq!(next, "a\nb");
+        assert_eq!(report.applied, vec!["line_ending", "bom", "trailing_newline"]);
+
+        let as_is = parse_overrides(&json!({ "line_ending": "as_is" })).unwrap();
+        assert_eq!(normalize("a\nb", detect("x\r\n"), &as_is).0, "a\nb");
+        assert!(parse_overrides(&json!({ "line_ending": "cr" })).is_err());
+
+        let (next, _) = normalize("a\nb", None, &StyleOverrides::default());
+        assert_eq!(next, "a\nb");
+    }
+
+    #[test]
+    fn appended_chunks_get_no_bom() {
+        let (next, report) = normalize_append("\u{feff}more\n", detect("\u{feff}a\r\n"), &StyleOverrides::default());
+        assert_eq!(next, "more\r\n");
+        assert!(report.bom);
+    }
+}
@@ -9,10 +9,20 @@ use uuid::Uuid;
 #[derive(Debug, Default)]
 pub struct ParsedArgs {
     pub positional: Vec<String>,
+    /// The last value given for each option.
     pub values: HashMap<String, String>,
+    /// Every value given for each option, in order, for repeatable options.
+    pub all_values: HashMap<String, Vec<String>>,
     pub flags: HashSet<String>,
 }
 
+impl ParsedArgs {
+    fn push_value(&mut self, key: &str, value: &str) {
+        self.values.insert(key.to_string(), value.to_string());
+        self.all_values.entry(key.to_string()).or_default().push(value.to_string());
+    }
+}
+
 pub fn parse_args(argv: &[String]) -> ParsedArgs {
     let mut result = ParsedArgs::default();
     let mut i = 0;
@@ -29,16 +39,14 @@ pub fn parse_args(argv: &[String]) -> ParsedArgs {
             continue;
         }
         if let Some((name, inline)) = key.split_once('=') {
-            result.values.insert(name.to_string(), inline.to_string());
+            result.push_value(name, inline);
             i += 1;
             continue;
         }
         let next = argv.get(i + 1);
         if let Some(next_val) = next {
             if !next_val.starts_with('-') {
-                result
-                    .values
-                    .insert(key.to_string(), next_val.to_string());
+                result.push_value(key, next_val);
                 i += 2;
                 continue;
             }
@@ -116,7 +124,15 @@ pub fn resolve_state_dir(server_name: &str) -> PathBuf {
     get_home_dir().join(".mcp-servers").join(normalize_name(server_name))
 }
 
-pub fn ensure_path_inside_root(root: &Path, target: &Path) -> Result<PathBuf, String> {
+/// Resolves `target` against `root` and rejects it if it leaves the root,
+/// either lexically or through a symlink. Symlinks may only point outside the
+/// root into one of `allowed_external` (canonical paths). The returned path is
+/// the lexical one, so callers can still report it relative to the root.
+pub fn ensure_path_inside_root(
+    root: &Path,
+    target: &Path,
+    allowed_external: &[PathBuf],
+) -> Result<PathBuf, String> {
     let resolved_root = normalize_path(root);
     let candidate = if target.is_absolute() {
         target.to_path_buf()
@@ -130,9 +146,56 @@ pub fn ensure_path_inside_root(root: &Path, target: &Path) -> Result<PathBuf, St
             target.display()
         ));
     }
+    let real_root = real_path(&resolved_root)?;
+    let real = real_path(&resolved)?;
+    if !real.starts_with(&real_root) && !allowed_external.iter().any(|allowed| real.starts_with(allowed)) {
+        return Err(format!(
+            "Path resolves outside workspace root through a symlink: {} -> {}",
+            target.display(),
+            real.display()
+        ));
+    }
     Ok(resolved)
 }
 
+/// Canonicalizes the existing part of `path` and appends the components that
+/// do not exist yet. Dangling symlinks are followed by hand, since writing
+/// through one creates its target.
+pub fn real_path(path: &Path) -> Result<PathBuf, String> {
+    const MAX_LINK_HOPS: usize = 40;
+    let mut current = normalize_path(path);
+    let mut hops = 0;
+    'resolve: loop {
+        let mut existing = current.as_path();
+        let mut rest = Vec::new();
+        loop {
+            if let Ok(canonical) = fs::canonicalize(existing) {
+                let mut real = canonical;
+                real.extend(rest.iter().rev());
+                return Ok(real);
+            }
+            if let Ok(link) = fs::read_link(existing) {
+                hops += 1;
+                if hops > MAX_LINK_HOPS {
+                    return Err(format!("Too many levels of symlinks: {}", path.display()));
+                }
+                let parent = existing.parent().unwrap_or(Path::new("/"));
+                let mut next = parent.join(link);
+                next.extend(rest.iter().rev());
+                current = normalize_path(&next);
+                continue 'resolve;
+            }
+            match (existing.parent(), existing.file_name()) {
+                (Some(parent), Some(name)) => {
+                    rest.push(name.to_os_string());
+                    existing = parent;
+                }
+                _ => return Ok(current),
+            }
+        }
+    }
+}
+
 pub fn is_binary_buffer(buffer: &[u8]) -> bool {
     let limit = buffer.len().min(8000);
     buffer.iter().take(limit).any(|b| *b == 0)
@@ -166,7 +229,7 @@ pub fn now_iso() -> String {
     Utc::now().to_rfc3339()
 }
 
-fn normalize_path(path: &Path) -> PathBuf {
+pub fn normalize_path(path: &Path) -> PathBuf {
     let mut components = Vec::new();
     for component in path.components() {
         match component {
@@ -190,3 +253,95 @@ fn normalize_path(path: &Path) -> PathBuf {
     }
     normalized
 }
+
+/// A fresh canonical scratch directory for tests, removed when dropped.
+#[cfg(test)]
+pub struct Scratch(pub PathBuf);
+
+#[cfg(test)]
+impl Scratch {
+    pub fn new() -> Self {
+        let dir = std::env::temp_dir().join(generate_id("scratch"));
+        fs::create_dir_all(&dir).unwrap();
+        Self(fs::canonicalize(dir).unwrap())
+    }
+
+    /// A writable `FsOps` rooted here, with 1 MiB read and write limits.
+    pub fn fs_ops(&self) -> crate::fs_ops::FsOps {
+        crate::fs_ops::FsOps::new(self.0.clone(), true, 1 << 20, 1 << 20, 100)
+    }
+}
+
+#[cfg(test)]
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::symlink;
+
+    #[test]
+    fn real_path_appends_missing_components() {
+        let scratch = Scratch::new();
+        fs::create_dir(scratch.0.join("src")).unwrap();
+        let real = real_path(&scratch.0.join("src/./new/../file.rs")).unwrap();
+        assert_eq!(real, scratch.0.join("src/file.rs"));
+        assert_eq!(real_path(&scratch.0.join("a/b")).unwrap(), scratch.0.join("a/b"));
+    }
+
+    #[test]
+    fn real_path_follows_existing_and_dangling_links() {
+        let scratch = Scratch::new();
+        fs::create_dir(scratch.0.join("target")).unwrap();
+        symlink(scratch.0.join("target"), scratch.0.join("dir_link")).unwrap();
+        symlink("missing/file.txt", scratch.0.join("dangling")).unwrap();
+        assert_eq!(
+            real_path(&scratch.0.join("dir_link/x.rs")).unwrap(),
+            scratch.0.join("target/x.rs")
+        );
+        assert_eq!(
+            real_path(&scratch.0.join("dangling")).unwrap(),
+            scratch.0.join("missing/file.txt")
+        );
+    }
+
+    #[test]
+    fn real_path_rejects_symlink_loops() {
+        let scratch = Scratch::new();
+        symlink("b", scratch.0.join("a")).unwrap();
+        symlink("a", scratch.0.join("b")).unwrap();
+        let err = real_path(&scratch.0.join("a/file")).unwrap_err();
+        assert!(err.contains("Too many levels of symlinks"), "{err}");
+    }
+
+    #[test]
+    fn ensure_path_inside_root_rejects_escapes() {
+        let scratch = Scratch::new();
+        let root = scratch.0.join("root");
+        let outside = scratch.0.join("outside");
+        fs::create_dir_all(&root).unwrap();
+        fs::create_dir_all(&outside).unwrap();
+        symlink(&outside, root.join("out")).unwrap();
+        symlink("../outside/new.txt", root.join("dangling")).unwrap();
+
+        assert_eq!(
+            ensure_path_inside_root(&root, Path::new("src/../a.rs"), &[]).unwrap(),
+            root.join("a.rs")
+        );
+        assert!(ensure_path_inside_root(&root, Path::new("../outside"), &[])
+            .unwrap_err()
+            .contains("outside workspace root"));
+        for path in ["out/file", "dangling"] {
+            let err = ensure_path_inside_root(&root, Path::new(path), &[]).unwrap_err();
+            assert!(err.contains("through a symlink"), "{path}: {err}");
+        }
+        assert_eq!(
+            ensure_path_inside_root(&root, Path::new("out/file"), &[outside]).unwrap(),
+            root.join("out/file")
+        );
+    }
+}
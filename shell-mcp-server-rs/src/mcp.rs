@@ -77,15 +77,11 @@ impl McpServer {
     }
 
     fn handle_request(&self, request: Value) -> Option<Value> {
-        let id = request.get("id").cloned();
+        let id_val = request.get("id").cloned()?;
         let method = request
             .get("method")
             .and_then(|v| v.as_str())
             .unwrap_or("");
-        if id.is_none() {
-            return None;
-        }
-        let id_val = id.unwrap();
         match method {
             "initialize" => {
                 let result = json!({
@@ -30,6 +30,7 @@ pub struct ShellExecOptions {
     pub max_output_bytes: usize,
 }
 
+#[derive(Clone, Copy)]
 enum StreamKind {
     Stdout,
     Stderr,
@@ -1,5 +1,6 @@
 use std::collections::{HashMap, HashSet};
-use std::path::{Path, PathBuf};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
 
 #[derive(Debug, Default)]
 pub struct ParsedArgs {
@@ -88,19 +89,81 @@ pub fn clamp_number(value: Option<&String>, min: i64, max: i64, fallback: i64) -
 }
 
 pub fn resolve_within_root(root: &Path, target: &str) -> PathBuf {
-    root.join(target)
+    normalize_path(&root.join(target))
 }
 
+/// True if `candidate` stays inside `root` both lexically and after
+/// resolving symlinks, so a link to `/etc` inside the workspace is rejected.
 pub fn is_subpath(root: &Path, candidate: &Path) -> bool {
-    let relative = pathdiff::diff_paths(candidate, root);
-    if let Some(rel) = relative {
-        let rel_str = rel.to_string_lossy();
-        !rel_str.starts_with("..") && !rel.is_absolute()
-    } else {
-        false
+    let relative = pathdiff::diff_paths(normalize_path(candidate), normalize_path(root));
+    let lexical = match relative {
+        Some(rel) => !rel.to_string_lossy().starts_with("..") && !rel.is_absolute(),
+        None => false,
+    };
+    lexical
+        && match (real_path(candidate), real_path(root)) {
+            (Ok(real), Ok(real_root)) => real.starts_with(real_root),
+            _ => false,
+        }
+}
+
+/// Canonicalizes the existing part of `path` and appends the components that
+/// do not exist yet. Dangling symlinks are followed by hand; a symlink loop
+/// is an error rather than a guess.
+pub fn real_path(path: &Path) -> Result<PathBuf, String> {
+    const MAX_LINK_HOPS: usize = 40;
+    let mut current = normalize_path(path);
+    let mut hops = 0;
+    'resolve: loop {
+        let mut existing = current.as_path();
+        let mut rest = Vec::new();
+        loop {
+            if let Ok(canonical) = fs::canonicalize(existing) {
+                let mut real = canonical;
+                real.extend(rest.iter().rev());
+                return Ok(real);
+            }
+            if let Ok(link) = fs::read_link(existing) {
+                hops += 1;
+                if hops > MAX_LINK_HOPS {
+                    return Err(format!("Too many levels of symlinks: {}", path.display()));
+                }
+                let parent = existing.parent().unwrap_or(Path::new("/"));
+                let mut next = parent.join(link);
+                next.extend(rest.iter().rev());
+                current = normalize_path(&next);
+                continue 'resolve;
+            }
+            match (existing.parent(), existing.file_name()) {
+                (Some(parent), Some(name)) => {
+                    rest.push(name.to_os_string());
+                    existing = parent;
+                }
+                _ => return Ok(current),
+            }
+        }
     }
 }
 
+fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !matches!(
+                    normalized.components().next_back(),
+                    None | Some(Component::RootDir) | Some(Component::Prefix(_))
+                ) {
+                    normalized.pop();
+                }
+            }
+            other => normalized.push(other.as_os_str()),
+        }
+    }
+    normalized
+}
+
 pub fn get_command_root(command: &str) -> String {
     let trimmed = command.trim();
     if trimmed.is_empty() {
@@ -130,3 +193,79 @@ pub fn format_bytes(bytes: i64) -> String {
         format!("{:.1} {}", value, units[idx])
     }
 }
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::symlink;
+
+    /// A fresh canonical scratch directory, removed when dropped.
+    struct Scratch(PathBuf);
+
+    impl Scratch {
+        fn new() -> Self {
+            let dir = std::env::temp_dir().join(format!("real-path-{}", uuid::Uuid::new_v4()));
+            fs::create_dir_all(&dir).unwrap();
+            Self(fs::canonicalize(dir).unwrap())
+        }
+    }
+
+    impl Drop for Scratch {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn real_path_appends_missing_components() {
+        let scratch = Scratch::new();
+        fs::create_dir(scratch.0.join("src")).unwrap();
+        let real = real_path(&scratch.0.join("src/./new/../file.rs")).unwrap();
+        assert_eq!(real, scratch.0.join("src/file.rs"));
+        assert_eq!(real_path(&scratch.0.join("a/b")).unwrap(), scratch.0.join("a/b"));
+    }
+
+    #[test]
+    fn real_path_follows_existing_and_dangling_links() {
+        let scratch = Scratch::new();
+        fs::create_dir(scratch.0.join("target")).unwrap();
+        symlink(scratch.0.join("target"), scratch.0.join("dir_link")).unwrap();
+        symlink("missing/file.txt", scratch.0.join("dangling")).unwrap();
+        assert_eq!(
+            real_path(&scratch.0.join("dir_link/x.rs")).unwrap(),
+            scratch.0.join("target/x.rs")
+        );
+        assert_eq!(
+            real_path(&scratch.0.join("dangling")).unwrap(),
+            scratch.0.join("missing/file.txt")
+        );
+    }
+
+    #[test]
+    fn real_path_rejects_symlink_loops() {
+        let scratch = Scratch::new();
+        symlink("b", scratch.0.join("a")).unwrap();
+        symlink("a", scratch.0.join("b")).unwrap();
+        let err = real_path(&scratch.0.join("a/file")).unwrap_err();
+        assert!(err.contains("Too many levels of symlinks"), "{err}");
+        assert!(!is_subpath(&scratch.0, &scratch.0.join("a/file")));
+    }
+
+    #[test]
+    fn is_subpath_rejects_escapes() {
+        let scratch = Scratch::new();
+        let root = scratch.0.join("root");
+        let outside = scratch.0.join("outside");
+        fs::create_dir_all(&root).unwrap();
+        fs::create_dir_all(&outside).unwrap();
+        symlink(&outside, root.join("out")).unwrap();
+        symlink("../outside/new.txt", root.join("dangling")).unwrap();
+
+        assert!(is_subpath(&root, &root.join("src/../a.rs")));
+        assert!(is_subpath(&root, &root.join("new/dir")));
+        assert!(!is_subpath(&root, &root.join("../outside")));
+        assert!(!is_subpath(&root, &root.join("out/file")));
+        assert!(!is_subpath(&root, &root.join("dangling")));
+        assert_eq!(resolve_within_root(&root, "src/../a.rs"), root.join("a.rs"));
+    }
+}